@@ -0,0 +1,366 @@
+use crate::binding::renderd_protocol::{
+    protoCmd,
+    protoCmd_cmdDone,
+    protoCmd_cmdIgnore,
+    protoCmd_cmdNotDone,
+    protocol,
+    protocol_v2,
+};
+use crate::schema::renderd::error::ProtocolError;
+use crate::schema::renderd::request::{RenderRequest, RenderRequestVersion,};
+use crate::schema::renderd::response::RenderResponseCommand;
+use crate::schema::tile::identity::{LayerName, TileIdentity,};
+
+use std::mem::size_of;
+use std::os::raw::{c_char, c_int,};
+use std::ptr;
+use std::result::Result;
+use std::slice;
+use std::string::String;
+use std::vec::Vec;
+
+
+// Every version starts with the fields of the v2 struct, which include the version of the whole frame
+pub const HEADER_SIZE: usize = size_of::<protocol_v2>();
+
+// renderd answers with a struct of the same version as the request, so one frame is the same size either way
+pub fn frame_size(request: &RenderRequest) -> usize {
+    match request {
+        RenderRequest::V2(_) => size_of::<protocol_v2>(),
+        RenderRequest::V3(_) => size_of::<protocol>(),
+    }
+}
+
+pub fn encode(request: &RenderRequest) -> Vec<u8> {
+    match request {
+        RenderRequest::V2(value) => as_bytes(value).to_vec(),
+        RenderRequest::V3(value) => as_bytes(value).to_vec(),
+    }
+}
+
+pub fn decode(
+    request: &RenderRequest,
+    frame: &[u8],
+) -> Result<RenderResponseCommand, ProtocolError> {
+    let expected = frame_size(request);
+    if frame.len() != expected {
+        return Err(
+            ProtocolError::InvalidLength {
+                expected,
+                actual: frame.len(),
+            }
+        );
+    }
+    match request {
+        RenderRequest::V2(sent) => {
+            let received: protocol_v2 = from_bytes(frame);
+            check_tile(
+                (sent.ver, sent.x, sent.y, sent.z, &sent.xmlname),
+                (received.ver, received.x, received.y, received.z, &received.xmlname),
+            )?;
+            as_response_command(received.cmd)
+        },
+        RenderRequest::V3(sent) => {
+            let received: protocol = from_bytes(frame);
+            check_tile(
+                (sent.ver, sent.x, sent.y, sent.z, &sent.xmlname),
+                (received.ver, received.x, received.y, received.z, &received.xmlname),
+            )?;
+            as_response_command(received.cmd)
+        },
+    }
+}
+
+// The v2 struct is a prefix of the v3 struct, so the tile can be read from a frame of either version
+pub fn frame_tile(frame: &[u8]) -> Result<TileIdentity, ProtocolError> {
+    let header = read_header(frame)?;
+    // Only a v3 frame carries the style parameter
+    let parameter = if frame.len() >= size_of::<protocol>() {
+        let full: protocol = from_bytes(frame);
+        Some(c_str_to_string(&full.options)).filter(|parameter| !parameter.is_empty())
+    } else {
+        None
+    };
+    Ok(
+        TileIdentity {
+            x: header.x,
+            y: header.y,
+            z: header.z,
+            layer: LayerName::from(c_str_to_string(&header.xmlname).as_str()),
+            parameter,
+        }
+    )
+}
+
+pub fn frame_version(frame: &[u8]) -> Result<c_int, ProtocolError> {
+    Ok(read_header(frame)?.ver)
+}
+
+// The number of bytes that follow the header, going by the version in the header
+pub fn remaining_frame_size(header: &[u8]) -> Result<usize, ProtocolError> {
+    match read_header(header)?.ver {
+        version if version == RenderRequestVersion::Two as c_int => Ok(0),
+        version if version == RenderRequestVersion::Three as c_int => Ok(size_of::<protocol>() - HEADER_SIZE),
+        version => Err(ProtocolError::VersionMismatch(version)),
+    }
+}
+
+// renderd before v3 does not know the style parameter or mime type, so those are dropped
+pub fn downgrade_to_v2(frame: &[u8]) -> Vec<u8> {
+    let mut header: protocol_v2 = from_bytes(frame);
+    header.ver = RenderRequestVersion::Two as c_int;
+    as_bytes(&header).to_vec()
+}
+
+pub fn frame_command(frame: &[u8]) -> Result<RenderResponseCommand, ProtocolError> {
+    as_response_command(read_header(frame)?.cmd)
+}
+
+// A reply in another version than the request is rebuilt in the request's version,
+// keeping the tile and command from the reply
+pub fn in_version_of(
+    request: &[u8],
+    reply: &[u8],
+) -> Result<Vec<u8>, ProtocolError> {
+    let mut header = read_header(reply)?;
+    header.ver = read_header(request)?.ver;
+    let mut result = request.to_vec();
+    result[..HEADER_SIZE].copy_from_slice(as_bytes(&header));
+    return Ok(result);
+}
+
+fn read_header(frame: &[u8]) -> Result<protocol_v2, ProtocolError> {
+    if frame.len() < size_of::<protocol_v2>() {
+        return Err(
+            ProtocolError::InvalidLength {
+                expected: size_of::<protocol_v2>(),
+                actual: frame.len(),
+            }
+        );
+    }
+    Ok(from_bytes(frame))
+}
+
+type TileFields<'f> = (c_int, c_int, c_int, c_int, &'f [c_char]);
+
+// renderd echoes the tile it was asked for, so a reply for any other tile is not the answer to this request
+fn check_tile(
+    sent: TileFields,
+    received: TileFields,
+) -> Result<(), ProtocolError> {
+    let (sent_ver, sent_x, sent_y, sent_z, sent_xmlname) = sent;
+    let (received_ver, received_x, received_y, received_z, received_xmlname) = received;
+    if sent_ver != received_ver {
+        return Err(ProtocolError::VersionMismatch(received_ver));
+    }
+    if (sent_x, sent_y, sent_z) != (received_x, received_y, received_z)
+        || c_str_field(sent_xmlname) != c_str_field(received_xmlname) {
+        return Err(ProtocolError::TileMismatch);
+    }
+    return Ok(());
+}
+
+fn as_response_command(cmd: protoCmd) -> Result<RenderResponseCommand, ProtocolError> {
+    #[allow(non_upper_case_globals)]
+    match cmd {
+        protoCmd_cmdDone => Ok(RenderResponseCommand::Done),
+        protoCmd_cmdNotDone => Ok(RenderResponseCommand::NotDone),
+        protoCmd_cmdIgnore => Ok(RenderResponseCommand::InvalidRequestIgnored),
+        _ => Err(ProtocolError::InvalidCommand(cmd)),
+    }
+}
+
+// Bytes after the null terminator are not significant, renderd does not clear them
+fn c_str_field(field: &[c_char]) -> &[c_char] {
+    let end = field.iter().position(|c| *c == 0).unwrap_or(field.len());
+    &field[..end]
+}
+
+fn c_str_to_string(field: &[c_char]) -> String {
+    let bytes: Vec<u8> = c_str_field(field).iter().map(|c| *c as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+    // The protocol structs are repr(C) plain data, so their bytes are the wire format
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+fn from_bytes<T: Copy>(frame: &[u8]) -> T {
+    assert!(frame.len() >= size_of::<T>());
+    // Every bit pattern is a valid protocol struct and the frame has no alignment guarantee
+    unsafe { ptr::read_unaligned(frame.as_ptr() as *const T) }
+}
+
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    // Builds the reply renderd would have sent for the request frame
+    pub fn with_command(
+        frame: &[u8],
+        command: RenderResponseCommand,
+    ) -> Vec<u8> {
+        let mut header: protocol_v2 = from_bytes(frame);
+        header.cmd = command as protoCmd;
+        let mut result = frame.to_vec();
+        result[..size_of::<protocol_v2>()].copy_from_slice(as_bytes(&header));
+        return result;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_utils::with_command;
+    use crate::schema::renderd::request::{
+        MAX_LAYER_NAME_LEN, MAX_MIME_TYPE_LEN, MAX_OPTIONS_LEN, RenderRequestCommand,
+    };
+
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+
+    fn make_request() -> protocol {
+        let mut result = protocol {
+            ver: RenderRequestVersion::Three as c_int,
+            cmd: RenderRequestCommand::Render as protoCmd,
+            x: 1,
+            y: 2,
+            z: 3,
+            xmlname: [0; MAX_LAYER_NAME_LEN + 1],
+            mimetype: [0; MAX_MIME_TYPE_LEN + 1],
+            options: [0; MAX_OPTIONS_LEN + 1],
+        };
+        for (index, byte) in b"osm".iter().enumerate() {
+            result.xmlname[index] = *byte as c_char;
+        }
+        return result;
+    }
+
+    #[test]
+    fn test_encode_writes_one_struct() -> Result<(), Box<dyn StdError>> {
+        let request = RenderRequest::V3(make_request());
+        assert_eq!(size_of::<protocol>(), encode(&request).len(), "Incorrect v3 frame size");
+        let mut v2 = protocol_v2 {
+            ver: RenderRequestVersion::Two as c_int,
+            cmd: RenderRequestCommand::Render as protoCmd,
+            x: 1,
+            y: 2,
+            z: 3,
+            xmlname: [0; MAX_LAYER_NAME_LEN + 1],
+        };
+        v2.xmlname[0] = b'a' as c_char;
+        assert_eq!(size_of::<protocol_v2>(), encode(&RenderRequest::V2(v2)).len(), "Incorrect v2 frame size");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_matching_response() -> Result<(), Box<dyn StdError>> {
+        let request = RenderRequest::V3(make_request());
+        let mut response = make_request();
+        response.cmd = protoCmd_cmdDone;
+        // Leftover bytes after the terminator must not cause a mismatch
+        response.xmlname[10] = b'x' as c_char;
+        let command = decode(&request, &encode(&RenderRequest::V3(response)))?;
+        assert_eq!(RenderResponseCommand::Done, command, "Incorrect response command");
+        response.cmd = protoCmd_cmdNotDone;
+        let command = decode(&request, &encode(&RenderRequest::V3(response)))?;
+        assert_eq!(RenderResponseCommand::NotDone, command, "Incorrect response command");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reply_for_request_frame() -> Result<(), Box<dyn StdError>> {
+        let request = encode(&RenderRequest::V3(make_request()));
+        let tile = frame_tile(&request)?;
+        assert_eq!((1, 2, 3, "osm"), (tile.x, tile.y, tile.z, tile.layer.as_str()), "Incorrect frame tile");
+        let reply = with_command(&request, RenderResponseCommand::Done);
+        assert_eq!(request.len(), reply.len(), "Incorrect reply size");
+        assert_eq!(RenderResponseCommand::Done, frame_command(&reply)?, "Incorrect reply command");
+        assert_eq!(
+            RenderResponseCommand::Done,
+            decode(&RenderRequest::V3(make_request()), &reply)?,
+            "Reply does not match the request"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_downgrade_to_v2() -> Result<(), Box<dyn StdError>> {
+        let request = encode(&RenderRequest::V3(make_request()));
+        assert_eq!(size_of::<protocol>() - HEADER_SIZE, remaining_frame_size(&request)?, "Incorrect v3 frame size");
+        let downgraded = downgrade_to_v2(&request);
+        assert_eq!(HEADER_SIZE, downgraded.len(), "Incorrect v2 frame size");
+        assert_eq!(0, remaining_frame_size(&downgraded)?, "Incorrect v2 frame size");
+        assert_eq!(RenderRequestVersion::Two as c_int, frame_version(&downgraded)?, "Failed to change the version");
+        let tile = frame_tile(&downgraded)?;
+        assert_eq!((1, 2, 3, "osm"), (tile.x, tile.y, tile.z, tile.layer.as_str()), "Failed to keep the tile");
+        let mut unknown = make_request();
+        unknown.ver = 7;
+        assert_eq!(
+            Err(ProtocolError::VersionMismatch(7)),
+            remaining_frame_size(&encode(&RenderRequest::V3(unknown))),
+            "Failed to reject an unknown version"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reply_in_version_of_request() -> Result<(), Box<dyn StdError>> {
+        let request = encode(&RenderRequest::V3(make_request()));
+        let mut other_tile = make_request();
+        other_tile.x = 6;
+        let reply = with_command(&downgrade_to_v2(&encode(&RenderRequest::V3(other_tile))), RenderResponseCommand::Done);
+        let converted = in_version_of(&request, &reply)?;
+        assert_eq!(request.len(), converted.len(), "Incorrect reply size");
+        assert_eq!(RenderRequestVersion::Three as c_int, frame_version(&converted)?, "Failed to change the version");
+        assert_eq!(RenderResponseCommand::Done, frame_command(&converted)?, "Failed to keep the command");
+        assert_eq!(6, frame_tile(&converted)?.x, "Failed to keep the tile of the reply");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_response() -> Result<(), Box<dyn StdError>> {
+        let request = RenderRequest::V3(make_request());
+        let frame = encode(&request);
+        assert_eq!(
+            Err(ProtocolError::InvalidLength { expected: frame.len(), actual: frame.len() - 1 }),
+            decode(&request, &frame[1..]),
+            "Failed to reject a short response"
+        );
+        let mut response = make_request();
+        response.cmd = protoCmd_cmdDone;
+        response.ver = RenderRequestVersion::Two as c_int;
+        assert_eq!(
+            Err(ProtocolError::VersionMismatch(2)),
+            decode(&request, &encode(&RenderRequest::V3(response))),
+            "Failed to reject a different version"
+        );
+        let mut response = make_request();
+        response.cmd = RenderRequestCommand::RenderBulk as protoCmd;
+        assert_eq!(
+            Err(ProtocolError::InvalidCommand(RenderRequestCommand::RenderBulk as protoCmd)),
+            decode(&request, &encode(&RenderRequest::V3(response))),
+            "Failed to reject a request command"
+        );
+        let mut response = make_request();
+        response.cmd = protoCmd_cmdDone;
+        response.y = 7;
+        assert_eq!(
+            Err(ProtocolError::TileMismatch),
+            decode(&request, &encode(&RenderRequest::V3(response))),
+            "Failed to reject a response for another tile"
+        );
+        let mut response = make_request();
+        response.cmd = protoCmd_cmdDone;
+        response.xmlname[0] = b'x' as c_char;
+        assert_eq!(
+            Err(ProtocolError::TileMismatch),
+            decode(&request, &encode(&RenderRequest::V3(response))),
+            "Failed to reject a response for another layer"
+        );
+        Ok(())
+    }
+}
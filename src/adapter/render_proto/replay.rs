@@ -0,0 +1,481 @@
+use crate::binding::apache2::request_rec;
+use crate::binding::renderd_protocol::{protoCmd, protocol};
+use crate::schema::apache2::config::ModuleConfig;
+use crate::schema::http::request::HttpRequest;
+use crate::schema::renderd::request::RenderRequestCommand;
+use crate::schema::slippy::request::{BodyVariant, ServeTileRequest};
+use crate::adapter::slippy::interface::ReadContext;
+use crate::adapter::slippy::reader::SlippyRequestParser;
+use crate::framework::apache2::context::HostContext;
+use crate::service::rendering::interface::create_request;
+use crate::use_case::tile::requested_tile;
+
+use chrono::Utc;
+
+use std::ffi::CStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::string::String;
+use std::vec::Vec;
+
+
+const MOD_TILE_LOG_MARKER: &str = "-mod_tile-log-";
+const RENDERD_LOG_MARKER: &str = "-renderd-log-";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CapturedRequest {
+    DescribeLayer {
+        layer: String,
+    },
+    ServeTile(CapturedTile),
+    Unmatched,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedTile {
+    pub version: i32,
+    pub parameter: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub extension: String,
+    pub option: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedCommand {
+    pub version: i32,
+    pub command: String,
+    pub layer: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub mime: String,
+    pub options: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+    pub name: String,
+    pub uri: String,
+    pub request: CapturedRequest,
+    pub commands: Vec<CapturedCommand>,
+}
+
+impl Capture {
+    // Pairs each mod_tile log with the renderd log of the same name, if one was captured
+    pub fn load_all(capture_dir: &Path) -> io::Result<Vec<Capture>> {
+        let mut mod_tile_logs: Vec<PathBuf> = fs::read_dir(capture_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| file_name(path).contains(MOD_TILE_LOG_MARKER))
+            .collect();
+        mod_tile_logs.sort();
+        let mut captures = Vec::new();
+        for mod_tile_log in mod_tile_logs {
+            let renderd_log = capture_dir.join(file_name(&mod_tile_log).replace(MOD_TILE_LOG_MARKER, RENDERD_LOG_MARKER));
+            let renderd_text = if renderd_log.exists() {
+                fs::read_to_string(&renderd_log)?
+            } else {
+                String::new()
+            };
+            let name = file_name(&mod_tile_log);
+            let name = name.split(MOD_TILE_LOG_MARKER).next().unwrap_or_default();
+            captures.push(
+                Capture::parse(name, &fs::read_to_string(&mod_tile_log)?, &renderd_text)?
+            );
+        }
+        return Ok(captures);
+    }
+
+    pub fn parse(
+        name: &str,
+        mod_tile_log: &str,
+        renderd_log: &str,
+    ) -> io::Result<Capture> {
+        let mut uri = None;
+        let mut request = CapturedRequest::Unmatched;
+        for line in mod_tile_log.lines() {
+            if let Some(value) = field(line, "tile_translate: uri") {
+                uri = Some(value.to_string());
+            } else if let Some(index) = line.find("tile_translate: matched request v") {
+                request = CapturedRequest::ServeTile(parse_matched_request(&line[index..])?);
+            } else if let Some(index) = line.find("Requesting tileJSON for tilelayer ") {
+                let layer = &line[(index + "Requesting tileJSON for tilelayer ".len())..];
+                request = CapturedRequest::DescribeLayer { layer: layer.trim().to_string() };
+            }
+        }
+        let mut commands = Vec::new();
+        let mut version = None;
+        for line in renderd_log.lines() {
+            if let Some(index) = line.find("Got incoming request with protocol version ") {
+                let value = &line[(index + "Got incoming request with protocol version ".len())..];
+                version = Some(parse_number(value.trim())?);
+            } else if line.contains("Got command ") {
+                let version = version.take().ok_or_else(|| invalid_capture(line))?;
+                commands.push(parse_command(version, line)?);
+            }
+        }
+        let uri = uri.ok_or_else(|| invalid_capture(name))?;
+        return Ok(
+            Capture {
+                name: name.to_string(),
+                uri,
+                request,
+                commands,
+            }
+        );
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub capture: String,
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: expected {:?} but was {:?}", self.capture, self.field, self.expected, self.actual)
+    }
+}
+
+// Runs the captured URI through the request parser and builds the render request the way the tile handler does,
+// then reports every field where the result differs from what mod_tile and renderd logged
+pub fn replay(
+    module_config: &ModuleConfig,
+    record: &request_rec,
+    capture: &Capture,
+) -> Vec<Divergence> {
+    let mut report = DivergenceReport {
+        capture: &capture.name,
+        divergences: Vec::new(),
+    };
+    let read_context = ReadContext {
+        host_context: HostContext::new(module_config, record),
+    };
+    let request = HttpRequest::new(&capture.uri, Utc::now(), record);
+    let (actual_request, actual_commands) = match SlippyRequestParser::parse(&read_context, &request, &capture.uri) {
+        Ok(slippy_request) => match &slippy_request.body {
+            BodyVariant::DescribeLayer => (
+                CapturedRequest::DescribeLayer { layer: slippy_request.header.layer.to_string() },
+                Vec::new(),
+            ),
+            BodyVariant::ServeTile(body) => {
+                let tile_id = requested_tile(&slippy_request.header, body);
+                let commands = match create_request(&module_config.renderd, &tile_id, body) {
+                    Ok(proto) => vec![to_captured_command(&proto)],
+                    Err(error) => {
+                        report.diverge("render request", "a request", &error.to_string());
+                        Vec::new()
+                    },
+                };
+                (CapturedRequest::ServeTile(to_captured_tile(body)), commands)
+            },
+            _ => (CapturedRequest::Unmatched, Vec::new()),
+        },
+        Err(_) => (CapturedRequest::Unmatched, Vec::new()),
+    };
+    report.compare_request(&capture.request, &actual_request);
+    report.compare_commands(&capture.commands, &actual_commands);
+    return report.divergences;
+}
+
+struct DivergenceReport<'c> {
+    capture: &'c str,
+    divergences: Vec<Divergence>,
+}
+
+impl<'c> DivergenceReport<'c> {
+    fn diverge(
+        &mut self,
+        field: &str,
+        expected: &str,
+        actual: &str,
+    ) -> () {
+        self.divergences.push(
+            Divergence {
+                capture: self.capture.to_string(),
+                field: field.to_string(),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            }
+        );
+    }
+
+    fn compare<T: fmt::Display + PartialEq>(
+        &mut self,
+        field: &str,
+        expected: &T,
+        actual: &T,
+    ) -> () {
+        if expected != actual {
+            self.diverge(field, &expected.to_string(), &actual.to_string());
+        }
+    }
+
+    fn compare_request(
+        &mut self,
+        expected: &CapturedRequest,
+        actual: &CapturedRequest,
+    ) -> () {
+        match (expected, actual) {
+            (
+                CapturedRequest::DescribeLayer { layer: expected_layer },
+                CapturedRequest::DescribeLayer { layer: actual_layer },
+            ) => {
+                self.compare("layer", expected_layer, actual_layer);
+            },
+            (CapturedRequest::ServeTile(expected_tile), CapturedRequest::ServeTile(actual_tile)) => {
+                self.compare("request version", &expected_tile.version, &actual_tile.version);
+                self.compare("parameter", &expected_tile.parameter, &actual_tile.parameter);
+                self.compare("x", &expected_tile.x, &actual_tile.x);
+                self.compare("y", &expected_tile.y, &actual_tile.y);
+                self.compare("z", &expected_tile.z, &actual_tile.z);
+                self.compare("extension", &expected_tile.extension, &actual_tile.extension);
+                self.compare(
+                    "option",
+                    &expected_tile.option.clone().unwrap_or_default(),
+                    &actual_tile.option.clone().unwrap_or_default(),
+                );
+            },
+            (expected, actual) if expected != actual => {
+                self.diverge("request", &format!("{:?}", expected), &format!("{:?}", actual));
+            },
+            _ => (),
+        }
+    }
+
+    fn compare_commands(
+        &mut self,
+        expected: &[CapturedCommand],
+        actual: &[CapturedCommand],
+    ) -> () {
+        if expected.len() != actual.len() {
+            self.compare("render command count", &expected.len(), &actual.len());
+            return;
+        }
+        for (expected_command, actual_command) in expected.iter().zip(actual.iter()) {
+            self.compare("protocol version", &expected_command.version, &actual_command.version);
+            self.compare("command", &expected_command.command, &actual_command.command);
+            self.compare("xml", &expected_command.layer, &actual_command.layer);
+            self.compare("render x", &expected_command.x, &actual_command.x);
+            self.compare("render y", &expected_command.y, &actual_command.y);
+            self.compare("render z", &expected_command.z, &actual_command.z);
+            self.compare("mime", &expected_command.mime, &actual_command.mime);
+            self.compare("options", &expected_command.options, &actual_command.options);
+        }
+    }
+}
+
+fn to_captured_tile(body: &ServeTileRequest) -> CapturedTile {
+    match body {
+        ServeTileRequest::V2(body) => CapturedTile {
+            version: 2,
+            parameter: String::new(),
+            x: body.x,
+            y: body.y,
+            z: body.z,
+            extension: body.extension.clone(),
+            option: body.option.clone(),
+        },
+        ServeTileRequest::V3(body) => CapturedTile {
+            version: 3,
+            parameter: body.parameter.clone(),
+            x: body.x,
+            y: body.y,
+            z: body.z,
+            extension: body.extension.clone(),
+            option: body.option.clone(),
+        },
+    }
+}
+
+fn to_captured_command(proto: &protocol) -> CapturedCommand {
+    CapturedCommand {
+        version: proto.ver,
+        command: command_name(proto.cmd),
+        layer: c_string(&proto.xmlname),
+        x: proto.x,
+        y: proto.y,
+        z: proto.z,
+        mime: c_string(&proto.mimetype),
+        options: c_string(&proto.options),
+    }
+}
+
+// Matches the names renderd uses when it logs a command
+fn command_name(cmd: protoCmd) -> String {
+    let name = match cmd {
+        _ if cmd == RenderRequestCommand::Render as protoCmd => "Render",
+        _ if cmd == RenderRequestCommand::Dirty as protoCmd => "Dirty",
+        _ if cmd == RenderRequestCommand::RenderPriority as protoCmd => "RenderPrio",
+        _ if cmd == RenderRequestCommand::RenderBulk as protoCmd => "RenderBulk",
+        _ if cmd == RenderRequestCommand::RenderLow as protoCmd => "RenderLow",
+        _ => return format!("Unknown({})", cmd),
+    };
+    return name.to_string();
+}
+
+fn c_string(value: &[c_char]) -> String {
+    let value = unsafe { CStr::from_ptr(value.as_ptr()) };
+    return value.to_string_lossy().into_owned();
+}
+
+// Parses e.g. "matched request v3 - parameters=global, z=0, x=0, y=0, extension=png, option=dirty"
+fn parse_matched_request(line: &str) -> io::Result<CapturedTile> {
+    let (prefix, fields) = line.split_once(" - ").ok_or_else(|| invalid_capture(line))?;
+    let version = parse_number(prefix.rsplit('v').next().unwrap_or_default())?;
+    let mut tile = CapturedTile {
+        version,
+        parameter: String::new(),
+        x: 0,
+        y: 0,
+        z: 0,
+        extension: String::new(),
+        option: None,
+    };
+    for pair in fields.split(", ") {
+        let (key, value) = pair.split_once('=').ok_or_else(|| invalid_capture(line))?;
+        let value = value.trim();
+        match key {
+            "parameters" => tile.parameter = value.to_string(),
+            "x" => tile.x = parse_number(value)?,
+            "y" => tile.y = parse_number(value)?,
+            "z" => tile.z = parse_number(value)?,
+            "extension" => tile.extension = value.to_string(),
+            "option" if !value.is_empty() => tile.option = Some(value.to_string()),
+            _ => (),
+        }
+    }
+    return Ok(tile);
+}
+
+// Parses e.g. "Got command Dirty fd(5) xml(default), z(0), x(0), y(0), mime(image/png), options(global)"
+fn parse_command(
+    version: i32,
+    line: &str,
+) -> io::Result<CapturedCommand> {
+    let missing = || invalid_capture(line);
+    let after_command = &line[(line.find("Got command ").ok_or_else(missing)? + "Got command ".len())..];
+    let command = after_command.split_whitespace().next().ok_or_else(missing)?;
+    return Ok(
+        CapturedCommand {
+            version,
+            command: command.to_string(),
+            layer: field(line, " xml").ok_or_else(missing)?.to_string(),
+            x: parse_number(field(line, " x").ok_or_else(missing)?)?,
+            y: parse_number(field(line, " y").ok_or_else(missing)?)?,
+            z: parse_number(field(line, " z").ok_or_else(missing)?)?,
+            mime: field(line, " mime").ok_or_else(missing)?.to_string(),
+            options: field(line, " options").ok_or_else(missing)?.to_string(),
+        }
+    );
+}
+
+// Extracts the value of a "name(value)" field from a log line
+fn field<'l>(
+    line: &'l str,
+    name: &str,
+) -> Option<&'l str> {
+    let start = line.find(&format!("{}(", name))? + name.len() + 1;
+    let end = line[start..].find(')')? + start;
+    return Some(&line[start..end]);
+}
+
+fn parse_number(value: &str) -> io::Result<i32> {
+    return value.parse::<i32>().map_err(|_| invalid_capture(value));
+}
+
+fn invalid_capture(context: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, format!("Unrecognised capture: {}", context));
+}
+
+fn file_name(path: &Path) -> String {
+    return path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+    use crate::schema::apache2::config::ANY_PARAMETER;
+    use crate::schema::tile::identity::LayerName;
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+
+    // Behaviour the Rust implementation does not yet share with the C implementation
+    const KNOWN_DIVERGENCES: &[&str] = &[
+        // mod_tile asks renderd to prioritise tiles a client is waiting on
+        "v2_request command: expected \"RenderPrio\" but was \"Render\"",
+        // mod_tile sends a request without a style parameter in protocol version 2, while the tile handler always uses 3
+        "v2_request protocol version: expected \"2\" but was \"3\"",
+        // mod_tile translates the extension into the mime type of the layer
+        "v2_request mime: expected \"image/png\" but was \"png\"",
+        "v3_request mime: expected \"image/png\" but was \"png\"",
+        // mod_tile handles the dirty option by marking the tile dirty instead of rendering it
+        "v3_request command: expected \"Dirty\" but was \"Render\"",
+    ];
+
+    fn capture_dir() -> PathBuf {
+        let mut capture_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        capture_dir.push("resources/capture");
+        return capture_dir;
+    }
+
+    #[test]
+    fn test_parse_captures() -> Result<(), Box<dyn StdError>> {
+        let captures = Capture::load_all(&capture_dir())?;
+        let names: Vec<&str> = captures.iter().map(|capture| capture.name.as_str()).collect();
+        assert_eq!(vec!["json_request", "v2_request", "v3_request"], names, "Failed to load the captures");
+        let v3_capture = &captures[2];
+        assert_eq!("/osm/global/0/0/0.png/dirty", v3_capture.uri, "Incorrect URI");
+        let expected_tile = CapturedTile {
+            version: 3,
+            parameter: String::from("global"),
+            x: 0,
+            y: 0,
+            z: 0,
+            extension: String::from("png"),
+            option: Some(String::from("dirty")),
+        };
+        assert_eq!(CapturedRequest::ServeTile(expected_tile), v3_capture.request, "Incorrect request");
+        let expected_command = CapturedCommand {
+            version: 3,
+            command: String::from("Dirty"),
+            layer: String::from("default"),
+            x: 0,
+            y: 0,
+            z: 0,
+            mime: String::from("image/png"),
+            options: String::from("global"),
+        };
+        assert_eq!(vec![expected_command], v3_capture.commands, "Incorrect render commands");
+        assert!(captures[0].commands.is_empty(), "Incorrect render commands for a tileJSON request");
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_captures() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let mut module_config = ModuleConfig::new();
+            let layer_config = module_config.layers.get_mut(&LayerName::from("default")).unwrap();
+            layer_config.parameters_allowed = true;
+            layer_config.allowed_parameters = vec![String::from(ANY_PARAMETER)];
+            let mut divergences = Vec::new();
+            for capture in Capture::load_all(&capture_dir())? {
+                divergences.extend(replay(&module_config, record, &capture).iter().map(|divergence| divergence.to_string()));
+            }
+            let mut expected: Vec<String> = KNOWN_DIVERGENCES.iter().map(|divergence| divergence.to_string()).collect();
+            expected.sort();
+            divergences.sort();
+            assert_eq!(expected, divergences, "Incorrect divergence from the C implementation");
+            Ok(())
+        })
+    }
+}
@@ -109,8 +109,8 @@ fn set_xmlname<P: HasXmlNameField>(
 ) -> Result<(), InvalidParameterError> {
     let layer_as_i8_slice = unsafe {
         // on x86_64 c_char is aliased to i8
-        core::slice::from_raw_parts_mut(
-            header.layer.as_u8().as_ptr() as *mut i8,
+        core::slice::from_raw_parts(
+            header.layer.as_u8().as_ptr() as *const i8,
             header.layer.len(),
         )
     };
@@ -135,8 +135,8 @@ fn set_mimetype(
     }
     let extension_as_i8_slice = unsafe {
         // on x86_64 c_char is aliased to i8
-        core::slice::from_raw_parts_mut(
-            from.extension.as_bytes().as_ptr() as *mut i8,
+        core::slice::from_raw_parts(
+            from.extension.as_bytes().as_ptr() as *const i8,
             from.extension.len(),
         )
     };
@@ -149,26 +149,27 @@ fn set_options(
     from: &ServeTileRequestV3,
     to: &mut protocol,
 ) -> Result<(), InvalidParameterError> {
-    if let Some(options_value) = &from.option {
+    // renderd reads the style parameter from the options field, while the trailing option is a mod_tile command
+    if !from.parameter.is_empty() {
         const _OPTIONS_LIMIT: usize = size_of_return_type(|p: protocol| p.options);
-        if options_value.len() >= _OPTIONS_LIMIT {
+        if from.parameter.len() >= _OPTIONS_LIMIT {
             return Err(
                 InvalidParameterError {
                     param: "options".to_string(),
-                    value: options_value.clone(),
+                    value: from.parameter.clone(),
                     reason: format!("Options parameter must be less than {}", _OPTIONS_LIMIT),
                 }
             )
         }
-        let options_as_i8_slice = unsafe {
+        let parameter_as_i8_slice = unsafe {
             // on x86_64 c_char is aliased to i8
-            core::slice::from_raw_parts_mut(
-                from.extension.as_bytes().as_ptr() as *mut i8,
-                from.extension.len(),
+            core::slice::from_raw_parts(
+                from.parameter.as_bytes().as_ptr() as *const i8,
+                from.parameter.len(),
             )
         };
-        to.options.as_mut_slice()[..options_value.len()].copy_from_slice(options_as_i8_slice);
-        to.options.as_mut_slice()[options_value.len()] = 0;  // C string null terminator
+        to.options.as_mut_slice()[..from.parameter.len()].copy_from_slice(parameter_as_i8_slice);
+        to.options.as_mut_slice()[from.parameter.len()] = 0;  // C string null terminator
     }
     Ok(())
 }
@@ -1,5 +1,8 @@
 use crate::schema::apache2::config::ModuleConfig;
+use crate::schema::apache2::error::InvalidConfigError;
 use crate::schema::apache2::virtual_host::VirtualHost;
+use crate::schema::core::processed::ProcessOutcome;
+use crate::schema::handler::error::HandleError;
 use crate::schema::http::request::HttpRequest;
 use crate::schema::http::response::HttpResponse;
 use crate::schema::slippy::error::{ReadError, WriteError,};
@@ -64,6 +67,54 @@ pub trait WriteResponseObserver {
     ) -> ();
 }
 
+// Middleware is built from the module config so that a reload can rebuild it,
+// and each thread handling requests builds its own stages
+pub type MiddlewareFactory = dyn Fn(&ModuleConfig) -> Result<Box<dyn RequestMiddleware>, InvalidConfigError> + Send + Sync;
+
+// A stage wraps the whole pipeline. On the way in it sees the result of reading the request and may
+// short-circuit the handlers with a response of its own. On the way out the stages it was entered through
+// see the handle result, may decorate the response before it is written, and see the result of writing it.
+pub trait RequestMiddleware {
+    fn after_read(
+        &mut self,
+        _context: &HostContext,
+        _read_result: &mut Result<SlippyRequest, ReadError>,
+    ) -> () {
+    }
+
+    fn before_handle(
+        &mut self,
+        _context: &HostContext,
+        _request: &SlippyRequest,
+    ) -> ProcessOutcome<SlippyResponse> {
+        ProcessOutcome::Ignored
+    }
+
+    fn after_handle(
+        &mut self,
+        _context: &HostContext,
+        _request: &SlippyRequest,
+        _handle_result: &mut Result<SlippyResponse, HandleError>,
+    ) -> () {
+    }
+
+    fn before_write(
+        &mut self,
+        _context: &WriteContext,
+        _response: &mut SlippyResponse,
+        _writer: &mut dyn HttpResponseWriter,
+    ) -> () {
+    }
+
+    fn after_write(
+        &mut self,
+        _context: &WriteContext,
+        _response: &SlippyResponse,
+        _write_result: &Result<HttpResponse, WriteError>,
+    ) -> () {
+    }
+}
+
 
 #[cfg(test)]
 pub mod test_utils {
@@ -31,25 +31,13 @@ pub struct SlippyObserverInventory { }
 impl SlippyObserverInventory {
     pub fn read_observers<'i>(
         telemetry: &'i mut dyn TelemetryInventory
-    ) -> [&'i mut dyn ReadRequestObserver; 2] {
-        let [read_observer_0, read_observer_1] = telemetry.read_request_observers();
-        return [read_observer_0, read_observer_1];
+    ) -> Vec<&'i mut dyn ReadRequestObserver> {
+        return telemetry.read_request_observers();
     }
 
     pub fn write_observers<'i>(
         telemetry: &'i mut dyn TelemetryInventory,
-    ) -> [&'i mut dyn WriteResponseObserver; 4] {
-        let [
-            write_observer_0,
-            write_observer_1,
-            write_observer_2,
-            write_observer_3,
-        ] = telemetry.write_response_observers();
-        return [
-            write_observer_0,
-            write_observer_1,
-            write_observer_2,
-            write_observer_3,
-        ];
+    ) -> Vec<&'i mut dyn WriteResponseObserver> {
+        return telemetry.write_response_observers();
     }
 }
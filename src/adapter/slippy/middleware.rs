@@ -0,0 +1,381 @@
+use crate::schema::apache2::config::ModuleConfig;
+use crate::schema::apache2::error::InvalidConfigError;
+use crate::schema::core::processed::ProcessOutcome;
+use crate::schema::handler::error::HandleError;
+use crate::schema::http::response::HttpResponse;
+use crate::schema::slippy::error::{ReadError, WriteError,};
+use crate::schema::slippy::request::SlippyRequest;
+use crate::schema::slippy::response::SlippyResponse;
+use crate::io::communication::interface::HttpResponseWriter;
+use crate::framework::apache2::context::HostContext;
+use crate::adapter::slippy::interface::{MiddlewareFactory, RequestMiddleware, WriteContext,};
+
+use std::boxed::Box;
+use std::sync::Arc;
+use std::vec::Vec;
+
+
+pub struct MiddlewarePipeline {
+    factories: Vec<Arc<MiddlewareFactory>>,
+    stages: Vec<Box<dyn RequestMiddleware>>,
+}
+
+impl MiddlewarePipeline {
+    pub fn new() -> MiddlewarePipeline {
+        MiddlewarePipeline {
+            factories: Vec::new(),
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        config: &ModuleConfig,
+        factory: Arc<MiddlewareFactory>,
+    ) -> Result<(), InvalidConfigError> {
+        self.stages.push(factory(config)?);
+        self.factories.push(factory);
+        return Ok(());
+    }
+
+    // Builds every stage again from the given config, leaving this pipeline untouched if any stage fails
+    pub fn rebuild(
+        &self,
+        config: &ModuleConfig,
+    ) -> Result<MiddlewarePipeline, InvalidConfigError> {
+        let mut pipeline = MiddlewarePipeline::new();
+        for factory in self.factories.iter() {
+            pipeline.add(config, factory.clone())?;
+        }
+        return Ok(pipeline);
+    }
+
+    pub fn stage_count(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn after_read(
+        &mut self,
+        context: &HostContext,
+        read_result: &mut Result<SlippyRequest, ReadError>,
+    ) -> () {
+        for stage in self.stages.iter_mut() {
+            stage.after_read(context, read_result);
+        }
+    }
+
+    // Returns how many stages were entered so that only those stages see the response
+    pub fn before_handle(
+        &mut self,
+        context: &HostContext,
+        request: &SlippyRequest,
+    ) -> (usize, ProcessOutcome<SlippyResponse>) {
+        for (index, stage) in self.stages.iter_mut().enumerate() {
+            let outcome = stage.before_handle(context, request);
+            if outcome.is_processed() {
+                return (index + 1, outcome);
+            }
+        }
+        return (self.stages.len(), ProcessOutcome::Ignored);
+    }
+
+    pub fn after_handle(
+        &mut self,
+        entered_stages: usize,
+        context: &HostContext,
+        request: &SlippyRequest,
+        handle_result: &mut Result<SlippyResponse, HandleError>,
+    ) -> () {
+        for stage in self.stages[..entered_stages].iter_mut().rev() {
+            stage.after_handle(context, request, handle_result);
+        }
+    }
+
+    pub fn before_write(
+        &mut self,
+        entered_stages: usize,
+        context: &WriteContext,
+        response: &mut SlippyResponse,
+        writer: &mut dyn HttpResponseWriter,
+    ) -> () {
+        for stage in self.stages[..entered_stages].iter_mut().rev() {
+            stage.before_write(context, response, writer);
+        }
+    }
+
+    pub fn after_write(
+        &mut self,
+        entered_stages: usize,
+        context: &WriteContext,
+        response: &SlippyResponse,
+        write_result: &Result<HttpResponse, WriteError>,
+    ) -> () {
+        for stage in self.stages[..entered_stages].iter_mut().rev() {
+            stage.after_write(context, response, write_result);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::identifier::generate_id;
+    use crate::schema::apache2::config::ModuleConfig;
+    use crate::schema::slippy::request;
+    use crate::schema::slippy::response;
+    use crate::schema::tile::identity::LayerName;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+    use crate::io::communication::http_exchange::test_utils::MockWriter;
+
+    use chrono::Utc;
+    use http::header::{HeaderMap, HeaderName, HeaderValue,};
+    use http::status::StatusCode;
+
+    use std::error::Error as StdError;
+    use std::string::String;
+    use std::sync::Mutex;
+
+    struct RecordingMiddleware {
+        name: &'static str,
+        short_circuit: Option<StatusCode>,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RequestMiddleware for RecordingMiddleware {
+        fn after_read(
+            &mut self,
+            _context: &HostContext,
+            _read_result: &mut Result<SlippyRequest, ReadError>,
+        ) -> () {
+            self.calls.lock().unwrap().push(format!("{}.after_read", self.name));
+        }
+
+        fn before_handle(
+            &mut self,
+            _context: &HostContext,
+            _request: &SlippyRequest,
+        ) -> ProcessOutcome<SlippyResponse> {
+            self.calls.lock().unwrap().push(format!("{}.before_handle", self.name));
+            match self.short_circuit {
+                Some(status_code) => ProcessOutcome::Processed(status_response(status_code)),
+                None => ProcessOutcome::Ignored,
+            }
+        }
+
+        fn after_handle(
+            &mut self,
+            _context: &HostContext,
+            _request: &SlippyRequest,
+            _handle_result: &mut Result<SlippyResponse, HandleError>,
+        ) -> () {
+            self.calls.lock().unwrap().push(format!("{}.after_handle", self.name));
+        }
+
+        fn before_write(
+            &mut self,
+            _context: &WriteContext,
+            response: &mut SlippyResponse,
+            _writer: &mut dyn HttpResponseWriter,
+        ) -> () {
+            self.calls.lock().unwrap().push(format!("{}.before_write", self.name));
+            if let response::BodyVariant::Status(status) = &mut response.body {
+                let value = HeaderValue::from_static(self.name);
+                status.http_headers.append(HeaderName::from_static("x-middleware"), value);
+            }
+        }
+
+        fn after_write(
+            &mut self,
+            _context: &WriteContext,
+            _response: &SlippyResponse,
+            _write_result: &Result<HttpResponse, WriteError>,
+        ) -> () {
+            self.calls.lock().unwrap().push(format!("{}.after_write", self.name));
+        }
+    }
+
+    fn status_response(status_code: StatusCode) -> SlippyResponse {
+        let now = Utc::now();
+        SlippyResponse {
+            header: response::Header {
+                mime_type: mime::TEXT_PLAIN.clone(),
+                before_timestamp: now,
+                after_timestamp: now,
+            },
+            body: response::BodyVariant::Status(
+                response::StatusResponse {
+                    status_code,
+                    http_headers: HeaderMap::new(),
+                }
+            ),
+        }
+    }
+
+    fn make_request() -> SlippyRequest {
+        SlippyRequest {
+            header: request::Header {
+                layer: LayerName::new(),
+                request_id: generate_id(),
+                uri: String::from("/mod_tile_rs"),
+                received_timestamp: Utc::now(),
+            },
+            body: request::BodyVariant::ReportStatistics,
+        }
+    }
+
+    fn make_pipeline(
+        config: &ModuleConfig,
+        short_circuits: Vec<Option<StatusCode>>,
+        calls: &Arc<Mutex<Vec<String>>>,
+    ) -> Result<MiddlewarePipeline, InvalidConfigError> {
+        let names = ["auth", "throttle", "cors"];
+        let mut pipeline = MiddlewarePipeline::new();
+        for (index, short_circuit) in short_circuits.into_iter().enumerate() {
+            let name = names[index];
+            let calls = calls.clone();
+            pipeline.add(
+                config,
+                Arc::new(move |_config: &ModuleConfig| -> Result<Box<dyn RequestMiddleware>, InvalidConfigError> {
+                    Ok(
+                        Box::new(
+                            RecordingMiddleware {
+                                name,
+                                short_circuit,
+                                calls: calls.clone(),
+                            }
+                        )
+                    )
+                })
+            )?;
+        }
+        Ok(pipeline)
+    }
+
+    #[test]
+    fn test_stages_wrap_the_handler() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let module_config = ModuleConfig::new();
+            let context = HostContext::new(&module_config, record);
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            let mut pipeline = make_pipeline(&module_config, vec![None, None], &calls)?;
+            let mut read_result = Ok(make_request());
+            pipeline.after_read(&context, &mut read_result);
+            let request = read_result?;
+            let (entered_stages, outcome) = pipeline.before_handle(&context, &request);
+            assert!(!outcome.is_processed(), "Pipeline short-circuited unexpectedly");
+            let mut handle_result = Ok(status_response(StatusCode::OK));
+            pipeline.after_handle(entered_stages, &context, &request, &mut handle_result);
+            let mut response = handle_result?;
+            let write_context = WriteContext {
+                host_context: HostContext::new(&module_config, record),
+                request: &request,
+            };
+            let mut writer = MockWriter::new();
+            pipeline.before_write(entered_stages, &write_context, &mut response, &mut writer);
+            let write_result = Ok(
+                HttpResponse {
+                    status_code: StatusCode::OK,
+                    bytes_written: 0,
+                    http_headers: HeaderMap::new(),
+                }
+            );
+            pipeline.after_write(entered_stages, &write_context, &response, &write_result);
+            let expected_calls = vec![
+                "auth.after_read", "throttle.after_read",
+                "auth.before_handle", "throttle.before_handle", "throttle.after_handle", "auth.after_handle",
+                "throttle.before_write", "auth.before_write", "throttle.after_write", "auth.after_write",
+            ];
+            assert_eq!(expected_calls, *calls.lock().unwrap(), "Failed to call the stages in onion order");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_short_circuit_skips_inner_stages() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let module_config = ModuleConfig::new();
+            let context = HostContext::new(&module_config, record);
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            let mut pipeline = make_pipeline(
+                &module_config,
+                vec![None, Some(StatusCode::TOO_MANY_REQUESTS), None],
+                &calls,
+            )?;
+            let request = make_request();
+            let (entered_stages, outcome) = pipeline.before_handle(&context, &request);
+            let mut handle_result = Ok(outcome.expect_processed());
+            pipeline.after_handle(entered_stages, &context, &request, &mut handle_result);
+            let mut response = handle_result?;
+            let write_context = WriteContext {
+                host_context: HostContext::new(&module_config, record),
+                request: &request,
+            };
+            let mut writer = MockWriter::new();
+            pipeline.before_write(entered_stages, &write_context, &mut response, &mut writer);
+            // Each stage that was entered decorates the response on its way out
+            let mut expected_headers = HeaderMap::new();
+            expected_headers.append(HeaderName::from_static("x-middleware"), HeaderValue::from_static("throttle"));
+            expected_headers.append(HeaderName::from_static("x-middleware"), HeaderValue::from_static("auth"));
+            assert_eq!(
+                response::BodyVariant::Status(
+                    response::StatusResponse {
+                        status_code: StatusCode::TOO_MANY_REQUESTS,
+                        http_headers: expected_headers,
+                    }
+                ),
+                response.body,
+                "Failed to short-circuit with the middleware response"
+            );
+            let expected_calls = vec![
+                "auth.before_handle", "throttle.before_handle", "throttle.after_handle", "auth.after_handle",
+                "throttle.before_write", "auth.before_write",
+            ];
+            assert_eq!(expected_calls, *calls.lock().unwrap(), "Failed to skip the stages after the short-circuit");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_rebuild_from_config() -> Result<(), Box<dyn StdError>> {
+        let mut pipeline = MiddlewarePipeline::new();
+        let built_from = Arc::new(Mutex::new(Vec::new()));
+        let factory_built_from = built_from.clone();
+        let mut module_config = ModuleConfig::new();
+        pipeline.add(
+            &module_config,
+            Arc::new(move |config: &ModuleConfig| -> Result<Box<dyn RequestMiddleware>, InvalidConfigError> {
+                if config.renderd.ipc_uri.is_empty() {
+                    return Err(
+                        InvalidConfigError {
+                            entry: String::from("ipc_uri"),
+                            reason: String::from("Socket path is empty"),
+                        }
+                    );
+                }
+                factory_built_from.lock().unwrap().push(config.renderd.ipc_uri.clone());
+                Ok(
+                    Box::new(
+                        RecordingMiddleware {
+                            name: "auth",
+                            short_circuit: None,
+                            calls: Arc::new(Mutex::new(Vec::new())),
+                        }
+                    )
+                )
+            })
+        )?;
+        module_config.renderd.ipc_uri = String::from("/run/renderd/reloaded.sock");
+        let rebuilt = pipeline.rebuild(&module_config)?;
+        assert_eq!(1, rebuilt.stages.len(), "Failed to rebuild the stage");
+        assert_eq!(
+            vec!["/var/run/renderd/renderd.sock", "/run/renderd/reloaded.sock"],
+            *built_from.lock().unwrap(),
+            "Failed to rebuild the stage from the new config"
+        );
+        module_config.renderd.ipc_uri = String::new();
+        assert!(pipeline.rebuild(&module_config).is_err(), "Invalid config was not rejected");
+        assert_eq!(1, pipeline.stages.len(), "Failed to keep the previous stages");
+        Ok(())
+    }
+}
@@ -1,13 +1,13 @@
 use crate::binding::apache2::get_module_name;
 use crate::core::identifier::generate_id;
-use crate::schema::apache2::config::{ LayerConfig, MAX_ZOOM_SERVER };
+use crate::schema::apache2::config::{ LayerConfig, ANY_PARAMETER, MAX_ZOOM_SERVER };
 use crate::schema::core::processed::ProcessOutcome;
 use crate::schema::http::request::HttpRequest;
 use crate::schema::slippy::error::{
     InvalidParameterError, ReadError
 };
 use crate::schema::slippy::request::{
-    BodyVariant, Header, ServeTileRequest, ServeTileRequestV2,
+    BodyVariant, DumpConfigRequest, Header, ResetStatisticsRequest, ServeTileRequest, ServeTileRequestV2,
     ServeTileRequestV3, SlippyRequest, MAX_EXTENSION_LEN,
 };
 use crate::schema::tile::identity::LayerName;
@@ -19,6 +19,10 @@ use scan_fmt::scan_fmt;
 use std::string::String;
 
 
+const AUTHORIZATION_HEADER: &str = "Authorization";
+const BEARER_PREFIX: &str = "Bearer ";
+const RESET_METHOD: &str = "POST";
+
 pub struct SlippyRequestReader;
 impl SlippyRequestReader {
     pub fn read(
@@ -45,6 +49,10 @@ impl SlippyRequestParser {
         if let ProcessOutcome::Processed(stat_result) = stat_outcome {
             return stat_result;
         }
+        let config_outcome = ConfigDumpRequestParser::parse(&context, request, request_url);
+        if let ProcessOutcome::Processed(config_result) = config_outcome {
+            return config_result;
+        }
         let parse_layer_request = LayerParserCombinator::try_else(
             DescribeLayerRequestParser::parse,
             LayerParserCombinator::try_else(
@@ -70,15 +78,7 @@ impl SlippyRequestParser {
             };
         }
         info!(context.host().record, "SlippyRequestParser::parse - URL {} does not match any known request types", request_url);
-        return Err(
-            ReadError::Param(
-                InvalidParameterError {
-                    param: String::from("uri"),
-                    value: request.uri.to_string(),
-                    reason: "URI is not a valid Slippy request".to_string(),
-                }
-            )
-        );
+        return Err(ReadError::NotMatched(request.uri.to_string()));
     }
 }
 
@@ -113,7 +113,35 @@ impl StatisticsRequestParser {
     ) -> ParseOutcome {
         let module_name = get_module_name();
         let stats_uri = format!("/{}", module_name);
-        if request.uri.eq(&stats_uri) {
+        let reset_uri = format!("/{}/reset", module_name);
+        if request.uri.eq(&reset_uri) {
+            info!(context.host().record, "StatisticsRequestParser::parse - matched ResetStatistics");
+            // Reset changes state, so it must not be triggered by a GET that a crawler or cache could repeat
+            let method = request.method().unwrap_or_default();
+            if method != RESET_METHOD {
+                return ProcessOutcome::Processed(Err(ReadError::MethodNotAllowed(method.to_string())));
+            }
+            let credential = request.header(AUTHORIZATION_HEADER).and_then(|authorization| {
+                authorization.strip_prefix(BEARER_PREFIX).map(|token| token.trim().to_string())
+            });
+            ProcessOutcome::Processed(
+                Ok(
+                    SlippyRequest {
+                        header: Header {
+                            layer: LayerName::new(),
+                            request_id: generate_id(),
+                            uri: request.uri.to_string(),
+                            received_timestamp: request.received_time.clone(),
+                        },
+                        body: BodyVariant::ResetStatistics(
+                            ResetStatisticsRequest {
+                                credential,
+                            }
+                        ),
+                    }
+                )
+            )
+        } else if request.uri.eq(&stats_uri) {
             info!(context.host().record, "StatisticsRequestParser::parse - matched ReportStatistics");
             ProcessOutcome::Processed(
                 Ok(
@@ -135,6 +163,40 @@ impl StatisticsRequestParser {
     }
 }
 
+struct ConfigDumpRequestParser;
+impl ConfigDumpRequestParser {
+    fn parse(
+        context: &ReadContext,
+        request: &HttpRequest,
+        _request_url: &str,
+    ) -> ParseOutcome {
+        let config_uri = format!("/{}/config", get_module_name());
+        if request.uri.eq(&config_uri) {
+            info!(context.host().record, "ConfigDumpRequestParser::parse - matched DumpConfig");
+            ProcessOutcome::Processed(
+                Ok(
+                    SlippyRequest {
+                        header: Header {
+                            layer: LayerName::new(),
+                            request_id: generate_id(),
+                            uri: request.uri.to_string(),
+                            received_timestamp: request.received_time.clone(),
+                        },
+                        body: BodyVariant::DumpConfig(
+                            DumpConfigRequest {
+                                client_ip: request.client_ip().map(|ip| ip.to_string()),
+                            }
+                        ),
+                    }
+                )
+            )
+        } else {
+            info!(context.host().record, "ConfigDumpRequestParser::parse - no match");
+            ProcessOutcome::Ignored
+        }
+    }
+}
+
 struct DescribeLayerRequestParser;
 impl DescribeLayerRequestParser {
     fn parse(
@@ -206,6 +268,9 @@ impl ServeTileV3RequestParser {
         ) {
             Ok((parameter, x, y, z, extension, option)) => {
                 info!(context.host().record, "ServeTileV3RequestParser::parse - matched ServeTileV3 with option");
+                if let Err(error) = Self::validate_parameter(layer_config, &parameter) {
+                    return ProcessOutcome::Processed(Err(ReadError::Param(error)));
+                }
                 if z <= MAX_ZOOM_SERVER as i32 {
                     return ProcessOutcome::Processed(
                         Ok(
@@ -256,6 +321,9 @@ impl ServeTileV3RequestParser {
         ) {
             Ok((parameter, x, y, z, extension)) => {
                 info!(context.host().record, "ServeTileV3RequestParser::parse - matched ServeTileV3 no option");
+                if let Err(error) = Self::validate_parameter(layer_config, &parameter) {
+                    return ProcessOutcome::Processed(Err(ReadError::Param(error)));
+                }
                 if z <= MAX_ZOOM_SERVER as i32 {
                     return ProcessOutcome::Processed(
                         Ok(
@@ -301,6 +369,28 @@ impl ServeTileV3RequestParser {
         info!(context.host().record, "ServeTileV3RequestParser::parse - no match");
         return ProcessOutcome::Ignored;
     }
+
+    // The parameter is forwarded to renderd and becomes a directory of the tile store
+    fn validate_parameter(
+        layer_config: &LayerConfig,
+        parameter: &str,
+    ) -> Result<(), InvalidParameterError> {
+        let is_safe = parameter.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        let reason = if !is_safe {
+            "Parameter may only contain letters, digits, '-' and '_'"
+        } else if !layer_config.allowed_parameters.iter().any(|allowed| allowed == parameter || allowed == ANY_PARAMETER) {
+            "Parameter is not in the allowed_parameters of the layer"
+        } else {
+            return Ok(());
+        };
+        Err(
+            InvalidParameterError {
+                param: String::from("parameter"),
+                value: String::from(parameter),
+                reason: String::from(reason),
+            }
+        )
+    }
 }
 
 struct ServeTileV2RequestParser;
@@ -418,6 +508,7 @@ impl ServeTileV2RequestParser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::binding::apache2::{apr_table_make, apr_table_setn,};
     use crate::schema::apache2::config::ModuleConfig;
     use crate::schema::apache2::virtual_host::VirtualHost;
     use crate::core::memory::PoolStored;
@@ -427,6 +518,7 @@ mod tests {
     use std::boxed::Box;
     use std::error::Error as StdError;
     use std::ffi::CString;
+    use std::os::raw::c_char;
 
     #[test]
     fn test_parse_report_mod_stats() -> Result<(), Box<dyn StdError>> {
@@ -453,6 +545,129 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_parse_reset_mod_stats() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let module_config = ModuleConfig::new();
+            let uri = CString::new("/mod_tile_rs/reset")?;
+            record.uri = uri.clone().into_raw();
+            record.method = cstr!("POST");
+            let authorization = CString::new("Bearer secret")?;
+            unsafe {
+                record.headers_in = apr_table_make(record.pool, 1);
+                apr_table_setn(record.headers_in, cstr!("Authorization"), authorization.as_ptr());
+            }
+            let context = ReadContext {
+                host_context: HostContext {
+                    module_config: &module_config,
+                    host: VirtualHost::find_or_allocate_new(record)?,
+                }
+            };
+            let request = HttpRequest::new(
+                uri.as_c_str().to_str()?,
+                Utc::now(),
+                record,
+            );
+            let request_url= request.uri;
+
+            let actual_request = SlippyRequestParser::parse(&context, &request, request_url)?;
+            let expected_body = BodyVariant::ResetStatistics(
+                ResetStatisticsRequest {
+                    credential: Some(String::from("secret")),
+                }
+            );
+            assert_eq!(expected_body, actual_request.body, "Incorrect parsing");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_parse_reset_mod_stats_with_get() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let module_config = ModuleConfig::new();
+            let uri = CString::new("/mod_tile_rs/reset")?;
+            record.uri = uri.clone().into_raw();
+            record.method = cstr!("GET");
+            let context = ReadContext {
+                host_context: HostContext {
+                    module_config: &module_config,
+                    host: VirtualHost::find_or_allocate_new(record)?,
+                }
+            };
+            let request = HttpRequest::new(
+                uri.as_c_str().to_str()?,
+                Utc::now(),
+                record,
+            );
+            let request_url= request.uri;
+
+            let result = SlippyRequestParser::parse(&context, &request, request_url);
+            assert!(matches!(result, Err(ReadError::MethodNotAllowed(_))), "Failed to reject a reset with GET");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_parse_dump_config() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let module_config = ModuleConfig::new();
+            let uri = CString::new("/mod_tile_rs/config")?;
+            record.uri = uri.clone().into_raw();
+            let client_ip = CString::new("192.168.0.1")?;
+            record.useragent_ip = client_ip.as_ptr() as *mut c_char;
+            let context = ReadContext {
+                host_context: HostContext {
+                    module_config: &module_config,
+                    host: VirtualHost::find_or_allocate_new(record)?,
+                }
+            };
+            let request = HttpRequest::new(
+                uri.as_c_str().to_str()?,
+                Utc::now(),
+                record,
+            );
+            let request_url= request.uri;
+
+            let actual_request = SlippyRequestParser::parse(&context, &request, request_url)?;
+            let expected_body = BodyVariant::DumpConfig(
+                DumpConfigRequest {
+                    client_ip: Some(String::from("192.168.0.1")),
+                }
+            );
+            assert_eq!(expected_body, actual_request.body, "Incorrect parsing");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_parse_unmatched_uri() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let module_config = ModuleConfig::new();
+            let uri = CString::new("/index.html")?;
+            record.uri = uri.clone().into_raw();
+            let context = ReadContext {
+                host_context: HostContext {
+                    module_config: &module_config,
+                    host: VirtualHost::find_or_allocate_new(record)?,
+                }
+            };
+            let request = HttpRequest::new(
+                uri.as_c_str().to_str()?,
+                Utc::now(),
+                record,
+            );
+            let request_url= request.uri;
+
+            match SlippyRequestParser::parse(&context, &request, request_url) {
+                Err(ReadError::NotMatched(actual_uri)) => {
+                    assert_eq!("/index.html", actual_uri, "Incorrect unmatched URI");
+                },
+                _ => panic!("Expected NotMatched in result"),
+            }
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_parse_describe_layer() -> Result<(), Box<dyn StdError>> {
         with_request_rec(|record| {
@@ -488,6 +703,7 @@ mod tests {
             let mut module_config = ModuleConfig::new();
             let layer_config = module_config.layers.get_mut(&layer_name).unwrap();
             layer_config.parameters_allowed = true;
+            layer_config.allowed_parameters = vec![String::from("foo")];
             let uri = CString::new(format!("{}/foo/7/8/9.png/bar", layer_config.base_url))?;
             record.uri = uri.clone().into_raw();
             let context = ReadContext {
@@ -522,6 +738,78 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_parse_serve_tile_v3_with_disallowed_parameter() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let layer_name = LayerName::from("default");
+            let mut module_config = ModuleConfig::new();
+            let layer_config = module_config.layers.get_mut(&layer_name).unwrap();
+            layer_config.parameters_allowed = true;
+            layer_config.allowed_parameters = vec![String::from("de")];
+            let base_url = layer_config.base_url.clone();
+            for (parameter, is_allowed) in vec![("de", true), ("fr", false), ("..", false)] {
+                let uri = CString::new(format!("{}/{}/7/8/9.png", base_url, parameter))?;
+                record.uri = uri.clone().into_raw();
+                let context = ReadContext {
+                    host_context: HostContext {
+                        module_config: &module_config,
+                        host: VirtualHost::find_or_allocate_new(record)?,
+                    }
+                };
+                let request = HttpRequest::new(
+                    uri.as_c_str().to_str()?,
+                    Utc::now(),
+                    record,
+                );
+                let result = SlippyRequestParser::parse(&context, &request, request.uri);
+                match result {
+                    Ok(_) => assert!(is_allowed, "Failed to reject parameter {}", parameter),
+                    Err(ReadError::Param(err)) => {
+                        assert!(!is_allowed, "Rejected allowed parameter {}", parameter);
+                        assert_eq!("parameter", err.param, "Did not identify the invalid parameter");
+                    },
+                    Err(_) => panic!("Expected InvalidParameterError in result"),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_parse_serve_tile_v3_parameters_denied_by_default() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let layer_name = LayerName::from("default");
+            for (allowed_parameters, is_allowed) in vec![(vec![], false), (vec![String::from(ANY_PARAMETER)], true)] {
+                let mut module_config = ModuleConfig::new();
+                let layer_config = module_config.layers.get_mut(&layer_name).unwrap();
+                layer_config.parameters_allowed = true;
+                layer_config.allowed_parameters = allowed_parameters;
+                let uri = CString::new(format!("{}/de/7/8/9.png", layer_config.base_url))?;
+                record.uri = uri.clone().into_raw();
+                let context = ReadContext {
+                    host_context: HostContext {
+                        module_config: &module_config,
+                        host: VirtualHost::find_or_allocate_new(record)?,
+                    }
+                };
+                let request = HttpRequest::new(
+                    uri.as_c_str().to_str()?,
+                    Utc::now(),
+                    record,
+                );
+                match SlippyRequestParser::parse(&context, &request, request.uri) {
+                    Ok(_) => assert!(is_allowed, "Failed to refuse a parameter that is not listed"),
+                    Err(ReadError::Param(err)) => {
+                        assert!(!is_allowed, "Failed to allow any parameter with the wildcard");
+                        assert_eq!("parameter", err.param, "Did not identify the invalid parameter");
+                    },
+                    Err(_) => panic!("Expected InvalidParameterError in result"),
+                }
+            }
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_parse_serve_tile_v3_with_invalid_zoom_param() -> Result<(), Box<dyn StdError>> {
         with_request_rec(|record| {
@@ -529,6 +817,7 @@ mod tests {
             let mut module_config = ModuleConfig::new();
             let layer_config = module_config.layers.get_mut(&layer_name).unwrap();
             layer_config.parameters_allowed = true;
+            layer_config.allowed_parameters = vec![String::from("foo")];
             let uri = CString::new(format!("{}/foo/7/8/999.png/bar", layer_config.base_url))?;
             record.uri = uri.clone().into_raw();
             let context = ReadContext {
@@ -565,6 +854,7 @@ mod tests {
             let mut module_config = ModuleConfig::new();
             let layer_config = module_config.layers.get_mut(&layer_name).unwrap();
             layer_config.parameters_allowed = true;
+            layer_config.allowed_parameters = vec![String::from("foo")];
             let uri = CString::new(format!("{}/foo/7/8/9.png/", layer_config.base_url))?;
             record.uri = uri.clone().into_raw();
             let context = ReadContext {
@@ -606,6 +896,7 @@ mod tests {
             let mut module_config = ModuleConfig::new();
             let layer_config = module_config.layers.get_mut(&layer_name).unwrap();
             layer_config.parameters_allowed = true;
+            layer_config.allowed_parameters = vec![String::from("foo")];
             let uri = CString::new(format!("{}/foo/7/8/9.png", layer_config.base_url))?;
             record.uri = uri.clone().into_raw();
             let context = ReadContext {
@@ -0,0 +1,176 @@
+use crate::schema::communication::error::CommunicationError;
+use crate::schema::handler::error::HandleError;
+use crate::schema::renderd::error::RenderError;
+use crate::schema::slippy::error::{ReadError, WriteError,};
+use crate::schema::slippy::response::{BodyVariant, Header, SlippyResponse, StatusResponse,};
+use crate::schema::tile::error::TileReadError;
+
+use chrono::Utc;
+use http::header::{HeaderMap, HeaderValue, RETRY_AFTER,};
+use http::status::StatusCode;
+use mime;
+
+use std::option::Option;
+
+
+pub struct ErrorStatusMapper;
+impl ErrorStatusMapper {
+    // None means the request should be declined so that other Apache handlers can serve it
+    pub fn read_error_status(error: &ReadError) -> Option<StatusCode> {
+        match error {
+            ReadError::NotMatched(_) => None,
+            ReadError::Param(_) => Some(StatusCode::BAD_REQUEST),
+            ReadError::MethodNotAllowed(_) => Some(StatusCode::METHOD_NOT_ALLOWED),
+            ReadError::Utf8(_) => Some(StatusCode::BAD_REQUEST),
+            ReadError::Io(_) => Some(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    pub fn handle_error_status(error: &HandleError) -> StatusCode {
+        match error {
+            HandleError::RequestNotRead(read_error) => {
+                Self::read_error_status(read_error).unwrap_or(StatusCode::NOT_FOUND)
+            },
+            HandleError::Timeout(_) => StatusCode::SERVICE_UNAVAILABLE,
+            HandleError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            HandleError::TileRead(TileReadError::NotFound(_)) => StatusCode::NOT_FOUND,
+            HandleError::TileRead(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            HandleError::Communication(comms_error) => Self::communication_error_status(comms_error),
+            HandleError::Render(RenderError::InvalidParameter(_)) => StatusCode::BAD_REQUEST,
+            HandleError::Render(RenderError::Communication(comms_error)) => {
+                Self::communication_error_status(comms_error)
+            },
+            // A malformed reply means renderd misbehaved, rather than the client
+            HandleError::Render(RenderError::Protocol(_)) => StatusCode::BAD_GATEWAY,
+            HandleError::Render(RenderError::NotRendered(_)) => StatusCode::BAD_GATEWAY,
+            HandleError::Render(RenderError::TileRead(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            HandleError::Forbidden(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    pub fn handle_error_response(error: &HandleError) -> SlippyResponse {
+        let timestamp = Utc::now();
+        let mut http_headers = HeaderMap::new();
+        if let HandleError::Timeout(timeout) = error {
+            http_headers.insert(RETRY_AFTER.clone(), HeaderValue::from(timeout.retry_after));
+        }
+        SlippyResponse {
+            header: Header {
+                mime_type: mime::TEXT_PLAIN.clone(),
+                before_timestamp: timestamp,
+                after_timestamp: timestamp,
+            },
+            body: BodyVariant::Status(
+                StatusResponse {
+                    status_code: Self::handle_error_status(error),
+                    http_headers,
+                }
+            ),
+        }
+    }
+
+    // None means the request should be declined so that other Apache handlers can serve it
+    pub fn write_error_status(error: &WriteError) -> Option<StatusCode> {
+        match error {
+            WriteError::RequestNotHandled => None,
+            WriteError::UnsupportedMediaType(_) => Some(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            WriteError::ResponseWrite(_) => Some(StatusCode::INTERNAL_SERVER_ERROR),
+            WriteError::Io(_) => Some(StatusCode::INTERNAL_SERVER_ERROR),
+            WriteError::InvalidHeader(_) => Some(StatusCode::INTERNAL_SERVER_ERROR),
+            WriteError::Serialise(_) => Some(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    fn communication_error_status(error: &CommunicationError) -> StatusCode {
+        match error {
+            CommunicationError::TimeoutError => StatusCode::GATEWAY_TIMEOUT,
+            CommunicationError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            CommunicationError::Io(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::handler::error::TimeoutError;
+    use crate::schema::slippy::error::InvalidParameterError;
+
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+    use std::path::PathBuf;
+    use std::string::String;
+
+    #[test]
+    fn test_read_error_status() -> Result<(), Box<dyn StdError>> {
+        let unmatched = ReadError::NotMatched(String::from("/index.html"));
+        assert_eq!(None, ErrorStatusMapper::read_error_status(&unmatched), "Failed to decline unmatched URI");
+        let invalid = ReadError::Param(
+            InvalidParameterError {
+                param: String::from("z"),
+                value: String::from("/osm/99/1/1.png"),
+                reason: String::from("Zoom level exceeds the limit"),
+            }
+        );
+        assert_eq!(
+            Some(StatusCode::BAD_REQUEST),
+            ErrorStatusMapper::read_error_status(&invalid),
+            "Failed to map invalid parameter to bad request"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_handle_error_status() -> Result<(), Box<dyn StdError>> {
+        let not_found = HandleError::TileRead(TileReadError::NotFound(PathBuf::from("/var/cache/renderd/0.meta")));
+        assert_eq!(StatusCode::NOT_FOUND, ErrorStatusMapper::handle_error_status(&not_found), "Failed to map missing tile");
+        let render_timeout = HandleError::Render(RenderError::Communication(CommunicationError::TimeoutError));
+        assert_eq!(
+            StatusCode::GATEWAY_TIMEOUT,
+            ErrorStatusMapper::handle_error_status(&render_timeout),
+            "Failed to map renderd timeout"
+        );
+        assert_eq!(
+            StatusCode::FORBIDDEN,
+            ErrorStatusMapper::handle_error_status(&HandleError::Forbidden(String::from("denied"))),
+            "Failed to map forbidden request"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_timeout_response_has_retry_after() -> Result<(), Box<dyn StdError>> {
+        let timeout = HandleError::Timeout(
+            TimeoutError {
+                threshold: 5,
+                retry_after: 30,
+                reason: String::from("Render queue is full"),
+            }
+        );
+        let response = ErrorStatusMapper::handle_error_response(&timeout);
+        match response.body {
+            BodyVariant::Status(status) => {
+                assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status.status_code, "Failed to map timeout");
+                assert_eq!("30", status.http_headers.get(RETRY_AFTER).unwrap(), "Failed to set Retry-After");
+            },
+            _ => panic!("Expected a status response"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_error_status() -> Result<(), Box<dyn StdError>> {
+        assert_eq!(
+            None,
+            ErrorStatusMapper::write_error_status(&WriteError::RequestNotHandled),
+            "Failed to decline unhandled request"
+        );
+        assert_eq!(
+            Some(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            ErrorStatusMapper::write_error_status(&WriteError::UnsupportedMediaType(mime::IMAGE_JPEG)),
+            "Failed to map unsupported media type"
+        );
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::schema::http::response::HttpResponse;
 use crate::schema::slippy::error::WriteError;
 use crate::schema::slippy::response::{
-    BodyVariant, Header, Description, SlippyResponse, Statistics, TileResponse,
+    BodyVariant, ConfigurationDump, Header, Description, SlippyResponse, Statistics, StatusResponse, TileResponse,
 };
 use crate::io::communication::interface::HttpResponseWriter;
 use crate::adapter::slippy::interface::WriteContext;
@@ -20,12 +20,18 @@ impl SlippyResponseWriter {
         writer: &mut dyn HttpResponseWriter,
     ) -> Result<HttpResponse, WriteError> {
         match &response.body {
+            BodyVariant::Configuration(configuration) => {
+                ConfigurationWriter::write(context, &response.header, configuration, writer)
+            },
             BodyVariant::Description(description) => {
                 DescriptionWriter::write(context, &response.header, description, writer)
             },
             BodyVariant::Statistics(statistics) => {
                 StatisticsWriter::write(context, &response.header, statistics, writer)
             },
+            BodyVariant::Status(status) => {
+                StatusWriter::write(context, &response.header, status, writer)
+            },
             BodyVariant::Tile(tile) => {
                 TileWriter::write(context, &response.header, tile, writer)
             },
@@ -33,6 +39,46 @@ impl SlippyResponseWriter {
     }
 }
 
+struct ConfigurationWriter { }
+impl ConfigurationWriter {
+    pub fn write(
+        context: &WriteContext,
+        header: &Header,
+        configuration: &ConfigurationDump,
+        writer: &mut dyn HttpResponseWriter,
+    ) -> Result<HttpResponse, WriteError> {
+        debug!(context.host().record, "ConfigurationWriter::write - start");
+        let mut http_headers = HeaderMap::new();
+        let text = match (header.mime_type.type_(), header.mime_type.subtype()) {
+            (mime::APPLICATION, mime::JSON) => {
+                writer.set_content_type(&mime::APPLICATION_JSON);
+                debug!(context.host().record, "ConfigurationWriter::write - setting content type to {}", mime::APPLICATION_JSON.essence_str());
+                serde_json::to_string_pretty(configuration)?
+            },
+            _ => String::from(""),
+        };
+
+        // The configuration changes on reload so it must never be served from a cache
+        let cache_key = CACHE_CONTROL.clone();
+        let cache_value = HeaderValue::from_static("no-store");
+        writer.append_http_header(&cache_key, &cache_value)?;
+        http_headers.insert(cache_key, cache_value);
+
+        let written_length = writer.write_content(&text)?;
+        writer.set_content_length(written_length);
+        writer.flush_response()?;
+        debug!(context.host().record, "ConfigurationWriter::write - finish");
+
+        Ok(
+            HttpResponse {
+                status_code: StatusCode::OK,
+                bytes_written: written_length,
+                http_headers,
+            }
+        )
+    }
+}
+
 struct DescriptionWriter { }
 impl DescriptionWriter {
     pub fn write(
@@ -47,7 +93,7 @@ impl DescriptionWriter {
             (mime::APPLICATION, mime::JSON) => {
                 writer.set_content_type(&mime::APPLICATION_JSON);
                 debug!(context.host().record, "DescriptionWriter::write - setting content type to {}", mime::APPLICATION_JSON.essence_str());
-                serde_json::to_string_pretty(description).unwrap()
+                serde_json::to_string_pretty(description)?
             },
             _ => String::from(""),
         };
@@ -56,22 +102,22 @@ impl DescriptionWriter {
 
         let digest = format!("\"{:x}\"", md5::compute(&text));
         let etag_key = ETAG.clone();
-        let etag_value = HeaderValue::from_str(digest.as_str()).unwrap();
-        writer.set_http_header(&etag_key, &etag_value).unwrap();
+        let etag_value = HeaderValue::from_str(digest.as_str())?;
+        writer.set_http_header(&etag_key, &etag_value)?;
         http_headers.insert(etag_key, etag_value);
 
 
         let cache_age = format!("max-age={}", max_age);
         let cache_key = CACHE_CONTROL.clone();
-        let cache_value = HeaderValue::from_str(cache_age.as_str()).unwrap();
-        writer.append_http_header(&cache_key, &cache_value).unwrap();
+        let cache_value = HeaderValue::from_str(cache_age.as_str())?;
+        writer.append_http_header(&cache_key, &cache_value)?;
         http_headers.insert(cache_key, cache_value);
 
         let expiry_timestamp = context.request.header.received_timestamp + max_age_duration;
         let expiry_string = expiry_timestamp.to_rfc2822();
         let expiry_key = EXPIRES.clone();
-        let expiry_value = HeaderValue::from_str(expiry_string.as_str()).unwrap();
-        writer.set_http_header(&expiry_key, &expiry_value).unwrap();
+        let expiry_value = HeaderValue::from_str(expiry_string.as_str())?;
+        writer.set_http_header(&expiry_key, &expiry_value)?;
         http_headers.insert(expiry_key, expiry_value);
 
         let written_length = writer.write_content(&text)?;
@@ -103,15 +149,15 @@ impl StatisticsWriter {
             (mime::APPLICATION, mime::JSON) => {
                 writer.set_content_type(&mime::APPLICATION_JSON);
                 debug!(context.host().record, "StatisticsWriter::write - setting content type to {}", mime::APPLICATION_JSON.essence_str());
-                serde_json::to_string_pretty(statistics).unwrap()
+                serde_json::to_string_pretty(statistics)?
             },
             _ => String::from(""),
         };
 
         let digest = format!("\"{:x}\"", md5::compute(&text));
         let etag_key = ETAG.clone();
-        let etag_value = HeaderValue::from_str(digest.as_str()).unwrap();
-        writer.set_http_header(&etag_key, &etag_value).unwrap();
+        let etag_value = HeaderValue::from_str(digest.as_str())?;
+        writer.set_http_header(&etag_key, &etag_value)?;
         http_headers.insert(etag_key, etag_value);
 
         let written_length = writer.write_content(&text)?;
@@ -129,6 +175,30 @@ impl StatisticsWriter {
     }
 }
 
+struct StatusWriter { }
+impl StatusWriter {
+    pub fn write(
+        context: &WriteContext,
+        _header: &Header,
+        status: &StatusResponse,
+        writer: &mut dyn HttpResponseWriter,
+    ) -> Result<HttpResponse, WriteError> {
+        // Apache generates the response body for the status code returned by the handler
+        debug!(context.host().record, "StatusWriter::write - responding with {}", status.status_code);
+        for (key, value) in status.http_headers.iter() {
+            // Apache only sends the error headers with a generated error response
+            writer.set_error_http_header(key, value)?;
+        }
+        Ok(
+            HttpResponse {
+                status_code: status.status_code,
+                bytes_written: 0,
+                http_headers: status.http_headers.clone(),
+            }
+        )
+    }
+}
+
 struct TileWriter {}
 impl TileWriter {
     pub fn write(
@@ -145,8 +215,8 @@ impl TileWriter {
             tile.tile_ref.with_tile(|raw_bytes| {
                 let digest = format!("\"{:x}\"", md5::compute(&raw_bytes));
                 let etag_key = ETAG.clone();
-                let etag_value = HeaderValue::from_str(digest.as_str()).unwrap();
-                writer.set_http_header(&etag_key, &etag_value).unwrap();
+                let etag_value = HeaderValue::from_str(digest.as_str())?;
+                writer.set_http_header(&etag_key, &etag_value)?;
                 http_headers.insert(etag_key, etag_value);
                 writer.set_content_encoding(&tile.tile_ref.encoding);
                 let written_length = writer.write_content(&raw_bytes)?;
@@ -169,3 +239,61 @@ impl TileWriter {
         return result;
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::identifier::generate_id;
+    use crate::schema::apache2::config::ModuleConfig;
+    use crate::schema::slippy::request::{self, SlippyRequest,};
+    use crate::schema::tile::identity::LayerName;
+    use crate::framework::apache2::context::HostContext;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+    use crate::io::communication::http_exchange::test_utils::CapturingWriter;
+    use chrono::Utc;
+    use http::header::RETRY_AFTER;
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+    use std::string::String;
+
+    #[test]
+    fn test_status_header_write_failure_is_an_error() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let module_config = ModuleConfig::new();
+            let request = SlippyRequest {
+                header: request::Header {
+                    layer: LayerName::new(),
+                    request_id: generate_id(),
+                    uri: String::from("/osm/0/0/0.png"),
+                    received_timestamp: Utc::now(),
+                },
+                body: request::BodyVariant::DescribeLayer,
+            };
+            let mut http_headers = HeaderMap::new();
+            // Apache headers must be visible ASCII, so this value can't be written
+            http_headers.insert(RETRY_AFTER.clone(), HeaderValue::from_bytes(b"caf\xe9")?);
+            let response = SlippyResponse {
+                header: Header {
+                    mime_type: mime::TEXT_PLAIN.clone(),
+                    before_timestamp: Utc::now(),
+                    after_timestamp: Utc::now(),
+                },
+                body: BodyVariant::Status(
+                    StatusResponse {
+                        status_code: StatusCode::SERVICE_UNAVAILABLE,
+                        http_headers,
+                    }
+                ),
+            };
+            let mut writer = CapturingWriter::new();
+            let context = WriteContext {
+                host_context: HostContext::new(&module_config, record),
+                request: &request,
+            };
+            let result = SlippyResponseWriter::write(&context, &response, &mut writer);
+            assert!(matches!(result, Err(WriteError::InvalidHeader(_))), "Failed to report the header write failure");
+            Ok(())
+        })
+    }
+}
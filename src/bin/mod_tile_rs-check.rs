@@ -0,0 +1,31 @@
+use mod_tile_rs::lint;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+
+fn main() -> () {
+    let path = match env::args_os().nth(1) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("Usage: mod_tile_rs-check <renderd.conf>");
+            process::exit(2);
+        },
+    };
+    match lint(path.as_path()) {
+        Ok(problems) if problems.is_empty() => {
+            println!("{}: OK", path.display());
+        },
+        Ok(problems) => {
+            for problem in &problems {
+                println!("{}: {}", path.display(), problem);
+            }
+            process::exit(1);
+        },
+        Err(error) => {
+            eprintln!("{}: {}", path.display(), error);
+            process::exit(1);
+        },
+    };
+}
@@ -1,12 +1,72 @@
 use snowflake::SnowflakeIdGenerator;
 
+use std::option::Option;
+use std::process;
+use std::sync::{Mutex, PoisonError,};
+
+
+// Snowflake IDs have 5 bits each for the machine and node
+const SNOWFLAKE_ID_MASK: u32 = 0x1f;
+
+// A generator only keeps IDs apart within a millisecond by its sequence, so the threads of a process share
+// one. It is keyed by the pid, so a child forked after the parent generated an ID starts its own.
+static ID_GENERATOR: Mutex<Option<(u32, SnowflakeIdGenerator)>> = Mutex::new(None);
 
 pub fn generate_id() -> i64 {
-    // TODO: find a better way of assigning machine and node IDs
-    let mut generator = SnowflakeIdGenerator::new(
-        1,
-        1,
-    );
-    let id = generator.real_time_generate();
+    let pid = process::id();
+    let mut generator = ID_GENERATOR.lock().unwrap_or_else(PoisonError::into_inner);
+    let is_current = matches!(&*generator, Some((generator_pid, _)) if *generator_pid == pid);
+    if !is_current {
+        *generator = Some((pid, process_id_generator(pid)));
+    }
+    let id = generator.as_mut().unwrap().1.real_time_generate();
     return id;
 }
+
+// Every Apache child generates IDs independently, so the machine and node IDs come
+// from the low 10 bits of the pid to keep children from generating the same IDs
+fn process_id_generator(pid: u32) -> SnowflakeIdGenerator {
+    let (machine_id, node_id) = snowflake_ids(pid);
+    SnowflakeIdGenerator::new(machine_id, node_id)
+}
+
+fn snowflake_ids(pid: u32) -> (i32, i32) {
+    let machine_id = (pid >> 5) & SNOWFLAKE_ID_MASK;
+    let node_id = pid & SNOWFLAKE_ID_MASK;
+    (machine_id as i32, node_id as i32)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::collections::HashSet;
+    use std::error::Error as StdError;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_snowflake_ids_differ_between_processes() -> Result<(), Box<dyn StdError>> {
+        let ids: HashSet<(i32, i32)> = (4096..(4096 + 1024)).map(snowflake_ids).collect();
+        assert_eq!(1024, ids.len(), "Failed to give consecutive pids distinct IDs");
+        for (machine_id, node_id) in ids {
+            assert!(machine_id <= SNOWFLAKE_ID_MASK as i32, "Incorrect machine ID {}", machine_id);
+            assert!(node_id <= SNOWFLAKE_ID_MASK as i32, "Incorrect node ID {}", node_id);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_distinct_ids_across_threads() -> Result<(), Box<dyn StdError>> {
+        let generators: Vec<_> = (0..4).map(|_| {
+            thread::spawn(|| (0..1000).map(|_| generate_id()).collect::<Vec<i64>>())
+        }).collect();
+        let mut ids = HashSet::new();
+        for generator in generators {
+            ids.extend(generator.join().unwrap());
+        }
+        assert_eq!(4000, ids.len(), "Failed to generate distinct IDs");
+        Ok(())
+    }
+}
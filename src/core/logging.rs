@@ -1,4 +1,4 @@
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "fuzzing")))]
 #[macro_export(local_inner_macros_)]
 macro_rules! error {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -6,7 +6,7 @@ macro_rules! error {
     );
 }
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "fuzzing")))]
 #[macro_export(local_inner_macros_)]
 macro_rules! warn {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -14,7 +14,7 @@ macro_rules! warn {
     );
 }
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "fuzzing")))]
 #[macro_export(local_inner_macros_)]
 macro_rules! info {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -22,7 +22,7 @@ macro_rules! info {
     );
 }
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "fuzzing")))]
 #[macro_export(local_inner_macros_)]
 macro_rules! debug {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -30,7 +30,7 @@ macro_rules! debug {
     );
 }
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "fuzzing")))]
 #[macro_export(local_inner_macros_)]
 macro_rules! trace {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -38,7 +38,7 @@ macro_rules! trace {
     );
 }
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "fuzzing")))]
 macro_rules! _log { (
     $level:expr,
     $server_expr:expr,
@@ -58,23 +58,23 @@ macro_rules! _log { (
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 use crate::binding::apache2::{ apr_initialize, apr_terminate, };
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 #[ctor::ctor]
 unsafe fn mod_test_setup() {
     apr_initialize();
     env_logger::init();
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 #[ctor::dtor]
 unsafe fn mod_test_teardown() {
     apr_terminate();
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 #[macro_export]
 macro_rules! error {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -82,7 +82,7 @@ macro_rules! error {
     );
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 #[macro_export]
 macro_rules! warn {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -90,7 +90,7 @@ macro_rules! warn {
     );
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 #[macro_export]
 macro_rules! info {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -98,7 +98,7 @@ macro_rules! info {
     );
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 #[macro_export]
 macro_rules! debug {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -106,7 +106,7 @@ macro_rules! debug {
     );
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 #[macro_export]
 macro_rules! trace {
     ($server_expr:expr, $($arg:tt)+) => (
@@ -114,7 +114,7 @@ macro_rules! trace {
     );
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 macro_rules! _log { (
     $level:expr,
     $server_expr:expr,
@@ -1,14 +1,23 @@
-use crate::schema::apache2::config::{ ModuleConfig, RenderdConfig, LayerConfig };
+use crate::schema::apache2::config::{
+    BalancePolicy, ModuleConfig, RenderdConfig, RenderdEndpoint, LayerConfig, TraceExportFormat,
+    MAX_ZOOM_SERVER,
+};
+use crate::schema::apache2::error::InvalidConfigError;
 use crate::schema::tile::identity::{ LayerName, max_layer_name_char_len };
 
 use configparser::ini::Ini;
 use thiserror::Error;
 
+use std::collections::HashSet;
 use std::fmt;
+use std::net::IpAddr;
 use std::option::Option;
-use std::path::Path;
+use std::path::{Path, PathBuf,};
 use std::result::Result;
+use std::str::FromStr;
 use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
 
 
 pub trait Loadable {
@@ -33,80 +42,550 @@ fn parse(
     ini: &Ini,
     server_name: Option<&str>,
 ) -> Result<ModuleConfig, ParseError> {
+    let (config, mut errors) = parse_entries(ini, server_name);
+    if errors.is_empty() {
+        return Ok(config);
+    } else {
+        return Err(errors.remove(0).error);
+    }
+}
+
+#[derive(Debug)]
+pub struct EntryError {
+    pub section: String,
+    pub key: String,
+    pub error: ParseError,
+}
+
+// Every section is parsed even after an entry fails, so that all the entries which can't be parsed are reported
+pub fn parse_entries(
+    ini: &Ini,
+    server_name: Option<&str>,
+) -> (ModuleConfig, Vec<EntryError>) {
     let mut config = ModuleConfig::new();
+    let mut errors = Vec::new();
     'sections: for section_name in &(ini.sections()) {
         match section_name.to_lowercase().as_str() {
             "mapnik" => {
                 continue 'sections;
             },
             "renderd" => {
-                config.renderd = parse_renderd(ini, section_name)?;
+                config.renderd = parse_renderd(ini, section_name, &mut errors);
+            },
+            // Like mod_tile, only the first renderd instance is used and the others only configure renderd
+            name if name.starts_with("renderd") => {
+                continue 'sections;
             },
             _ => {
-                let layer_name = match LayerName::try_make(section_name.as_str()) {
-                    Ok(name) => name,
+                match LayerName::try_make(section_name.as_str()) {
+                    Ok(layer_name) => {
+                        let layer = parse_layer(ini, &layer_name, server_name, &mut errors);
+                        config.layers.insert(layer_name, layer);
+                    },
                     Err(_) => {
-                        return Err(
-                            ParseError{
-                                reason: format!(
-                                    "Layer name {} exceeds length limit of {}",
-                                    section_name,
-                                    max_layer_name_char_len(),
-                                ),
+                        errors.push(
+                            EntryError {
+                                section: section_name.clone(),
+                                key: String::new(),
+                                error: ParseError {
+                                    reason: format!(
+                                        "Layer name {} exceeds length limit of {}",
+                                        section_name,
+                                        max_layer_name_char_len(),
+                                    ),
+                                },
                             }
                         );
                     },
                 };
-                let layer = parse_layer(ini, &layer_name, server_name)?;
-                config.layers.insert(layer_name, layer);
             },
         };
     }
-    return Ok(config);
+    return (config, errors);
+}
+
+fn parsed_entry<T>(
+    section_name: &str,
+    key: &str,
+    result: Result<T, ParseError>,
+    errors: &mut Vec<EntryError>,
+) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(error) => {
+            errors.push(
+                EntryError {
+                    section: String::from(section_name),
+                    key: String::from(key),
+                    error,
+                }
+            );
+            None
+        },
+    }
+}
+
+pub fn validate(config: &ModuleConfig) -> Vec<InvalidConfigError> {
+    return validate_sections(config).into_iter().map(|(_, error)| error).collect();
+}
+
+// Pairs each error with the config file section of the entry, which is empty for entries outside of any section
+pub fn validate_sections(config: &ModuleConfig) -> Vec<(String, InvalidConfigError)> {
+    let mut errors = Vec::new();
+    if config.renderd.store_uri.is_empty() {
+        errors.push((String::from("renderd"), invalid_entry("tile_dir", String::from("Tile directory is empty"))));
+    }
+    if config.renderd.ipc_uri.is_empty() {
+        errors.push((String::from("renderd"), invalid_entry("socketname", String::from("Renderd socket name is empty"))));
+    }
+    if config.layers.is_empty() {
+        errors.push((String::new(), invalid_entry("layers", String::from("No layers are configured"))));
+    }
+    let mut layers: Vec<&LayerConfig> = config.layers.values().collect();
+    layers.sort_by(|left, right| left.name.as_str().cmp(right.name.as_str()));
+    let mut base_urls = HashSet::new();
+    for layer in layers {
+        let section = String::from(layer.name.as_str());
+        if layer.min_zoom > layer.max_zoom {
+            errors.push((
+                section.clone(),
+                invalid_entry(
+                    "minzoom",
+                    format!("Layer {} minzoom {} exceeds maxzoom {}", layer.name, layer.min_zoom, layer.max_zoom),
+                ),
+            ));
+        }
+        if layer.max_zoom as usize > MAX_ZOOM_SERVER {
+            errors.push((
+                section.clone(),
+                invalid_entry(
+                    "maxzoom",
+                    format!("Layer {} maxzoom {} exceeds the limit of {}", layer.name, layer.max_zoom, MAX_ZOOM_SERVER),
+                ),
+            ));
+        }
+        if layer.file_extension.is_empty() {
+            errors.push((
+                section.clone(),
+                invalid_entry("type", format!("Layer {} has no file extension", layer.name)),
+            ));
+        }
+        if !base_urls.insert(layer.base_url.as_str()) {
+            errors.push((
+                section.clone(),
+                invalid_entry("uri", format!("Layer {} reuses base URL {}", layer.name, layer.base_url)),
+            ));
+        }
+    }
+    return errors;
+}
+
+fn invalid_entry(
+    entry: &str,
+    reason: String,
+) -> InvalidConfigError {
+    InvalidConfigError {
+        entry: String::from(entry),
+        reason,
+    }
 }
 
-fn parse_renderd(ini: &Ini, section_name: &String) -> Result<RenderdConfig, ParseError> {
+fn parse_renderd(
+    ini: &Ini,
+    section_name: &String,
+    errors: &mut Vec<EntryError>,
+) -> RenderdConfig {
+    let section = section_name.as_str();
     let mut config = RenderdConfig::new();
-    if let Some(tile_dir) = ini.get(section_name.as_str(), "tile_dir") {
+    if let Some(tile_dir) = ini.get(section, "tile_dir") {
         config.store_uri = tile_dir;
     }
-    if let Some(socket_name) = ini.get(section_name.as_str(), "socketname") {
+    if let Some(socket_name) = ini.get(section, "socketname") {
         config.ipc_uri = socket_name;
     }
-    return Ok(config);
+    if let Some(host_name) = ini.get(section, "iphostname") {
+        // renderd listens on every interface when the host name is empty
+        if !host_name.trim().is_empty() {
+            config.ip_host_name = String::from(host_name.trim());
+        }
+    }
+    if let Some(port) = ini.get(section, "ipport") {
+        if let Some(port) = parsed_entry(section, "ipport", parse_number::<u16>("ipport", &port), errors) {
+            // renderd ignores a zero port and uses the Unix socket instead
+            config.ip_port = Some(port).filter(|port| *port != 0);
+        }
+    }
+    if let Some(failover_sockets) = ini.get(section, "failover_sockets") {
+        if let Some(endpoints) = parsed_entry(section, "failover_sockets", parse_endpoints(&failover_sockets), errors) {
+            config.failover_endpoints = endpoints;
+        }
+    }
+    if let Some(balance) = ini.get(section, "balance") {
+        if let Some(policy) = parsed_entry(section, "balance", parse_balance_policy(&balance), errors) {
+            config.balance_policy = policy;
+        }
+    }
+    return config;
+}
+
+fn parse_balance_policy(value: &str) -> Result<BalancePolicy, ParseError> {
+    match value.to_lowercase().as_str() {
+        "round_robin" => Ok(BalancePolicy::RoundRobin),
+        "least_outstanding" => Ok(BalancePolicy::LeastOutstanding),
+        _ => Err(
+            ParseError {
+                reason: format!("Balance policy {} is not one of round_robin or least_outstanding", value),
+            }
+        ),
+    }
+}
+
+fn parse_endpoints(value: &str) -> Result<Vec<RenderdEndpoint>, ParseError> {
+    let mut endpoints = Vec::new();
+    for uri in value.split(|c: char| c == ',' || c.is_whitespace()).filter(|uri| !uri.is_empty()) {
+        let endpoint = if let Some(address) = uri.strip_prefix("tcp://") {
+            let (host, port) = address.rsplit_once(':').ok_or_else(|| {
+                ParseError { reason: format!("Renderd endpoint {} has no port", uri) }
+            })?;
+            RenderdEndpoint::Tcp {
+                host: String::from(host),
+                port: parse_number("Renderd endpoint port", port)?,
+            }
+        } else {
+            RenderdEndpoint::Unix(String::from(uri.strip_prefix("unix://").unwrap_or(uri)))
+        };
+        endpoints.push(endpoint);
+    }
+    return Ok(endpoints);
+}
+
+fn parse_trace_export_format(value: &str) -> Result<TraceExportFormat, ParseError> {
+    match value.to_lowercase().as_str() {
+        "otlp" => Ok(TraceExportFormat::OtlpJson),
+        "zipkin" => Ok(TraceExportFormat::ZipkinJson),
+        _ => Err(
+            ParseError {
+                reason: format!("Trace export format {} is not one of otlp or zipkin", value),
+            }
+        ),
+    }
+}
+
+fn parse_ip_addresses(value: &str) -> Result<Vec<IpAddr>, ParseError> {
+    let mut addresses = Vec::new();
+    for address in value.split(|c: char| c == ',' || c.is_whitespace()).filter(|a| !a.is_empty()) {
+        match IpAddr::from_str(address) {
+            Ok(ip) => addresses.push(ip),
+            Err(_) => {
+                return Err(
+                    ParseError {
+                        reason: format!("Config dump allowed IP {} is not an IP address", address),
+                    }
+                );
+            },
+        };
+    }
+    return Ok(addresses);
 }
 
 fn parse_layer(
     ini: &Ini,
     section_name: &LayerName,
     server_name: Option<&str>,
-) -> Result<LayerConfig, ParseError> {
+    errors: &mut Vec<EntryError>,
+) -> LayerConfig {
+    let section = section_name.as_str();
     let mut config = LayerConfig::new();
     config.name = section_name.clone();
-    if let Some(description) = ini.get(section_name.as_str(), "description") {
+    if let Some(description) = ini.get(section, "description") {
         config.description = description;
     }
-    if let Some(attribution) = ini.get(section_name.as_str(), "attribution") {
+    if let Some(attribution) = ini.get(section, "attribution") {
         config.attribution = attribution;
     }
-    if let Some(min_zoom) = ini.getuint(section_name.as_str(), "minzoom")? {
+    let min_zoom_result = ini.getuint(section, "minzoom").map_err(ParseError::from);
+    if let Some(Some(min_zoom)) = parsed_entry(section, "minzoom", min_zoom_result, errors) {
         config.min_zoom = min_zoom;
     }
-    if let Some(max_zoom) = ini.getuint(section_name.as_str(), "maxzoom")? {
+    let max_zoom_result = ini.getuint(section, "maxzoom").map_err(ParseError::from);
+    if let Some(Some(max_zoom)) = parsed_entry(section, "maxzoom", max_zoom_result, errors) {
         config.max_zoom = max_zoom;
     }
-    if let Some(uri) = ini.get(section_name.as_str(), "uri") {
+    if let Some(uri) = ini.get(section, "uri") {
         config.base_url = uri.trim_end_matches("/").to_string();
     }
-    if let Some(parameters_allowed) = ini.getbool(section_name.as_str(), "parameterize_style")? {
+    let parameters_allowed_result = ini.getbool(section, "parameterize_style").map_err(ParseError::from);
+    if let Some(Some(parameters_allowed)) = parsed_entry(section, "parameterize_style", parameters_allowed_result, errors) {
         config.parameters_allowed = parameters_allowed;
     }
-    if let Some(alias) = ini.get(section_name.as_str(), "server_alias") {
+    if let Some(allowed_parameters) = ini.get(section, "allowed_parameters") {
+        config.allowed_parameters = allowed_parameters
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|parameter| !parameter.is_empty())
+            .map(String::from)
+            .collect();
+    }
+    if let Some(renderd_sockets) = ini.get(section, "renderd_sockets") {
+        if let Some(endpoints) = parsed_entry(section, "renderd_sockets", parse_endpoints(&renderd_sockets), errors) {
+            config.renderd_endpoints = endpoints;
+        }
+    }
+    if let Some(alias) = ini.get(section, "server_alias") {
         config.set_host_name(alias.as_str());
     } else if let Some(name) = server_name {
         config.set_host_name(name);
     }
-    return Ok(config);
+    return config;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Directive {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl Directive {
+    fn is_layer(&self) -> bool {
+        self.name == "AddTileConfig" || self.name == "AddTileMimeConfig"
+    }
+
+    fn layer_base_url(&self) -> Option<&str> {
+        if self.is_layer() {
+            self.args.get(0).map(|base_url| base_url.trim_end_matches("/"))
+        } else {
+            None
+        }
+    }
+
+    fn layer_name(&self) -> Option<&str> {
+        if self.is_layer() {
+            self.args.get(1).map(|name| name.as_str())
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub config_file_path: Option<PathBuf>,
+    pub directives: Vec<Directive>,
+}
+
+impl ServerConfig {
+    pub fn new() -> ServerConfig {
+        ServerConfig {
+            config_file_path: None,
+            directives: Vec::new(),
+        }
+    }
+
+    pub fn add_directive(
+        &mut self,
+        name: &str,
+        args: &[&str],
+    ) -> () {
+        self.directives.push(
+            Directive {
+                name: String::from(name),
+                args: args.iter().map(|arg| String::from(*arg)).collect(),
+            }
+        );
+    }
+
+    // A virtual host inherits the directives of the main server but its own are applied last so they take
+    // precedence, its layers replace main server layers with the same name or URL, and its config file
+    // replaces the main server's config file
+    pub fn merge(
+        base: &ServerConfig,
+        add: &ServerConfig,
+    ) -> ServerConfig {
+        let added_urls: HashSet<&str> = add.directives.iter().filter_map(|directive| directive.layer_base_url()).collect();
+        let added_names: HashSet<&str> = add.directives.iter().filter_map(|directive| directive.layer_name()).collect();
+        let inherited = base.directives.iter().filter(|directive| {
+            let url_replaced = directive.layer_base_url().map_or(false, |url| added_urls.contains(url));
+            let name_replaced = directive.layer_name().map_or(false, |name| added_names.contains(name));
+            !url_replaced && !name_replaced
+        });
+        ServerConfig {
+            config_file_path: add.config_file_path.clone().or(base.config_file_path.clone()),
+            directives: inherited.chain(add.directives.iter()).cloned().collect(),
+        }
+    }
+
+    pub fn build(
+        &self,
+        server_name: Option<&str>,
+    ) -> Result<ModuleConfig, ParseError> {
+        let mut config = self.load_config_file(server_name)?;
+        self.apply_directives(&mut config, server_name)?;
+        return Ok(config);
+    }
+
+    pub fn load_config_file(
+        &self,
+        server_name: Option<&str>,
+    ) -> Result<ModuleConfig, ParseError> {
+        match &self.config_file_path {
+            Some(path) => ModuleConfig::load(path.as_path(), server_name),
+            None => {
+                let mut config = ModuleConfig::new();
+                // The placeholder layer would otherwise clash with a layer declared by a directive
+                if self.directives.iter().any(|directive| directive.is_layer()) {
+                    config.layers.clear();
+                }
+                Ok(config)
+            },
+        }
+    }
+
+    pub fn apply_directives(
+        &self,
+        config: &mut ModuleConfig,
+        server_name: Option<&str>,
+    ) -> Result<(), ParseError> {
+        for directive in &self.directives {
+            let args: Vec<&str> = directive.args.iter().map(|arg| arg.as_str()).collect();
+            apply_directive(config, directive.name.as_str(), &args, server_name)?;
+        }
+        return Ok(());
+    }
+}
+
+pub fn apply_directive(
+    config: &mut ModuleConfig,
+    name: &str,
+    args: &[&str],
+    server_name: Option<&str>,
+) -> Result<(), ParseError> {
+    match (name, args) {
+        ("AddTileConfig", [base_url, layer_name]) => {
+            add_layer(config, base_url, layer_name, "png", server_name)?;
+        },
+        ("AddTileMimeConfig", [base_url, layer_name, file_extension]) => {
+            add_layer(config, base_url, layer_name, file_extension, server_name)?;
+        },
+        ("ModTileTileDir", [tile_dir]) => {
+            config.renderd.store_uri = tile_dir.to_string();
+        },
+        ("ModTileRenderdSocketName", [socket_name]) => {
+            config.renderd.ipc_uri = socket_name.to_string();
+        },
+        ("ModTileRequestTimeout", [timeout]) => {
+            config.renderd.render_timeout = parse_seconds(name, timeout)?;
+        },
+        ("ModTileMissingRequestTimeout", [timeout]) => {
+            config.renderd.missing_render_timeout = parse_seconds(name, timeout)?;
+        },
+        ("ModTileAccessLog", [path]) => {
+            config.telemetry.access_log_path = Some(path.to_string());
+        },
+        ("ModTileTraceExportUri", [uri]) => {
+            config.telemetry.trace_export_uri = Some(uri.to_string());
+        },
+        ("ModTileTraceExportFormat", [format]) => {
+            config.telemetry.trace_export_format = parse_trace_export_format(format)?;
+        },
+        ("ModTileStatisticsResetToken", [token]) => {
+            config.telemetry.statistics_reset_token = Some(token.to_string());
+        },
+        ("ModTileConfigDumpAllowedIps", [allowed_ips]) => {
+            config.telemetry.config_dump_allowed_ips = parse_ip_addresses(allowed_ips)?;
+        },
+        _ => {
+            return Err(
+                ParseError {
+                    reason: format!("Directive {} with {} arguments is not supported", name, args.len()),
+                }
+            );
+        },
+    };
+    return Ok(());
+}
+
+fn add_layer(
+    config: &mut ModuleConfig,
+    base_url: &str,
+    layer_name: &str,
+    file_extension: &str,
+    server_name: Option<&str>,
+) -> Result<(), ParseError> {
+    let name = match LayerName::try_make(layer_name) {
+        Ok(name) => name,
+        Err(_) => {
+            return Err(
+                ParseError {
+                    reason: format!(
+                        "Layer name {} exceeds length limit of {}",
+                        layer_name,
+                        max_layer_name_char_len(),
+                    ),
+                }
+            );
+        },
+    };
+    let mut layer = LayerConfig::new();
+    layer.name = name.clone();
+    layer.base_url = base_url.trim_end_matches("/").to_string();
+    layer.description = String::from(layer_name);
+    layer.file_extension = String::from(file_extension);
+    layer.mime_type = String::from(guess_mime_type(file_extension));
+    if let Some(host_name) = server_name {
+        layer.set_host_name(host_name);
+    }
+    // A layer with the same name is replaced, but two layers can't share a URL
+    let duplicate = config.layers.values().find(|existing| {
+        existing.name != name && existing.base_url == layer.base_url
+    });
+    if let Some(existing) = duplicate {
+        return Err(
+            ParseError {
+                reason: format!(
+                    "Layer {} has the same base URL {} as layer {}",
+                    layer_name,
+                    base_url,
+                    existing.name,
+                ),
+            }
+        );
+    }
+    config.layers.insert(name, layer);
+    return Ok(());
+}
+
+fn guess_mime_type(file_extension: &str) -> &'static str {
+    match file_extension.to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "js" => "text/javascript",
+        "json" | "geojson" => "application/json",
+        "pbf" | "mvt" => "application/x-protobuf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn parse_number<T: FromStr>(
+    name: &str,
+    value: &str,
+) -> Result<T, ParseError> {
+    value.trim().parse::<T>().or_else(|_| {
+        Err(
+            ParseError {
+                reason: format!("{} argument {} is not a valid number", name, value),
+            }
+        )
+    })
+}
+
+fn parse_seconds(
+    name: &str,
+    value: &str,
+) -> Result<Duration, ParseError> {
+    let seconds = parse_number::<u64>(name, value)?;
+    return Ok(Duration::from_secs(seconds));
 }
 
 #[derive(Error, Debug)]
@@ -208,6 +687,86 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_renderd_endpoints() -> Result<(), Box<dyn StdError>> {
+        let mut ini = Ini::new();
+        ini.set(
+            "renderd",
+            "failover_sockets",
+            Some(String::from("unix:///var/run/renderd/backup.sock, tcp://render2.example.org:7654")),
+        );
+        ini.set("renderd", "balance", Some(String::from("least_outstanding")));
+        ini.set("osm", "renderd_sockets", Some(String::from("/var/run/renderd/osm.sock")));
+        let actual_config = parse(&ini, None)?;
+        assert_eq!(
+            vec![
+                RenderdEndpoint::Unix(String::from("/var/run/renderd/renderd.sock")),
+                RenderdEndpoint::Unix(String::from("/var/run/renderd/backup.sock")),
+                RenderdEndpoint::Tcp { host: String::from("render2.example.org"), port: 7654 },
+            ],
+            actual_config.renderd.endpoints(),
+            "Failed to parse failover_sockets"
+        );
+        assert_eq!(BalancePolicy::LeastOutstanding, actual_config.renderd.balance_policy, "Failed to parse balance");
+
+        ini.set("renderd", "iphostname", Some(String::from("render1.example.org")));
+        ini.set("renderd", "ipport", Some(String::from("7654")));
+        let tcp_config = parse(&ini, None)?;
+        assert_eq!(
+            RenderdEndpoint::Tcp { host: String::from("render1.example.org"), port: 7654 },
+            tcp_config.renderd.primary_endpoint(),
+            "Failed to prefer the TCP port over the socket"
+        );
+        assert_eq!(
+            vec![RenderdEndpoint::Unix(String::from("/var/run/renderd/osm.sock"))],
+            actual_config.layers[&LayerName::from("osm")].renderd_endpoints,
+            "Failed to parse renderd_sockets"
+        );
+
+        let mut invalid_ini = Ini::new();
+        invalid_ini.set("renderd", "failover_sockets", Some(String::from("tcp://render2.example.org")));
+        assert!(parse(&invalid_ini, None).is_err(), "Endpoint without a port was not rejected");
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_telemetry_directives() -> Result<(), Box<dyn StdError>> {
+        let mut config = ModuleConfig::new();
+        apply_directive(&mut config, "ModTileTraceExportUri", &["file:///var/log/apache2/tile_spans.json"], None)?;
+        apply_directive(&mut config, "ModTileTraceExportFormat", &["Zipkin"], None)?;
+        apply_directive(&mut config, "ModTileStatisticsResetToken", &["secret"], None)?;
+        apply_directive(&mut config, "ModTileConfigDumpAllowedIps", &["127.0.0.1,::1"], None)?;
+        assert_eq!(
+            Some(String::from("file:///var/log/apache2/tile_spans.json")),
+            config.telemetry.trace_export_uri,
+            "Failed to apply ModTileTraceExportUri"
+        );
+        assert_eq!(
+            TraceExportFormat::ZipkinJson,
+            config.telemetry.trace_export_format,
+            "Failed to apply ModTileTraceExportFormat"
+        );
+        assert_eq!(
+            Some(String::from("secret")),
+            config.telemetry.statistics_reset_token,
+            "Failed to apply ModTileStatisticsResetToken"
+        );
+        assert_eq!(
+            vec![IpAddr::from([127, 0, 0, 1]), IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1])],
+            config.telemetry.config_dump_allowed_ips,
+            "Failed to apply ModTileConfigDumpAllowedIps"
+        );
+        assert!(
+            apply_directive(&mut config, "ModTileTraceExportFormat", &["jaeger"], None).is_err(),
+            "Invalid ModTileTraceExportFormat value was not rejected"
+        );
+        assert!(
+            apply_directive(&mut config, "ModTileConfigDumpAllowedIps", &["localhost"], None).is_err(),
+            "Invalid ModTileConfigDumpAllowedIps value was not rejected"
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_parse_uri_with_trailing_slash() -> Result<(), Box<dyn StdError>> {
         let mut ini = Ini::new();
@@ -241,6 +800,19 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_allowed_parameters() -> Result<(), Box<dyn StdError>> {
+        let layer = LayerName::from("basic");
+        let mut ini = Ini::new();
+        ini.set(layer.as_str(), "allowed_parameters", Some(String::from("de, en fr")));
+        let actual_config = parse(&ini, None)?;
+        assert_eq!(
+            vec!["de", "en", "fr"],
+            actual_config.layers.get(&layer).unwrap().allowed_parameters,
+            "Failed to parse allowed_parameters");
+        Ok(())
+    }
+
     #[test]
     fn test_parse_server_alias() -> Result<(), Box<dyn StdError>> {
         let layer1 = LayerName::from("basic");
@@ -288,4 +860,158 @@ mod tests {
         assert_eq!("/var/cache/renderd/", actual_config.renderd.store_uri, "Failed to parse upper case tile_dir");
         Ok(())
     }
+
+    #[test]
+    fn test_apply_directive() -> Result<(), Box<dyn StdError>> {
+        let mut config = ModuleConfig::new();
+        apply_directive(&mut config, "ModTileTileDir", &["/var/cache/tiles"], None)?;
+        assert_eq!("/var/cache/tiles", config.renderd.store_uri, "Failed to apply ModTileTileDir");
+        assert_eq!(
+            Duration::from_secs(10),
+            config.renderd.missing_render_timeout,
+            "Incorrect default ModTileMissingRequestTimeout"
+        );
+        apply_directive(&mut config, "ModTileMissingRequestTimeout", &["30"], None)?;
+        assert_eq!(
+            Duration::from_secs(30),
+            config.renderd.missing_render_timeout,
+            "Failed to apply ModTileMissingRequestTimeout"
+        );
+        apply_directive(&mut config, "ModTileAccessLog", &["/var/log/apache2/tile_access.log"], None)?;
+        assert_eq!(
+            Some(String::from("/var/log/apache2/tile_access.log")),
+            config.telemetry.access_log_path,
+            "Failed to apply ModTileAccessLog"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_invalid_directive() -> Result<(), Box<dyn StdError>> {
+        let mut config = ModuleConfig::new();
+        assert!(
+            apply_directive(&mut config, "ModTileRequestTimeout", &["soon"], None).is_err(),
+            "Non-numeric timeout was not rejected"
+        );
+        assert!(
+            apply_directive(&mut config, "AddTileConfig", &["/osm/"], None).is_err(),
+            "Missing argument was not rejected"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_add_tile_config() -> Result<(), Box<dyn StdError>> {
+        let mut config = ModuleConfig::new();
+        apply_directive(&mut config, "AddTileConfig", &["/folder/", "TileSetName"], Some("tiles.example.com"))?;
+        apply_directive(&mut config, "AddTileMimeConfig", &["/folder2/", "TileSetName2", "js"], None)?;
+        let layer1 = config.layers.get(&LayerName::from("TileSetName")).unwrap();
+        assert_eq!("/folder", layer1.base_url, "Failed to apply base_url");
+        assert_eq!("png", layer1.file_extension, "Failed to apply default file_extension");
+        assert_eq!("image/png", layer1.mime_type, "Failed to apply default mime_type");
+        assert_eq!("http://tiles.example.com", layer1.host_name, "Failed to apply server name as host_name");
+        let layer2 = config.layers.get(&LayerName::from("TileSetName2")).unwrap();
+        assert_eq!("js", layer2.file_extension, "Failed to apply file_extension");
+        assert_eq!("text/javascript", layer2.mime_type, "Failed to guess mime_type from file_extension");
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_invalid_add_tile_config() -> Result<(), Box<dyn StdError>> {
+        let mut config = ModuleConfig::new();
+        apply_directive(&mut config, "AddTileConfig", &["/folder/", "TileSetName"], None)?;
+        assert!(
+            apply_directive(&mut config, "AddTileConfig", &["/folder", "OtherTileSet"], None).is_err(),
+            "Duplicate base_url was not rejected"
+        );
+        let long_name = "a".repeat(max_layer_name_char_len() + 1);
+        assert!(
+            apply_directive(&mut config, "AddTileConfig", &["/long/", long_name.as_str()], None).is_err(),
+            "Layer name exceeding the length limit was not rejected"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_inherits_main_server_settings() -> Result<(), Box<dyn StdError>> {
+        let mut base = ServerConfig::new();
+        base.add_directive("ModTileTileDir", &["/var/cache/main"]);
+        base.add_directive("ModTileRenderdSocketName", &["/run/renderd/main.sock"]);
+        base.add_directive("ModTileRequestTimeout", &["3"]);
+        base.add_directive("AddTileConfig", &["/osm/", "osm"]);
+        let mut vhost = ServerConfig::new();
+        vhost.add_directive("ModTileRequestTimeout", &["5"]);
+        vhost.add_directive("AddTileConfig", &["/topo/", "topo"]);
+        let merged = ServerConfig::merge(&base, &vhost).build(Some("vhost.example.com"))?;
+        assert_eq!("/var/cache/main", merged.renderd.store_uri, "Failed to inherit tile dir");
+        assert_eq!("/run/renderd/main.sock", merged.renderd.ipc_uri, "Failed to inherit renderd socket");
+        assert_eq!(Duration::from_secs(5), merged.renderd.render_timeout, "Failed to override timeout");
+        assert!(merged.layers.contains_key(&LayerName::from("osm")), "Failed to inherit layer");
+        assert!(merged.layers.contains_key(&LayerName::from("topo")), "Failed to add layer");
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_replaces_main_server_layers() -> Result<(), Box<dyn StdError>> {
+        let mut base = ServerConfig::new();
+        base.add_directive("AddTileConfig", &["/osm/", "osm"]);
+        base.add_directive("AddTileConfig", &["/hot/", "hot"]);
+        let mut vhost = ServerConfig::new();
+        vhost.add_directive("AddTileMimeConfig", &["/osm/", "vector", "pbf"]);
+        vhost.add_directive("AddTileConfig", &["/humanitarian/", "hot"]);
+        let merged = ServerConfig::merge(&base, &vhost).build(None)?;
+        assert!(!merged.layers.contains_key(&LayerName::from("osm")), "Failed to replace layer with the same URL");
+        assert_eq!(
+            "/osm",
+            merged.layers.get(&LayerName::from("vector")).unwrap().base_url,
+            "Failed to add replacement layer"
+        );
+        assert_eq!(
+            "/humanitarian",
+            merged.layers.get(&LayerName::from("hot")).unwrap().base_url,
+            "Failed to replace layer with the same name"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_config_file() -> Result<(), Box<dyn StdError>> {
+        let mut file_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        file_path.push("resources/test/tile/basic_valid.conf");
+        let mut base = ServerConfig::new();
+        base.config_file_path = Some(file_path.clone());
+        let vhost = ServerConfig::new();
+        let merged = ServerConfig::merge(&base, &vhost);
+        assert_eq!(Some(file_path.clone()), merged.config_file_path, "Failed to inherit config file");
+        let mut overriding_vhost = ServerConfig::new();
+        overriding_vhost.config_file_path = Some(PathBuf::from("/etc/vhost-renderd.conf"));
+        let overridden = ServerConfig::merge(&base, &overriding_vhost);
+        assert_eq!(
+            Some(PathBuf::from("/etc/vhost-renderd.conf")),
+            overridden.config_file_path,
+            "Failed to override config file"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_default_config() -> Result<(), Box<dyn StdError>> {
+        let config = ModuleConfig::new();
+        assert!(validate(&config).is_empty(), "Default config failed validation");
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() -> Result<(), Box<dyn StdError>> {
+        let mut config = ModuleConfig::new();
+        config.renderd.ipc_uri = String::new();
+        let layer = config.layers.get_mut(&LayerName::from("default")).unwrap();
+        layer.min_zoom = 12;
+        layer.max_zoom = 8;
+        let errors = validate(&config);
+        assert_eq!(2, errors.len(), "Failed to report every problem");
+        assert_eq!("socketname", errors[0].entry, "Failed to report empty socket name");
+        assert_eq!("minzoom", errors[1].entry, "Failed to report inverted zoom range");
+        Ok(())
+    }
 }
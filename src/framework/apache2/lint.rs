@@ -0,0 +1,222 @@
+use crate::schema::apache2::config::{ModuleConfig, LayerConfig, RenderdEndpoint,};
+use crate::framework::apache2::config::{parse_entries, validate_sections, ParseError,};
+
+use configparser::ini::Ini;
+
+use std::fmt;
+use std::fs;
+use std::io::Result as IoResult;
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::result::Result;
+use std::string::String;
+use std::vec::Vec;
+
+
+const RENDERD_KEYS: [&str; 9] = [
+    "socketname", "tile_dir", "num_threads", "stats_file", "iphostname", "ipport", "pid_file",
+    "failover_sockets", "balance",
+];
+// Layer sections are shared with renderd, so the keys only renderd reads are known too
+const LAYER_KEYS: [&str; 19] = [
+    "uri", "xml", "host", "htcphost", "tiledir", "minzoom", "maxzoom", "type", "description",
+    "attribution", "server_alias", "cors", "parameterize_style", "tilesize", "aspectx", "aspecty", "scale",
+    "renderd_sockets", "allowed_parameters",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigProblem {
+    pub section: String,
+    pub key: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.section.is_empty() {
+            write!(f, "{}: {}", self.key, self.reason)
+        } else {
+            write!(f, "[{}] {}: {}", self.section, self.key, self.reason)
+        }
+    }
+}
+
+pub fn lint(path: &Path) -> Result<Vec<ConfigProblem>, ParseError> {
+    let mut ini = Ini::new();
+    ini.load(path)?;
+    let (mut config, entry_errors) = parse_entries(&ini, None);
+    // Only lint the layers the file declares, not the placeholder layer
+    let sections = ini.sections();
+    config.layers.retain(|name, _| sections.iter().any(|section| section == name.as_str()));
+    let mut problems: Vec<ConfigProblem> = entry_errors.iter()
+        .map(|entry_error| problem(&entry_error.section, &entry_error.key, entry_error.error.to_string()))
+        .collect();
+    lint_keys(&ini, &mut problems);
+    for (section, error) in validate_sections(&config) {
+        problems.push(problem(&section, &error.entry, error.reason));
+    }
+    lint_layer_urls(&config, &mut problems);
+    lint_renderd(&config, &mut problems);
+    problems.sort_by(|left, right| (&left.section, &left.key).cmp(&(&right.section, &right.key)));
+    return Ok(problems);
+}
+
+fn lint_keys(
+    ini: &Ini,
+    problems: &mut Vec<ConfigProblem>,
+) -> () {
+    let mut sections: Vec<(&String, Vec<&String>)> = ini.get_map_ref().iter().map(|(section, entries)| {
+        let mut keys: Vec<&String> = entries.keys().collect();
+        keys.sort();
+        (section, keys)
+    }).collect();
+    sections.sort();
+    for (section, keys) in sections {
+        let known_keys: &[&str] = match section.to_lowercase().as_str() {
+            // The mapnik section only configures renderd
+            "mapnik" => continue,
+            name if name.starts_with("renderd") => &RENDERD_KEYS,
+            _ => &LAYER_KEYS,
+        };
+        for key in keys {
+            if !known_keys.contains(&key.to_lowercase().as_str()) {
+                problems.push(problem(section, key, String::from("Key is not recognised")));
+            }
+        }
+    }
+}
+
+// Unlike validate, which only rejects a reused base URL, this also warns about a base URL nested under another
+fn lint_layer_urls(
+    config: &ModuleConfig,
+    problems: &mut Vec<ConfigProblem>,
+) -> () {
+    let mut layers: Vec<&LayerConfig> = config.layers.values().collect();
+    layers.sort_by(|left, right| left.name.as_str().cmp(right.name.as_str()));
+    for (index, layer) in layers.iter().enumerate() {
+        let section = layer.name.as_str();
+        for other in &layers[(index + 1)..] {
+            if layer.base_url != other.base_url && urls_overlap(&layer.base_url, &other.base_url) {
+                problems.push(
+                    problem(
+                        section,
+                        "uri",
+                        format!("{} overlaps {} of layer {}", layer.base_url, other.base_url, other.name),
+                    )
+                );
+            }
+        }
+    }
+}
+
+fn lint_renderd(
+    config: &ModuleConfig,
+    problems: &mut Vec<ConfigProblem>,
+) -> () {
+    match fs::metadata(&config.renderd.store_uri) {
+        Ok(metadata) if metadata.is_dir() => (),
+        Ok(_) => problems.push(
+            problem("renderd", "tile_dir", format!("{} is not a directory", config.renderd.store_uri))
+        ),
+        Err(error) => problems.push(
+            problem("renderd", "tile_dir", format!("{} is unreachable: {}", config.renderd.store_uri, error))
+        ),
+    };
+    let primary_endpoint = config.renderd.primary_endpoint();
+    let key = match primary_endpoint {
+        RenderdEndpoint::Unix(_) => "socketname",
+        RenderdEndpoint::Tcp { .. } => "ipport",
+    };
+    if let Err(error) = connect(&primary_endpoint) {
+        problems.push(problem("renderd", key, format!("{} is unreachable: {}", primary_endpoint, error)));
+    }
+    for endpoint in &config.renderd.failover_endpoints {
+        if let Err(error) = connect(endpoint) {
+            problems.push(problem("renderd", "failover_sockets", format!("{} is unreachable: {}", endpoint, error)));
+        }
+    }
+}
+
+fn connect(endpoint: &RenderdEndpoint) -> IoResult<()> {
+    match endpoint {
+        RenderdEndpoint::Unix(path) => UnixStream::connect(path).map(|_| ()),
+        RenderdEndpoint::Tcp { host, port } => TcpStream::connect((host.as_str(), *port)).map(|_| ()),
+    }
+}
+
+// Requests are routed by URL prefix, so a layer under another layer's URL can never be reached
+fn urls_overlap(
+    left: &str,
+    right: &str,
+) -> bool {
+    let is_under = |url: &str, parent: &str| url.starts_with(parent) && url[parent.len()..].starts_with("/");
+    left == right || is_under(left, right) || is_under(right, left)
+}
+
+fn problem(
+    section: &str,
+    key: &str,
+    reason: String,
+) -> ConfigProblem {
+    ConfigProblem {
+        section: String::from(section),
+        key: String::from(key),
+        reason,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+    use std::io::Write;
+
+    #[test]
+    fn test_lint_reports_problems() -> Result<(), Box<dyn StdError>> {
+        let tile_dir = mktemp::Temp::new_dir()?;
+        let config_file = mktemp::Temp::new_file()?;
+        let mut file = fs::File::create(config_file.to_path_buf())?;
+        write!(
+            file,
+            "[renderd]\nsocketname=/nonexistent/renderd.sock\ntile_dir={}\nbalance=fastest\n\
+             failover_sockets=/nonexistent/failover.sock\n\n\
+             [renderd1]\nsocketname=/nonexistent/renderd1.sock\nnum_threads=4\nthreads=4\n\n\
+             [osm]\nuri=/osm/\nminzoom=12\nmaxzoom=8\n\n\
+             [hot]\nuri=/osm/hot/\nmaxzoom=31\ncolour=red\n\n\
+             [topo]\nuri=/topo/\nminzoom=low\nparameterize_style=sometimes\n",
+            tile_dir.to_path_buf().display(),
+        )?;
+        let problems = lint(config_file.to_path_buf().as_path())?;
+        let found: Vec<(&str, &str)> = problems.iter()
+            .map(|problem| (problem.section.as_str(), problem.key.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("hot", "colour"),
+                ("hot", "maxzoom"),
+                ("hot", "uri"),
+                ("osm", "minzoom"),
+                ("renderd", "balance"),
+                ("renderd", "failover_sockets"),
+                ("renderd", "socketname"),
+                ("renderd1", "threads"),
+                ("topo", "minzoom"),
+                ("topo", "parameterize_style"),
+            ],
+            found,
+            "Failed to report every problem"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_urls_overlap() -> Result<(), Box<dyn StdError>> {
+        assert!(urls_overlap("/osm", "/osm"), "Failed to detect identical URLs");
+        assert!(urls_overlap("/osm", "/osm/hot"), "Failed to detect nested URLs");
+        assert!(!urls_overlap("/osm", "/osmhot"), "Unrelated URLs with a common prefix overlap");
+        Ok(())
+    }
+}
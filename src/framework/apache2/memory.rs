@@ -54,10 +54,37 @@ pub fn alloc<'p, T>(
     }
 }
 
+// The pool memory is zeroed rather than a valid T, so the value is moved in without dropping what was there
+pub fn alloc_init<'p, T>(
+    pool: &'p mut apr_pool_t,
+    key: &CString,
+    cleanup: Option<CleanUpFn>,
+    value: T,
+) -> Result<(&'p mut T, &'p mut apr_pool_t), AllocError> {
+    let (object, pool) = alloc::<T>(pool, key, cleanup)?;
+    unsafe { ptr::write(object as *mut T, value) };
+    return Ok((object, pool));
+}
+
 pub fn retrieve<'p, T>(
     pool: &'p apr_pool_t,
     user_data_key: &CString,
 ) -> Option<&'p mut T> {
+    return unsafe { retrieve_ptr::<T>(pool, user_data_key).as_mut() };
+}
+
+// Unlike retrieve the object is only borrowed immutably, so it can be looked up by several threads at once
+pub fn retrieve_shared<'p, T>(
+    pool: &'p apr_pool_t,
+    user_data_key: &CString,
+) -> Option<&'p T> {
+    return unsafe { retrieve_ptr::<T>(pool, user_data_key).as_ref() };
+}
+
+fn retrieve_ptr<T>(
+    pool: &apr_pool_t,
+    user_data_key: &CString,
+) -> *mut T {
     let mut value_ptr: *mut T = ptr::null_mut();
     unsafe {
         let get_result = apr_pool_userdata_get(
@@ -66,9 +93,9 @@ pub fn retrieve<'p, T>(
             pool as *const apr_pool_t as *mut apr_pool_t
         );
         if get_result == (APR_SUCCESS as i32) {
-            return value_ptr.as_mut();
+            return value_ptr;
         } else {
-            return None;
+            return ptr::null_mut();
         }
     }
 }
@@ -83,7 +110,7 @@ pub fn access_pool_object<'t, T>(object_void: *mut c_void) -> Option<&'t mut T>
 }
 
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 pub mod test_utils {
     use crate::binding::apache2::{
         APR_SUCCESS, apr_pool_create_ex, apr_pool_t, apr_pool_destroy,
@@ -182,6 +209,19 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_alloc_init() -> Result<(), Box<dyn StdError>> {
+        let id1 = CString::new("id1")?;
+        with_pool(|pool| {
+            let (name, pool) = alloc_init(pool, &id1, None, String::from("tile"))?;
+            assert_eq!("tile", name.as_str(), "Failed to initialise the allocation");
+            let retrieved = retrieve::<String>(pool, &id1);
+            assert_eq!(Some(&mut String::from("tile")), retrieved, "Failed to retrieve the initialised allocation");
+            unsafe { ptr::drop_in_place(retrieved.unwrap() as *mut String) };
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_multiple_allocations() -> Result<(), Box<dyn StdError>> {
         let mut counter1 = Counter::new();
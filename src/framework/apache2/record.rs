@@ -1,7 +1,9 @@
-use crate::binding::apache2::{ apr_pool_t, conn_rec, process_rec, request_rec, server_rec, };
+use crate::binding::apache2::{
+    apr_pool_t, apr_table_get, conn_rec, process_rec, request_rec, server_rec,
+};
 use crate::schema::apache2::error::InvalidRecordError;
 
-use std::ffi::CStr;
+use std::ffi::{CStr, CString,};
 use std::option::Option;
 use std::ptr;
 
@@ -12,6 +14,12 @@ pub trait RequestRecord {
     fn get_server_record<'s>(self: &'s Self) -> Result<&'s server_rec, InvalidRecordError>;
 
     fn get_pool<'p>(&'p self) -> Result<&'p mut apr_pool_t, InvalidRecordError>;
+
+    fn get_header_in<'s>(&'s self, name: &str) -> Option<&'s str>;
+
+    fn get_client_ip<'s>(&'s self) -> Option<&'s str>;
+
+    fn get_method<'s>(&'s self) -> Option<&'s str>;
 }
 
 impl RequestRecord for request_rec {
@@ -47,6 +55,35 @@ impl RequestRecord for request_rec {
             Ok(unsafe { self.pool.as_mut().unwrap() })
         }
     }
+
+    fn get_header_in<'s>(&'s self, name: &str) -> Option<&'s str> {
+        if self.headers_in == ptr::null_mut() {
+            return None;
+        }
+        let key = CString::new(name).ok()?;
+        let value = unsafe { apr_table_get(self.headers_in, key.as_ptr()) };
+        if value == ptr::null() {
+            None
+        } else {
+            unsafe { CStr::from_ptr(value) }.to_str().ok()
+        }
+    }
+
+    fn get_client_ip<'s>(&'s self) -> Option<&'s str> {
+        if self.useragent_ip == ptr::null_mut() {
+            None
+        } else {
+            unsafe { CStr::from_ptr(self.useragent_ip) }.to_str().ok()
+        }
+    }
+
+    fn get_method<'s>(&'s self) -> Option<&'s str> {
+        if self.method == ptr::null() {
+            None
+        } else {
+            unsafe { CStr::from_ptr(self.method) }.to_str().ok()
+        }
+    }
 }
 
 pub trait ConnectionRecord {
@@ -82,6 +119,8 @@ impl ConnectionRecord for conn_rec {
 pub trait ServerRecord {
     fn get_host_name<'s>(&'s self) -> Option<&'s str>;
 
+    fn get_definition_name<'s>(&'s self) -> Option<&'s str>;
+
     fn get_pool<'s>(&'s self) -> Result<&'s mut apr_pool_t, InvalidRecordError>;
 
     fn get_process_record<'s>(&'s self) -> Result<&'s process_rec, InvalidRecordError>;
@@ -96,6 +135,14 @@ impl ServerRecord for server_rec {
         }
     }
 
+    fn get_definition_name<'s>(&'s self) -> Option<&'s str> {
+        if self.defn_name == ptr::null() {
+            None
+        } else {
+            unsafe { CStr::from_ptr(self.defn_name) }.to_str().ok()
+        }
+    }
+
     fn get_pool<'s>(&'s self) -> Result<&'s mut apr_pool_t, InvalidRecordError> {
         let proc_record = self.get_process_record().unwrap();
         if proc_record.pool == ptr::null_mut() {
@@ -144,7 +191,7 @@ impl ProcessRecord for process_rec {
 }
 
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 pub mod test_utils {
     use crate::binding::apache2::{
         __BindgenBitfieldUnit, ap_conn_keepalive_e, ap_logconf,
@@ -0,0 +1,38 @@
+use crate::binding::apache2::{
+    APR_BADARG, APR_SUCCESS,
+    ap_get_module_config, apr_pool_t, apr_status_t, server_rec,
+};
+use crate::framework::apache2::config::ServerConfig;
+use crate::framework::apache2::memory::{access_pool_object, alloc_init, AllocError,};
+
+use std::ffi::{CString, c_void,};
+use std::option::Option;
+use std::ptr;
+use std::result::Result;
+
+
+pub fn alloc_server_config<'p>(
+    pool: &'p mut apr_pool_t,
+    key: &CString,
+    value: ServerConfig,
+) -> Result<&'p mut ServerConfig, AllocError> {
+    let (server_config, _) = alloc_init(pool, key, Some(drop_server_config), value)?;
+    return Ok(server_config);
+}
+
+pub fn find_server_config<'s>(record: &'s server_rec) -> Option<&'s mut ServerConfig> {
+    if record.module_config == ptr::null_mut() {
+        return None;
+    }
+    let config_void = unsafe { ap_get_module_config(record.module_config, &crate::TILE_MODULE) };
+    return access_pool_object::<ServerConfig>(config_void);
+}
+
+#[no_mangle]
+extern "C" fn drop_server_config(config_void: *mut c_void) -> apr_status_t {
+    if config_void == ptr::null_mut() {
+        return APR_BADARG as apr_status_t;
+    }
+    unsafe { ptr::drop_in_place(config_void as *mut ServerConfig) };
+    return APR_SUCCESS as apr_status_t;
+}
@@ -0,0 +1,78 @@
+use crate::binding::renderd_protocol::{protoCmd, protocol, protocol_v2};
+use crate::schema::apache2::config::{ModuleConfig, ANY_PARAMETER,};
+use crate::schema::http::request::HttpRequest;
+use crate::schema::renderd::request::{
+    Constructable, RenderRequest, RenderRequestCommand, RenderRequestVersion,
+};
+use crate::schema::tile::identity::{LayerName, TileIdentity, META_TILE_WIDTH,};
+use crate::framework::apache2::context::HostContext;
+use crate::framework::apache2::record::test_utils::with_request_rec;
+use crate::adapter::render_proto::codec;
+use crate::adapter::slippy::interface::ReadContext;
+use crate::adapter::slippy::reader::SlippyRequestParser;
+use crate::io::storage::meta_tile::MetaTile;
+
+use chrono::Utc;
+
+use std::os::raw::c_int;
+
+
+// Entry points for the targets under fuzz/, which can't reach the private modules of the crate
+
+pub fn parse_slippy_uri(data: &[u8]) {
+    let uri = match std::str::from_utf8(data) {
+        Ok(uri) => uri,
+        Err(_) => return,
+    };
+    let mut module_config = ModuleConfig::new();
+    // Style parameters are allowed so the v3 parser is reached as well
+    for layer_config in module_config.layers.values_mut() {
+        layer_config.parameters_allowed = true;
+        layer_config.allowed_parameters = vec![String::from(ANY_PARAMETER)];
+    }
+    with_request_rec(|record| {
+        let context = ReadContext {
+            host_context: HostContext::new(&module_config, record),
+        };
+        let request = HttpRequest::new(uri, Utc::now(), record);
+        let _ = SlippyRequestParser::parse(&context, &request, uri);
+        Ok(())
+    }).unwrap();
+}
+
+pub fn decode_meta_tile(data: &[u8]) {
+    if let Ok(meta_tile) = MetaTile::from_bytes(data.to_vec(), None) {
+        // One past the last tile checks the offset bounds too
+        for tile_offset in 0..=((META_TILE_WIDTH * META_TILE_WIDTH) as u32) {
+            if let Ok(tile_ref) = meta_tile.select(tile_offset) {
+                tile_ref.with_tile(|raw_bytes| raw_bytes.len());
+            }
+        }
+    }
+}
+
+pub fn decode_renderd_response(data: &[u8]) {
+    let _ = codec::frame_version(data);
+    let _ = codec::remaining_frame_size(data);
+    let _ = codec::frame_command(data);
+    let _ = codec::frame_tile(data);
+    let layer = LayerName::from("default");
+    let tile_id = TileIdentity {
+        x: 0,
+        y: 0,
+        z: 0,
+        layer: layer.clone(),
+        parameter: None,
+    };
+    let v3_request = protocol::new(RenderRequestVersion::Three, RenderRequestCommand::Render, &layer, &tile_id, "png").unwrap();
+    let v2_request = protocol_v2 {
+        ver: RenderRequestVersion::Two as c_int,
+        cmd: RenderRequestCommand::Render as protoCmd,
+        x: v3_request.x,
+        y: v3_request.y,
+        z: v3_request.z,
+        xmlname: v3_request.xmlname,
+    };
+    let _ = codec::decode(&RenderRequest::V2(v2_request), data);
+    let _ = codec::decode(&RenderRequest::V3(v3_request), data);
+}
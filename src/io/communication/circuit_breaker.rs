@@ -0,0 +1,204 @@
+use crate::schema::communication::error::CommunicationError;
+use crate::schema::communication::health::CircuitState;
+
+use std::cmp::min;
+use std::io::{Error as IoError, ErrorKind,};
+use std::option::Option;
+use std::result::Result;
+use std::time::{Duration, Instant,};
+
+
+pub const FAILURE_THRESHOLD: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    consecutive_failures: u32,
+    backoff: Duration,
+    open_until: Option<Instant>,
+    is_trial_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold,
+            consecutive_failures: 0,
+            backoff: INITIAL_BACKOFF,
+            open_until: None,
+            is_trial_in_flight: false,
+        }
+    }
+
+    pub fn state(
+        &self,
+        now: &Instant,
+    ) -> CircuitState {
+        match &self.open_until {
+            None => CircuitState::Closed,
+            Some(until) if now < until => CircuitState::Open,
+            // Once the backoff expires a single trial request decides whether to close the circuit
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    pub fn allows_request(
+        &self,
+        now: &Instant,
+    ) -> bool {
+        match self.state(now) {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => !self.is_trial_in_flight,
+        }
+    }
+
+    // Unlike allows_request this claims the trial while half open, so it must be followed by a recorded outcome
+    pub fn try_acquire(
+        &mut self,
+        now: &Instant,
+    ) -> bool {
+        match self.state(now) {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen if self.is_trial_in_flight => false,
+            CircuitState::HalfOpen => {
+                self.is_trial_in_flight = true;
+                true
+            },
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    pub fn record_success(&mut self) -> () {
+        self.consecutive_failures = 0;
+        self.backoff = INITIAL_BACKOFF;
+        self.open_until = None;
+        self.is_trial_in_flight = false;
+    }
+
+    pub fn record_failure(
+        &mut self,
+        now: &Instant,
+    ) -> () {
+        self.consecutive_failures += 1;
+        self.is_trial_in_flight = false;
+        if self.open_until.is_some() || self.consecutive_failures >= self.failure_threshold {
+            self.trip(now);
+        }
+    }
+
+    // Only failing to reach renderd counts towards opening the circuit. A slow render or a frame renderd
+    // didn't understand says nothing about whether it is up, so it just ends the trial.
+    pub fn record_result<T>(
+        &mut self,
+        result: &Result<T, CommunicationError>,
+        now: &Instant,
+    ) -> () {
+        match result {
+            Ok(_) => self.record_success(),
+            Err(CommunicationError::Io(ioerr)) if is_unreachable(ioerr) => self.record_failure(now),
+            Err(_) => self.is_trial_in_flight = false,
+        }
+    }
+
+    fn trip(
+        &mut self,
+        now: &Instant,
+    ) -> () {
+        self.open_until = Some(*now + self.backoff);
+        self.backoff = min(self.backoff * 2, MAX_BACKOFF);
+    }
+}
+
+fn is_unreachable(ioerr: &IoError) -> bool {
+    matches!(
+        ioerr.kind(),
+        ErrorKind::ConnectionRefused | ErrorKind::NotFound | ErrorKind::PermissionDenied | ErrorKind::AddrNotAvailable
+            | ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::BrokenPipe | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted | ErrorKind::NotConnected | ErrorKind::UnexpectedEof
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_opens_after_threshold() -> Result<(), Box<dyn StdError>> {
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new(3);
+        breaker.record_failure(&now);
+        breaker.record_failure(&now);
+        assert_eq!(CircuitState::Closed, breaker.state(&now), "Opened before the threshold");
+        breaker.record_failure(&now);
+        assert_eq!(CircuitState::Open, breaker.state(&now), "Failed to open at the threshold");
+        assert!(!breaker.allows_request(&now), "Failed to reject requests while open");
+        Ok(())
+    }
+
+    #[test]
+    fn test_half_open_trial() -> Result<(), Box<dyn StdError>> {
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new(1);
+        breaker.record_failure(&now);
+        let after_first_backoff = now + INITIAL_BACKOFF;
+        assert_eq!(CircuitState::HalfOpen, breaker.state(&after_first_backoff), "Failed to half open after the backoff");
+        breaker.record_failure(&after_first_backoff);
+        assert_eq!(
+            CircuitState::Open,
+            breaker.state(&(after_first_backoff + INITIAL_BACKOFF)),
+            "Failed to double the backoff"
+        );
+        let after_second_backoff = after_first_backoff + (INITIAL_BACKOFF * 2);
+        assert!(breaker.try_acquire(&after_second_backoff), "Failed to allow a trial request");
+        breaker.record_success();
+        assert_eq!(CircuitState::Closed, breaker.state(&after_second_backoff), "Failed to close after a success");
+        assert_eq!(0, breaker.consecutive_failures(), "Failed to reset the failures");
+        Ok(())
+    }
+    #[test]
+    fn test_half_open_allows_one_trial() -> Result<(), Box<dyn StdError>> {
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new(1);
+        breaker.record_failure(&now);
+        let after_backoff = now + INITIAL_BACKOFF;
+        assert!(breaker.try_acquire(&after_backoff), "Failed to allow a trial request");
+        assert!(!breaker.allows_request(&after_backoff), "Allowed a request while the trial is in flight");
+        assert!(!breaker.try_acquire(&after_backoff), "Allowed a second trial request");
+        breaker.record_failure(&after_backoff);
+        let after_second_backoff = after_backoff + (INITIAL_BACKOFF * 2);
+        assert!(breaker.try_acquire(&after_second_backoff), "Failed to allow a trial after the failed one");
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_unreachable_is_a_failure() -> Result<(), Box<dyn StdError>> {
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new(1);
+        let timeout: Result<(), CommunicationError> = Err(CommunicationError::TimeoutError);
+        breaker.record_result(&timeout, &now);
+        let invalid: Result<(), CommunicationError> = Err(
+            CommunicationError::Io(IoError::new(ErrorKind::InvalidData, "test"))
+        );
+        breaker.record_result(&invalid, &now);
+        assert_eq!(0, breaker.consecutive_failures(), "Counted a reachable renderd as a failure");
+        let refused: Result<(), CommunicationError> = Err(
+            CommunicationError::Io(IoError::new(ErrorKind::ConnectionRefused, "test"))
+        );
+        breaker.record_result(&refused, &now);
+        assert_eq!(CircuitState::Open, breaker.state(&now), "Failed to count an unreachable renderd");
+        let after_backoff = now + INITIAL_BACKOFF;
+        assert!(breaker.try_acquire(&after_backoff), "Failed to allow a trial request");
+        breaker.record_result(&timeout, &after_backoff);
+        assert!(breaker.try_acquire(&after_backoff), "Failed to end the trial that timed out");
+        Ok(())
+    }
+}
@@ -82,6 +82,37 @@ impl HttpResponseWriter for request_rec {
         Ok(())
     }
 
+    fn set_error_http_header(
+        &mut self,
+        key: &HeaderName,
+        value: &HeaderValue,
+    ) -> Result<(), ToStrError> {
+        let c_key = CString::new(key.as_str()).unwrap();
+        let c_value = CString::new(value.to_str()?).unwrap();
+        debug!(
+            self.get_server_record().unwrap(),
+            "request_rec::set_error_http_header - setting {} - {}",
+            c_key.to_str().unwrap(),
+            c_value.to_str().unwrap()
+        );
+        unsafe {
+            apr_table_setn(
+                self.err_headers_out,
+                apr_psprintf(
+                    self.pool,
+                    cstr!("%s"),
+                    c_key.as_c_str().as_ptr(),
+                ),
+                apr_psprintf(
+                    self.pool,
+                    cstr!("%s"),
+                    c_value.as_c_str().as_ptr(),
+                )
+            );
+        }
+        Ok(())
+    }
+
     fn set_content_encoding(
         &mut self,
         encoding: &ContentEncoding,
@@ -161,6 +192,14 @@ impl HttpResponseWriter for request_rec {
         Ok(())
     }
 
+    fn set_error_http_header(
+        &mut self,
+        _key: &HeaderName,
+        _value: &HeaderValue,
+    ) -> Result<(), ToStrError> {
+        Ok(())
+    }
+
     fn set_content_encoding(
         &mut self,
         _encoding: &ContentEncoding,
@@ -197,8 +236,10 @@ impl HttpResponseWriter for request_rec {
 pub mod test_utils {
     use super::*;
     use crate::io::communication::interface::HttpResponseWriter;
+    use http::header::HeaderMap;
     use std::cmp::min;
     use std::collections::VecDeque;
+    use std::option::Option;
     use std::slice;
 
 
@@ -238,6 +279,14 @@ pub mod test_utils {
             Ok(())
         }
 
+        fn set_error_http_header(
+            &mut self,
+            _key: &HeaderName,
+            _value: &HeaderValue,
+        ) -> Result<(), ToStrError> {
+            Ok(())
+        }
+
         fn set_content_encoding(
             &mut self,
             _encoding: &ContentEncoding,
@@ -282,6 +331,103 @@ pub mod test_utils {
         }
 
     }
+
+    // Keeps everything written to it, the way Apache would send it to the client
+    pub struct CapturingWriter {
+        pub headers: HeaderMap,
+        pub error_headers: HeaderMap,
+        pub content_encoding: Option<ContentEncoding>,
+        pub content_type: Option<Mime>,
+        pub content_length: Option<usize>,
+        pub body: Vec<u8>,
+        pub flush_count: u32,
+    }
+
+    impl CapturingWriter {
+        pub fn new() -> CapturingWriter {
+            CapturingWriter {
+                headers: HeaderMap::new(),
+                error_headers: HeaderMap::new(),
+                content_encoding: None,
+                content_type: None,
+                content_length: None,
+                body: Vec::new(),
+                flush_count: 0,
+            }
+        }
+    }
+
+    impl HttpResponseWriter for CapturingWriter {
+
+        fn append_http_header(
+            &mut self,
+            key: &HeaderName,
+            value: &HeaderValue,
+        ) -> Result<(), ToStrError> {
+            // The Apache writer only accepts visible ASCII header values
+            value.to_str()?;
+            self.headers.append(key.clone(), value.clone());
+            Ok(())
+        }
+
+        fn set_http_header(
+            &mut self,
+            key: &HeaderName,
+            value: &HeaderValue,
+        ) -> Result<(), ToStrError> {
+            // The Apache writer only accepts visible ASCII header values
+            value.to_str()?;
+            self.headers.insert(key.clone(), value.clone());
+            Ok(())
+        }
+
+        fn set_error_http_header(
+            &mut self,
+            key: &HeaderName,
+            value: &HeaderValue,
+        ) -> Result<(), ToStrError> {
+            // The Apache writer only accepts visible ASCII header values
+            value.to_str()?;
+            self.error_headers.insert(key.clone(), value.clone());
+            Ok(())
+        }
+
+        fn set_content_encoding(
+            &mut self,
+            encoding: &ContentEncoding,
+        ) -> () {
+            self.content_encoding = Some(encoding.clone());
+        }
+
+        fn set_content_type(
+            &mut self,
+            mime: &Mime,
+        ) -> () {
+            self.content_type = Some(mime.clone());
+        }
+
+        fn set_content_length(
+            &mut self,
+            length: usize,
+        ) -> () {
+            self.content_length = Some(length);
+        }
+
+        fn write(
+            &mut self,
+            payload: *const u8,
+            length: usize,
+        ) -> i32 {
+            let payload_slice = unsafe { slice::from_raw_parts(payload, length) };
+            self.body.extend_from_slice(payload_slice);
+            length as i32
+        }
+
+        fn flush_response(&mut self) -> Result<(), ResponseWriteError> {
+            self.flush_count += 1;
+            Ok(())
+        }
+    }
 }
 
 
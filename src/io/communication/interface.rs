@@ -1,5 +1,7 @@
-use crate::schema::communication::error::ResponseWriteError;
+use crate::schema::communication::error::{CommunicationError, ResponseWriteError,};
+use crate::schema::communication::health::ChannelHealth;
 use crate::schema::http::encoding::ContentEncoding;
+use crate::schema::tile::identity::LayerName;
 use crate::framework::apache2::context::HostContext;
 
 use http::header::{ HeaderName, HeaderValue, ToStrError, };
@@ -9,32 +11,31 @@ use std::mem::size_of;
 use std::option::Option;
 use std::result::Result;
 use std::string::String;
+use std::time::Duration;
 
 
-#[derive(Debug)]
-pub enum CommunicationError {
-    TimeoutError,
-    Io(std::io::Error),
-}
-
-impl From<std::io::Error> for CommunicationError {
-    fn from(error: std::io::Error) -> Self {
-        return CommunicationError::Io(error);
-    }
-}
-
 pub enum RenderResponse {
     NotDone,
     Done(String),
 }
 
-pub trait BidirectionalChannel {
+// Channels are shared by every thread of an Apache child, so requests can be sent concurrently
+pub trait BidirectionalChannel: Send + Sync {
     fn send_blocking_request(
-        &mut self,
+        &self,
         context: &HostContext,
         request: &[u8],
         response_buffer: Option<Vec<u8>>,
+        response_timeout: Option<Duration>,
     ) -> Result<Vec<u8>, CommunicationError>;
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn health(&self) -> Vec<ChannelHealth> {
+        Vec::new()
+    }
 }
 
 pub trait HttpResponseWriter {
@@ -51,6 +52,12 @@ pub trait HttpResponseWriter {
         value: &HeaderValue,
     ) -> Result<(), ToStrError>;
 
+    fn set_error_http_header(
+        &mut self,
+        key: &HeaderName,
+        value: &HeaderValue,
+    ) -> Result<(), ToStrError>;
+
     fn set_content_encoding(
         &mut self,
         encoding: &ContentEncoding,
@@ -96,11 +103,19 @@ pub trait HttpResponseWriter {
 }
 
 pub struct RenderdCommunicationInventory<'i> {
-    pub primary_comms: &'i mut dyn BidirectionalChannel,
+    pub primary_comms: &'i dyn BidirectionalChannel,
 }
 
 pub trait CommunicationInventory {
-    fn primary_renderd_comms(&mut self) -> &mut dyn BidirectionalChannel;
+    fn primary_renderd_comms(&self) -> &dyn BidirectionalChannel;
+
+    // Layers without their own renderd endpoints share the primary channel
+    fn renderd_comms(
+        &self,
+        layer: &LayerName,
+    ) -> &dyn BidirectionalChannel;
+
+    fn renderd_health(&self) -> Vec<ChannelHealth>;
     // TODO: add a method that returns the concrete type name
 }
 
@@ -112,10 +127,11 @@ pub mod test_utils {
 
     impl BidirectionalChannel for EmptyResultBiChannel {
         fn send_blocking_request(
-            &mut self,
+            &self,
             _context: &HostContext,
             _request: &[u8],
             _response_buffer: Option<Vec<u8>>,
+            _response_timeout: Option<Duration>,
         ) -> Result<Vec<u8>, CommunicationError> {
             Ok(Vec::new())
         }
@@ -134,8 +150,19 @@ pub mod test_utils {
     }
 
     impl CommunicationInventory for EmptyResultCommunicationInventory {
-        fn primary_renderd_comms(&mut self) -> &mut dyn BidirectionalChannel {
-            &mut self.renderd_comms
+        fn primary_renderd_comms(&self) -> &dyn BidirectionalChannel {
+            &self.renderd_comms
+        }
+
+        fn renderd_comms(
+            &self,
+            _layer: &LayerName,
+        ) -> &dyn BidirectionalChannel {
+            &self.renderd_comms
+        }
+
+        fn renderd_health(&self) -> Vec<ChannelHealth> {
+            Vec::new()
         }
     }
 
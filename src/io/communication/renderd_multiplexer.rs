@@ -0,0 +1,717 @@
+use crate::adapter::render_proto::codec::{
+    downgrade_to_v2, frame_command, frame_tile, frame_version, in_version_of, remaining_frame_size, HEADER_SIZE,
+};
+use crate::io::communication::circuit_breaker::{CircuitBreaker, FAILURE_THRESHOLD,};
+use crate::io::communication::interface::BidirectionalChannel;
+use crate::io::communication::renderd_socket::{is_connection_lost, open_stream, RenderdStream,};
+use crate::framework::apache2::context::HostContext;
+use crate::schema::apache2::config::{RenderdConfig, RenderdEndpoint,};
+use crate::schema::communication::error::CommunicationError;
+use crate::schema::communication::health::ChannelHealth;
+use crate::schema::renderd::request::RenderRequestVersion;
+use crate::schema::renderd::response::RenderResponseCommand;
+use crate::schema::tile::identity::TileIdentity;
+
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::io::{Error as IoError, ErrorKind, Read, Write,};
+use std::option::Option;
+use std::os::raw::c_int;
+use std::result::Result;
+use std::sync::{Arc, Condvar, Mutex, Weak,};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering,};
+use std::thread;
+use std::time::{Duration, Instant,};
+use std::vec::Vec;
+
+
+// A zero render timeout turns off the socket timeout, but a waiter is still released eventually
+const MAX_RENDER_WAIT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone)]
+enum Outcome {
+    Replied(Vec<u8>),
+    Disconnected,
+}
+
+struct PendingRender {
+    connection_id: AtomicU64,
+    // Only changed while the pending renders are locked, so the last waiter to give up can remove the render
+    waiter_count: AtomicUsize,
+    outcome: Mutex<Option<Outcome>>,
+    completed: Condvar,
+}
+
+impl PendingRender {
+    fn new() -> PendingRender {
+        PendingRender {
+            connection_id: AtomicU64::new(0),
+            waiter_count: AtomicUsize::new(1),
+            outcome: Mutex::new(None),
+            completed: Condvar::new(),
+        }
+    }
+
+    fn complete(
+        &self,
+        outcome: Outcome,
+    ) -> () {
+        *self.outcome.lock().unwrap() = Some(outcome);
+        self.completed.notify_all();
+    }
+}
+
+struct Connection {
+    id: u64,
+    writer: Box<dyn RenderdStream>,
+    is_alive: Arc<AtomicBool>,
+}
+
+struct Shared {
+    endpoint: RenderdEndpoint,
+    config: RenderdConfig,
+    connection: Mutex<Option<Connection>>,
+    pending_by_tile: Mutex<HashMap<TileIdentity, Arc<PendingRender>>>,
+    connect_count: AtomicU64,
+    // Set once the renderd on the current connection has been found to only accept v2 requests
+    is_v2_only: AtomicBool,
+    breaker: Mutex<CircuitBreaker>,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        // Unblocks the dispatcher thread so it can exit
+        if let Some(connection) = self.connection.get_mut().unwrap().take() {
+            connection.writer.shutdown_stream().ok();
+        }
+    }
+}
+
+// Shares one renderd connection between the threads of an Apache child. Requests are written by the
+// calling thread, while a dispatcher thread reads the responses and wakes the threads waiting on them.
+// The connection is opened on first use and again after renderd drops it, so a renderd restart does not
+// require an Apache restart. A request for a tile that is already being rendered waits on the outstanding
+// render instead of queuing another one.
+#[derive(Clone)]
+pub struct RenderdMultiplexer {
+    shared: Arc<Shared>,
+}
+
+impl RenderdMultiplexer {
+    pub fn new(
+        endpoint: &RenderdEndpoint,
+        config: &RenderdConfig,
+    ) -> RenderdMultiplexer {
+        RenderdMultiplexer {
+            shared: Arc::new(
+                Shared {
+                    endpoint: endpoint.clone(),
+                    config: config.clone(),
+                    connection: Mutex::new(None),
+                    pending_by_tile: Mutex::new(HashMap::new()),
+                    connect_count: AtomicU64::new(0),
+                    is_v2_only: AtomicBool::new(false),
+                    breaker: Mutex::new(CircuitBreaker::new(FAILURE_THRESHOLD)),
+                }
+            ),
+        }
+    }
+
+    fn submit(
+        &self,
+        request: &[u8],
+        response_timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, CommunicationError> {
+        let is_v3_request = frame_version(request) == Ok(RenderRequestVersion::Three as c_int);
+        let is_v2_only = self.shared.is_v2_only.load(Ordering::Acquire);
+        if is_v3_request && is_v2_only {
+            return self.submit_frame(&downgrade_to_v2(request), response_timeout);
+        }
+        let reply = self.submit_frame(request, response_timeout)?;
+        let is_ignored = frame_command(&reply) == Ok(RenderResponseCommand::InvalidRequestIgnored);
+        if is_v3_request && is_ignored {
+            // renderd builds from before v3 ignore requests of a newer version than they know
+            let retried = self.submit_frame(&downgrade_to_v2(request), response_timeout)?;
+            if frame_command(&retried) != Ok(RenderResponseCommand::InvalidRequestIgnored) {
+                self.shared.is_v2_only.store(true, Ordering::Release);
+            }
+            return Ok(retried);
+        }
+        return Ok(reply);
+    }
+
+    fn submit_frame(
+        &self,
+        request: &[u8],
+        response_timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, CommunicationError> {
+        // The tile is taken from the frame as sent, so it matches the reply whichever version renderd answers in
+        let tile = frame_tile(request).map_err(|error| IoError::new(ErrorKind::InvalidInput, error))?;
+        let mut may_resend = true;
+        loop {
+            let (pending, is_new) = self.register(&tile);
+            let mut is_reused = false;
+            if is_new {
+                match self.send(request, &pending) {
+                    Ok(was_reused) => is_reused = was_reused,
+                    Err(ioerr) => {
+                        self.shared.pending_by_tile.lock().unwrap().retain(|_, other| !Arc::ptr_eq(other, &pending));
+                        pending.complete(Outcome::Disconnected);
+                        // Unlike a slow render, a connect or write that times out means renderd can't be reached
+                        return Err(CommunicationError::Io(ioerr));
+                    },
+                };
+            }
+            match self.wait(&pending, response_timeout)? {
+                Outcome::Replied(frame) => return Ok(frame),
+                Outcome::Disconnected if is_reused && may_resend => {
+                    // The connection was left over from before a renderd restart, so the request is sent once
+                    // more on a new one before the breaker hears about it
+                    may_resend = false;
+                },
+                Outcome::Disconnected => return Err(
+                    CommunicationError::Io(IoError::new(ErrorKind::ConnectionAborted, "renderd closed the connection"))
+                ),
+            };
+        }
+    }
+
+    fn register(
+        &self,
+        tile: &TileIdentity,
+    ) -> (Arc<PendingRender>, bool) {
+        let mut pending_by_tile = self.shared.pending_by_tile.lock().unwrap();
+        if let Some(existing) = pending_by_tile.get(tile) {
+            existing.waiter_count.fetch_add(1, Ordering::AcqRel);
+            return (existing.clone(), false);
+        }
+        let pending = Arc::new(PendingRender::new());
+        pending_by_tile.insert(tile.clone(), pending.clone());
+        return (pending, true);
+    }
+
+    // Returns whether the request went out on a connection that was already open
+    fn send(
+        &self,
+        request: &[u8],
+        pending: &PendingRender,
+    ) -> Result<bool, IoError> {
+        // The connection stays locked until the render is tagged with the connection it was written to, so the
+        // dispatcher of a connection that drops can't miss it
+        let mut connection = self.shared.connection.lock().unwrap();
+        let is_reused = connection.as_ref()
+            .map(|connection| connection.is_alive.load(Ordering::Acquire))
+            .unwrap_or(false);
+        if !is_reused {
+            *connection = Some(self.connect()?);
+        }
+        match write_request(&mut connection, request, pending) {
+            Err(ioerr) if is_reused && is_connection_lost(&ioerr) => {
+                *connection = Some(self.connect()?);
+                write_request(&mut connection, request, pending)?;
+                return Ok(false);
+            },
+            result => {
+                result?;
+                return Ok(is_reused);
+            },
+        };
+    }
+
+    fn connect(&self) -> Result<Connection, IoError> {
+        let writer = open_stream(&self.shared.endpoint, &self.shared.config)?;
+        let reader = writer.try_clone_stream()?;
+        // The dispatcher waits for as long as any render is outstanding
+        reader.set_read_timeout(None)?;
+        let id = self.shared.connect_count.fetch_add(1, Ordering::AcqRel) + 1;
+        // The connection may be to a different renderd build, so the version is negotiated again
+        self.shared.is_v2_only.store(false, Ordering::Release);
+        let is_alive = Arc::new(AtomicBool::new(true));
+        let dispatcher_alive = is_alive.clone();
+        let shared = Arc::downgrade(&self.shared);
+        thread::Builder::new()
+            .name(String::from("renderd-dispatcher"))
+            .spawn(move || dispatch(shared, reader, id, dispatcher_alive))?;
+        Ok(
+            Connection {
+                id,
+                writer,
+                is_alive,
+            }
+        )
+    }
+
+    fn wait(
+        &self,
+        pending: &Arc<PendingRender>,
+        response_timeout: Option<Duration>,
+    ) -> Result<Outcome, CommunicationError> {
+        let wait_timeout = response_timeout
+            .or(Some(self.shared.config.render_timeout))
+            .filter(|timeout| !timeout.is_zero())
+            .unwrap_or(MAX_RENDER_WAIT);
+        let outcome = pending.outcome.lock().unwrap();
+        let (outcome, wait_result) = pending.completed
+            .wait_timeout_while(outcome, wait_timeout, |outcome| outcome.is_none())
+            .unwrap();
+        if wait_result.timed_out() {
+            drop(outcome);
+            self.abandon(pending);
+            return Err(CommunicationError::TimeoutError);
+        }
+        return Ok(outcome.clone().unwrap());
+    }
+
+    // Once every waiter has given up the render is forgotten, so the next request for the tile is sent
+    // to renderd again rather than waiting on a reply that may never come
+    fn abandon(
+        &self,
+        pending: &Arc<PendingRender>,
+    ) -> () {
+        let mut pending_by_tile = self.shared.pending_by_tile.lock().unwrap();
+        if pending.waiter_count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            pending_by_tile.retain(|_, other| !Arc::ptr_eq(other, pending));
+        }
+    }
+}
+
+fn write_request(
+    connection: &mut Option<Connection>,
+    request: &[u8],
+    pending: &PendingRender,
+) -> Result<(), IoError> {
+    let current = connection.as_mut().unwrap();
+    pending.connection_id.store(current.id, Ordering::Release);
+    let write_result = current.writer.write_all(request).and_then(|_| current.writer.flush());
+    if write_result.is_err() {
+        if let Some(broken) = connection.take() {
+            broken.writer.shutdown_stream().ok();
+        }
+    }
+    return write_result;
+}
+
+fn dispatch(
+    shared: Weak<Shared>,
+    mut reader: Box<dyn RenderdStream>,
+    connection_id: u64,
+    is_alive: Arc<AtomicBool>,
+) -> () {
+    while let Ok(frame) = read_frame(&mut reader) {
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        let tile = match frame_tile(&frame) {
+            Ok(tile) => tile,
+            // A frame that can't be matched to a request is dropped, and its waiters will time out
+            Err(_) => continue,
+        };
+        let mut pending_by_tile = shared.pending_by_tile.lock().unwrap();
+        let mut matched = pending_by_tile.remove(&tile);
+        if matched.is_none() && tile.parameter.is_none() {
+            // A v2 reply to a v3 request has lost the style parameter of the tile
+            let sent = pending_by_tile.keys()
+                .find(|sent| TileIdentity { parameter: None, ..(*sent).clone() } == tile)
+                .cloned();
+            matched = sent.and_then(|sent| pending_by_tile.remove(&sent));
+        }
+        if let Some(pending) = matched {
+            pending.complete(Outcome::Replied(frame));
+        }
+    }
+    // Nothing more will be read from this connection, so its waiters are released straight away
+    if let Some(shared) = shared.upgrade() {
+        // A request being written holds the connection, so it is tagged before the waiters are released
+        {
+            let _connection = shared.connection.lock().unwrap();
+            is_alive.store(false, Ordering::Release);
+        }
+        shared.pending_by_tile.lock().unwrap().retain(|_, pending| {
+            if pending.connection_id.load(Ordering::Acquire) == connection_id {
+                pending.complete(Outcome::Disconnected);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+// The frame size depends on the version renderd answered with. A frame of an unknown version can't be
+// skipped, so it ends the connection.
+fn read_frame(reader: &mut Box<dyn RenderdStream>) -> Result<Vec<u8>, IoError> {
+    let mut frame = vec![0u8; HEADER_SIZE];
+    reader.read_exact(&mut frame)?;
+    let remaining = remaining_frame_size(&frame).map_err(|error| IoError::new(ErrorKind::InvalidData, error))?;
+    frame.resize(HEADER_SIZE + remaining, 0);
+    reader.read_exact(&mut frame[HEADER_SIZE..])?;
+    return Ok(frame);
+}
+
+impl BidirectionalChannel for RenderdMultiplexer {
+    fn send_blocking_request(
+        &self,
+        _context: &HostContext,
+        request: &[u8],
+        response_buffer: Option<Vec<u8>>,
+        response_timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, CommunicationError> {
+        if !self.shared.breaker.lock().unwrap().try_acquire(&Instant::now()) {
+            return Err(CommunicationError::Unavailable);
+        }
+        let result = self.submit(request, response_timeout);
+        self.shared.breaker.lock().unwrap().record_result(&result, &Instant::now());
+        let mut reply = result?;
+        if frame_version(&reply) != frame_version(request) {
+            // The caller gets a reply in the version it asked for
+            reply = in_version_of(request, &reply)
+                .map_err(|error| IoError::new(ErrorKind::InvalidData, error))?;
+        }
+        let mut output = match response_buffer {
+            Some(buffer) => buffer,
+            None => Vec::new()
+        };
+        output.extend(reply);
+        return Ok(output);
+    }
+
+    fn is_available(&self) -> bool {
+        self.shared.breaker.lock().unwrap().allows_request(&Instant::now())
+    }
+
+    fn health(&self) -> Vec<ChannelHealth> {
+        let breaker = self.shared.breaker.lock().unwrap();
+        vec![
+            ChannelHealth {
+                endpoint: self.shared.endpoint.to_string(),
+                circuit: breaker.state(&Instant::now()),
+                consecutive_failures: breaker.consecutive_failures(),
+                reconnect_count: self.shared.connect_count.load(Ordering::Acquire).saturating_sub(1),
+            }
+        ]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::render_proto::codec::{decode, encode,};
+    use crate::adapter::render_proto::codec::test_utils::with_command;
+    use crate::binding::renderd_protocol::protocol;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+    use crate::schema::apache2::config::ModuleConfig;
+    use crate::schema::communication::health::CircuitState;
+    use crate::schema::renderd::request::{
+        Constructable, RenderRequest, RenderRequestCommand, RenderRequestVersion,
+    };
+    use crate::schema::tile::identity::LayerName;
+
+    use std::error::Error as StdError;
+    use std::net::{Shutdown, TcpListener, TcpStream,};
+    use std::string::String;
+    use std::sync::mpsc;
+
+    const TIMEOUT: Option<Duration> = Some(Duration::from_secs(5));
+
+    fn make_request(
+        x: i32,
+        y: i32,
+    ) -> Result<RenderRequest, Box<dyn StdError>> {
+        let layer = LayerName::from("osm");
+        let tile_id = TileIdentity { x, y, z: 3, layer: layer.clone(), parameter: None, };
+        let value = protocol::new(RenderRequestVersion::Three, RenderRequestCommand::Render, &layer, &tile_id, "png")?;
+        Ok(RenderRequest::V3(value))
+    }
+
+    fn start_renderd() -> Result<(TcpListener, RenderdMultiplexer), Box<dyn StdError>> {
+        let module_config = ModuleConfig::new();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let endpoint = RenderdEndpoint::Tcp {
+            host: String::from("127.0.0.1"),
+            port: listener.local_addr()?.port(),
+        };
+        Ok((listener, RenderdMultiplexer::new(&endpoint, &module_config.renderd)))
+    }
+
+    fn read_frame(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+        let mut frame = vec![0u8; HEADER_SIZE];
+        stream.read_exact(&mut frame)?;
+        let remaining = remaining_frame_size(&frame).map_err(|error| IoError::new(ErrorKind::InvalidData, error))?;
+        frame.resize(HEADER_SIZE + remaining, 0);
+        stream.read_exact(&mut frame[HEADER_SIZE..])?;
+        Ok(frame)
+    }
+
+    #[test]
+    fn test_match_responses_out_of_order() -> Result<(), Box<dyn StdError>> {
+        let (listener, multiplexer) = start_renderd()?;
+        let renderd = thread::spawn(move || -> std::io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            let first = read_frame(&mut stream)?;
+            let second = read_frame(&mut stream)?;
+            for frame in [second, first] {
+                let command = match frame_tile(&frame) {
+                    Ok(tile) if tile.x == 0 => RenderResponseCommand::Done,
+                    _ => RenderResponseCommand::NotDone,
+                };
+                stream.write_all(&with_command(&frame, command))?;
+            }
+            Ok(())
+        });
+        let waiters: Vec<_> = vec![(0, 0), (8, 8)].into_iter().map(|(x, y)| {
+            let multiplexer = multiplexer.clone();
+            thread::spawn(move || -> Result<(i32, RenderResponseCommand), String> {
+                let request = encode(&make_request(x, y).map_err(|error| error.to_string())?);
+                let reply = multiplexer.submit(&request, TIMEOUT).map_err(|error| error.to_string())?;
+                Ok((x, frame_command(&reply).map_err(|error| error.to_string())?))
+            })
+        }).collect();
+        let mut commands = Vec::new();
+        for waiter in waiters {
+            commands.push(waiter.join().unwrap()?);
+        }
+        renderd.join().unwrap()?;
+        commands.sort_by_key(|(x, _)| *x);
+        assert_eq!(
+            vec![(0, RenderResponseCommand::Done), (8, RenderResponseCommand::NotDone)],
+            commands,
+            "Failed to match the responses"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_share_one_render_of_a_tile() -> Result<(), Box<dyn StdError>> {
+        let (listener, multiplexer) = start_renderd()?;
+        let (read_sender, read_receiver) = mpsc::channel::<()>();
+        let (reply_sender, reply_receiver) = mpsc::channel::<()>();
+        let renderd = thread::spawn(move || -> std::io::Result<u32> {
+            let (mut stream, _) = listener.accept()?;
+            let frame = read_frame(&mut stream)?;
+            read_sender.send(()).ok();
+            reply_receiver.recv().ok();
+            stream.write_all(&with_command(&frame, RenderResponseCommand::Done))?;
+            // Any further render request would be read before the connection is closed
+            stream.set_read_timeout(Some(Duration::from_millis(200)))?;
+            let mut request_count = 1;
+            while read_frame(&mut stream).is_ok() {
+                request_count += 1;
+            }
+            stream.shutdown(Shutdown::Both).ok();
+            Ok(request_count)
+        });
+        let spawn_waiter = || {
+            let multiplexer = multiplexer.clone();
+            thread::spawn(move || -> Result<bool, String> {
+                let request = make_request(1, 2).map_err(|error| error.to_string())?;
+                let reply = multiplexer.submit(&encode(&request), TIMEOUT).map_err(|error| error.to_string())?;
+                Ok(decode(&request, &reply).map_err(|error| error.to_string())? == RenderResponseCommand::Done)
+            })
+        };
+        let first = spawn_waiter();
+        read_receiver.recv()?;
+        let second = spawn_waiter();
+        thread::sleep(Duration::from_millis(100));
+        reply_sender.send(())?;
+        assert!(first.join().unwrap()?, "Failed to reply to the first request");
+        assert!(second.join().unwrap()?, "Failed to reply to the request waiting on the render");
+        assert_eq!(1, renderd.join().unwrap()?, "Failed to share the render");
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_requests_over_one_connection() -> Result<(), Box<dyn StdError>> {
+        let (listener, multiplexer) = start_renderd()?;
+        let (done_sender, done_receiver) = mpsc::channel::<()>();
+        let renderd = thread::spawn(move || -> std::io::Result<Vec<Vec<u8>>> {
+            let (mut stream, _) = listener.accept()?;
+            let mut requests = Vec::new();
+            for command in [RenderResponseCommand::Done, RenderResponseCommand::NotDone] {
+                let request = read_frame(&mut stream)?;
+                stream.write_all(&with_command(&request, command))?;
+                requests.push(request);
+            }
+            // Like renderd the connection stays open
+            done_receiver.recv().ok();
+            Ok(requests)
+        });
+        let module_config = ModuleConfig::new();
+        let (tile, meta) = (encode(&make_request(1, 2)?), encode(&make_request(8, 2)?));
+        let result = with_request_rec(|record| {
+            let context = HostContext::new(&module_config, record);
+            let first = multiplexer.send_blocking_request(&context, &tile, None, TIMEOUT)?;
+            assert_eq!(with_command(&tile, RenderResponseCommand::Done), first, "Failed to read the response");
+            let second = multiplexer.send_blocking_request(&context, &meta, None, TIMEOUT)?;
+            assert_eq!(with_command(&meta, RenderResponseCommand::NotDone), second, "Failed to read the second response");
+            assert_eq!(0, multiplexer.health()[0].reconnect_count, "Failed to reuse the connection");
+            Ok(())
+        });
+        done_sender.send(())?;
+        result?;
+        assert_eq!(vec![tile, meta], renderd.join().unwrap()?, "Failed to write the requests");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconnect_after_renderd_closes() -> Result<(), Box<dyn StdError>> {
+        let (listener, multiplexer) = start_renderd()?;
+        let renderd = thread::spawn(move || -> std::io::Result<Vec<Vec<u8>>> {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept()?;
+                let request = read_frame(&mut stream)?;
+                stream.write_all(&with_command(&request, RenderResponseCommand::Done))?;
+                stream.shutdown(Shutdown::Both)?;
+                requests.push(request);
+            }
+            Ok(requests)
+        });
+        let module_config = ModuleConfig::new();
+        let (tile, meta) = (encode(&make_request(1, 2)?), encode(&make_request(8, 2)?));
+        with_request_rec(|record| {
+            let context = HostContext::new(&module_config, record);
+            let first = multiplexer.send_blocking_request(&context, &tile, None, TIMEOUT)?;
+            assert_eq!(with_command(&tile, RenderResponseCommand::Done), first, "Failed to read the response");
+            let second = multiplexer.send_blocking_request(&context, &meta, None, TIMEOUT)?;
+            assert_eq!(
+                with_command(&meta, RenderResponseCommand::Done),
+                second,
+                "Failed to read the response after reconnecting"
+            );
+            let health = multiplexer.health();
+            assert_eq!(1, health[0].reconnect_count, "Failed to count the reconnect");
+            assert_eq!(0, health[0].consecutive_failures, "Counted the stale connection as a failure");
+            Ok(())
+        })?;
+        assert_eq!(vec![tile, meta], renderd.join().unwrap()?, "Failed to write the requests");
+        Ok(())
+    }
+
+    #[test]
+    fn test_timeout_is_not_a_failure() -> Result<(), Box<dyn StdError>> {
+        let (listener, multiplexer) = start_renderd()?;
+        let (done_sender, done_receiver) = mpsc::channel::<()>();
+        let renderd = thread::spawn(move || -> std::io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            read_frame(&mut stream)?;
+            // The render never finishes, so the request times out
+            done_receiver.recv().ok();
+            Ok(())
+        });
+        let module_config = ModuleConfig::new();
+        let tile = encode(&make_request(1, 2)?);
+        let result = with_request_rec(|record| {
+            let context = HostContext::new(&module_config, record);
+            let result = multiplexer.send_blocking_request(&context, &tile, None, Some(Duration::from_millis(100)));
+            assert!(matches!(result, Err(CommunicationError::TimeoutError)), "Failed to time out");
+            let health = multiplexer.health();
+            assert_eq!(CircuitState::Closed, health[0].circuit, "Opened the circuit after a timeout");
+            assert_eq!(0, health[0].consecutive_failures, "Counted a slow render as a failure");
+            Ok(())
+        });
+        done_sender.send(())?;
+        result?;
+        renderd.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_resend_once_every_waiter_timed_out() -> Result<(), Box<dyn StdError>> {
+        let (listener, multiplexer) = start_renderd()?;
+        let renderd = thread::spawn(move || -> std::io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            // The first render never finishes
+            read_frame(&mut stream)?;
+            let frame = read_frame(&mut stream)?;
+            stream.write_all(&with_command(&frame, RenderResponseCommand::Done))
+        });
+        let request = encode(&make_request(0, 0)?);
+        let result = multiplexer.submit(&request, Some(Duration::from_millis(100)));
+        assert!(matches!(result, Err(CommunicationError::TimeoutError)), "Failed to time out");
+        assert!(
+            multiplexer.shared.pending_by_tile.lock().unwrap().is_empty(),
+            "Failed to drop the render once its waiter timed out"
+        );
+        let reply = multiplexer.submit(&request, TIMEOUT)?;
+        assert_eq!(RenderResponseCommand::Done, frame_command(&reply)?, "Failed to send the render again");
+        renderd.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_release_waiters_when_renderd_disconnects() -> Result<(), Box<dyn StdError>> {
+        let (listener, multiplexer) = start_renderd()?;
+        let renderd = thread::spawn(move || -> std::io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            read_frame(&mut stream)?;
+            stream.shutdown(Shutdown::Both)
+        });
+        let request = encode(&make_request(0, 0)?);
+        let result = multiplexer.submit(&request, TIMEOUT);
+        renderd.join().unwrap()?;
+        assert!(
+            matches!(result, Err(CommunicationError::Io(ref ioerr)) if ioerr.kind() == ErrorKind::ConnectionAborted),
+            "Failed to release the waiter"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fail_fast_while_renderd_is_down() -> Result<(), Box<dyn StdError>> {
+        let module_config = ModuleConfig::new();
+        let endpoint = RenderdEndpoint::Tcp {
+            host: String::from("127.0.0.1"),
+            port: TcpListener::bind("127.0.0.1:0")?.local_addr()?.port(),
+        };
+        let multiplexer = RenderdMultiplexer::new(&endpoint, &module_config.renderd);
+        let request = encode(&make_request(0, 0)?);
+        with_request_rec(|record| {
+            let context = HostContext::new(&module_config, record);
+            for _ in 0..FAILURE_THRESHOLD {
+                let result = multiplexer.send_blocking_request(&context, &request, None, TIMEOUT);
+                assert!(matches!(result, Err(CommunicationError::Io(_))), "Failed to report the connect error");
+            }
+            let result = multiplexer.send_blocking_request(&context, &request, None, TIMEOUT);
+            assert!(matches!(result, Err(CommunicationError::Unavailable)), "Failed to fail fast");
+            assert!(!multiplexer.is_available(), "Failed to report the multiplexer as unavailable");
+            let health = multiplexer.health();
+            assert_eq!(CircuitState::Open, health[0].circuit, "Failed to report the open circuit");
+            assert_eq!(FAILURE_THRESHOLD, health[0].consecutive_failures, "Incorrect failure count");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_fall_back_to_v2_when_renderd_ignores_v3() -> Result<(), Box<dyn StdError>> {
+        let (listener, multiplexer) = start_renderd()?;
+        let renderd = thread::spawn(move || -> std::io::Result<Vec<c_int>> {
+            let (mut stream, _) = listener.accept()?;
+            let mut versions = Vec::new();
+            for _ in 0..3 {
+                let frame = read_frame(&mut stream)?;
+                let version = frame_version(&frame).map_err(|error| IoError::new(ErrorKind::InvalidData, error))?;
+                // An old renderd only knows v2, so it answers anything newer with a v2 ignore
+                let reply = if version == RenderRequestVersion::Two as c_int {
+                    with_command(&frame, RenderResponseCommand::Done)
+                } else {
+                    with_command(&downgrade_to_v2(&frame), RenderResponseCommand::InvalidRequestIgnored)
+                };
+                stream.write_all(&reply)?;
+                versions.push(version);
+            }
+            Ok(versions)
+        });
+        let first = multiplexer.submit(&encode(&make_request(0, 0)?), TIMEOUT)?;
+        assert_eq!(RenderResponseCommand::Done, frame_command(&first)?, "Failed to retry the render as v2");
+        let second = multiplexer.submit(&encode(&make_request(8, 8)?), TIMEOUT)?;
+        assert_eq!(RenderResponseCommand::Done, frame_command(&second)?, "Failed to render as v2");
+        assert_eq!(
+            vec![RenderRequestVersion::Three as c_int, RenderRequestVersion::Two as c_int, RenderRequestVersion::Two as c_int],
+            renderd.join().unwrap()?,
+            "Failed to send v2 requests after the fallback"
+        );
+        Ok(())
+    }
+}
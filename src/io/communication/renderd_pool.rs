@@ -0,0 +1,330 @@
+use crate::io::communication::interface::BidirectionalChannel;
+use crate::io::communication::renderd_multiplexer::RenderdMultiplexer;
+use crate::framework::apache2::context::HostContext;
+use crate::schema::apache2::config::{BalancePolicy, RenderdConfig, RenderdEndpoint,};
+use crate::schema::communication::error::CommunicationError;
+use crate::schema::communication::health::ChannelHealth;
+
+use std::boxed::Box;
+use std::option::Option;
+use std::result::Result;
+use std::sync::atomic::{AtomicUsize, Ordering,};
+use std::time::Duration;
+use std::vec::Vec;
+
+
+struct PooledEndpoint {
+    endpoint: RenderdEndpoint,
+    channel: Box<dyn BidirectionalChannel>,
+    // Requests that are waiting on the endpoint across every thread sharing the pool
+    outstanding: AtomicUsize,
+}
+
+// Counts a request as outstanding until it is dropped, whether the endpoint replied or failed
+struct InFlight<'p> {
+    outstanding: &'p AtomicUsize,
+}
+
+impl<'p> InFlight<'p> {
+    fn new(outstanding: &'p AtomicUsize) -> InFlight<'p> {
+        outstanding.fetch_add(1, Ordering::AcqRel);
+        InFlight {
+            outstanding,
+        }
+    }
+}
+
+impl<'p> Drop for InFlight<'p> {
+    fn drop(&mut self) {
+        self.outstanding.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+pub struct RenderdPool {
+    endpoints: Vec<PooledEndpoint>,
+    policy: BalancePolicy,
+    next_index: AtomicUsize,
+}
+
+impl RenderdPool {
+    pub fn new(
+        endpoints: Vec<RenderdEndpoint>,
+        config: &RenderdConfig,
+    ) -> RenderdPool {
+        let channels = endpoints.into_iter().map(|endpoint| {
+            let channel: Box<dyn BidirectionalChannel> = Box::new(RenderdMultiplexer::new(&endpoint, config));
+            (endpoint, channel)
+        }).collect();
+        RenderdPool::with_channels(channels, config.balance_policy)
+    }
+
+    pub fn with_channels(
+        channels: Vec<(RenderdEndpoint, Box<dyn BidirectionalChannel>)>,
+        policy: BalancePolicy,
+    ) -> RenderdPool {
+        let endpoints = channels.into_iter().map(|(endpoint, channel)| {
+            PooledEndpoint {
+                endpoint,
+                channel,
+                outstanding: AtomicUsize::new(0),
+            }
+        }).collect();
+        RenderdPool {
+            endpoints,
+            policy,
+            next_index: AtomicUsize::new(0),
+        }
+    }
+
+    // Each channel tracks the circuit of its endpoint, so an endpoint is skipped while its circuit is open
+    fn candidates(&self) -> Vec<usize> {
+        let count = self.endpoints.len();
+        if count == 0 {
+            return Vec::new();
+        }
+        let start = self.next_index.fetch_add(1, Ordering::AcqRel) % count;
+        let mut order: Vec<usize> = (0..count)
+            .map(|offset| (start + offset) % count)
+            .filter(|index| self.endpoints[*index].channel.is_available())
+            .collect();
+        if let BalancePolicy::LeastOutstanding = self.policy {
+            // The sort is stable, so endpoints with equal load keep their round robin order
+            order.sort_by_key(|index| self.endpoints[*index].outstanding.load(Ordering::Acquire));
+        }
+        return order;
+    }
+}
+
+impl BidirectionalChannel for RenderdPool {
+    fn send_blocking_request(
+        &self,
+        context: &HostContext,
+        request: &[u8],
+        response_buffer: Option<Vec<u8>>,
+        response_timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, CommunicationError> {
+        let mut response_buffer = response_buffer;
+        let mut last_error = None;
+        for index in self.candidates() {
+            let pooled = &self.endpoints[index];
+            let result = {
+                let _in_flight = InFlight::new(&pooled.outstanding);
+                pooled.channel.send_blocking_request(context, request, response_buffer.take(), response_timeout)
+            };
+            match result {
+                Ok(response) => return Ok(response),
+                Err(CommunicationError::TimeoutError) => {
+                    // A stuck renderd has already used up the request's time budget, so the request is not retried
+                    warn!(context.host.record, "RenderdPool::send_blocking_request - {} timed out", pooled.endpoint);
+                    return Err(CommunicationError::TimeoutError);
+                },
+                Err(error) => {
+                    warn!(context.host.record, "RenderdPool::send_blocking_request - {} failed, failing over: {:?}", pooled.endpoint, error);
+                    last_error = Some(error);
+                },
+            };
+        }
+        return Err(last_error.unwrap_or(CommunicationError::Unavailable));
+    }
+
+    fn is_available(&self) -> bool {
+        self.endpoints.iter().any(|pooled| pooled.channel.is_available())
+    }
+
+    fn health(&self) -> Vec<ChannelHealth> {
+        self.endpoints.iter().flat_map(|pooled| pooled.channel.health()).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::communication::circuit_breaker::{CircuitBreaker, FAILURE_THRESHOLD,};
+    use crate::schema::apache2::config::ModuleConfig;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+
+    use std::collections::HashMap;
+    use std::error::Error as StdError;
+    use std::io::{Error as IoError, ErrorKind,};
+    use std::string::String;
+    use std::sync::{Arc, Barrier, Mutex,};
+    use std::sync::atomic::AtomicU32;
+    use std::thread;
+    use std::time::Instant;
+
+    #[derive(Clone)]
+    enum Behaviour {
+        Reply,
+        Fail,
+        Stall,
+        // Replies once the barrier has been passed twice, first on arrival and then on release
+        Hold(Arc<Barrier>),
+    }
+
+    // Like the renderd channels, the stub tracks the circuit of its endpoint
+    struct StubChannel {
+        name: String,
+        behaviour: Behaviour,
+        calls: Arc<AtomicU32>,
+        breaker: Mutex<CircuitBreaker>,
+    }
+
+    impl BidirectionalChannel for StubChannel {
+        fn send_blocking_request(
+            &self,
+            _context: &HostContext,
+            _request: &[u8],
+            _response_buffer: Option<Vec<u8>>,
+            _response_timeout: Option<Duration>,
+        ) -> Result<Vec<u8>, CommunicationError> {
+            if !self.breaker.lock().unwrap().try_acquire(&Instant::now()) {
+                return Err(CommunicationError::Unavailable);
+            }
+            self.calls.fetch_add(1, Ordering::AcqRel);
+            let result = match &self.behaviour {
+                Behaviour::Reply => Ok(self.name.clone().into_bytes()),
+                Behaviour::Fail => Err(CommunicationError::Io(IoError::new(ErrorKind::BrokenPipe, "test"))),
+                Behaviour::Stall => Err(CommunicationError::TimeoutError),
+                Behaviour::Hold(barrier) => {
+                    barrier.wait();
+                    barrier.wait();
+                    Ok(self.name.clone().into_bytes())
+                },
+            };
+            self.breaker.lock().unwrap().record_result(&result, &Instant::now());
+            return result;
+        }
+
+        fn is_available(&self) -> bool {
+            self.breaker.lock().unwrap().allows_request(&Instant::now())
+        }
+    }
+
+    fn make_pool(
+        stubs: Vec<(&'static str, Behaviour)>,
+        policy: BalancePolicy,
+    ) -> (RenderdPool, HashMap<&'static str, Arc<AtomicU32>>) {
+        let calls: HashMap<&'static str, Arc<AtomicU32>> = stubs.iter()
+            .map(|(name, _)| (*name, Arc::new(AtomicU32::new(0))))
+            .collect();
+        let channels = stubs.iter().map(|(name, behaviour)| {
+            let channel: Box<dyn BidirectionalChannel> = Box::new(
+                StubChannel {
+                    name: String::from(*name),
+                    behaviour: behaviour.clone(),
+                    calls: calls[name].clone(),
+                    breaker: Mutex::new(CircuitBreaker::new(FAILURE_THRESHOLD)),
+                }
+            );
+            (RenderdEndpoint::Unix(String::from(*name)), channel)
+        }).collect();
+        return (RenderdPool::with_channels(channels, policy), calls);
+    }
+
+    #[test]
+    fn test_round_robin() -> Result<(), Box<dyn StdError>> {
+        let module_config = ModuleConfig::new();
+        let (pool, calls) = make_pool(
+            vec![("first", Behaviour::Reply), ("second", Behaviour::Reply)],
+            BalancePolicy::RoundRobin,
+        );
+        with_request_rec(|record| {
+            let context = HostContext::new(&module_config, record);
+            let mut responses = Vec::new();
+            for _ in 0..4 {
+                responses.push(String::from_utf8(pool.send_blocking_request(&context, &[], None, None)?)?);
+            }
+            assert_eq!(vec!["first", "second", "first", "second"], responses, "Failed to spread the requests");
+            assert_eq!(2, calls["first"].load(Ordering::Acquire), "Incorrect request count");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_least_outstanding_with_concurrent_requests() -> Result<(), Box<dyn StdError>> {
+        let module_config = ModuleConfig::new();
+        let barrier = Arc::new(Barrier::new(2));
+        let (pool, calls) = make_pool(
+            vec![("busy", Behaviour::Hold(barrier.clone())), ("idle", Behaviour::Reply)],
+            BalancePolicy::LeastOutstanding,
+        );
+        thread::scope(|scope| {
+            let held = scope.spawn(|| {
+                with_request_rec(|record| {
+                    let context = HostContext::new(&module_config, record);
+                    let response = pool.send_blocking_request(&context, &[], None, None)?;
+                    assert_eq!(b"busy".to_vec(), response, "Failed to send the first request to the first endpoint");
+                    Ok(())
+                }).map_err(|error| error.to_string())
+            });
+            // The first request is now in flight on the busy endpoint
+            barrier.wait();
+            let result = with_request_rec(|record| {
+                let context = HostContext::new(&module_config, record);
+                for _ in 0..2 {
+                    let response = pool.send_blocking_request(&context, &[], None, None)?;
+                    assert_eq!(b"idle".to_vec(), response, "Failed to avoid the endpoint with a request in flight");
+                }
+                Ok(())
+            });
+            barrier.wait();
+            held.join().unwrap()?;
+            result
+        })?;
+        assert_eq!(1, calls["busy"].load(Ordering::Acquire), "Incorrect request count");
+        assert_eq!(0, pool.endpoints[0].outstanding.load(Ordering::Acquire), "Failed to count the reply");
+        Ok(())
+    }
+
+    #[test]
+    fn test_failover_to_healthy_endpoint() -> Result<(), Box<dyn StdError>> {
+        let module_config = ModuleConfig::new();
+        let (pool, calls) = make_pool(
+            vec![("broken", Behaviour::Fail), ("healthy", Behaviour::Reply)],
+            BalancePolicy::LeastOutstanding,
+        );
+        with_request_rec(|record| {
+            let context = HostContext::new(&module_config, record);
+            for _ in 0..(FAILURE_THRESHOLD + 2) {
+                let response = pool.send_blocking_request(&context, &[], None, None)?;
+                assert_eq!(b"healthy".to_vec(), response, "Failed to fail over");
+            }
+            assert_eq!(
+                FAILURE_THRESHOLD,
+                calls["broken"].load(Ordering::Acquire),
+                "Failed to skip the broken endpoint once its circuit opened"
+            );
+            assert_eq!(FAILURE_THRESHOLD + 2, calls["healthy"].load(Ordering::Acquire), "Incorrect request count");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_stalled_endpoint_stays_up() -> Result<(), Box<dyn StdError>> {
+        let module_config = ModuleConfig::new();
+        let (pool, calls) = make_pool(
+            vec![("stalled", Behaviour::Stall), ("healthy", Behaviour::Reply)],
+            BalancePolicy::RoundRobin,
+        );
+        with_request_rec(|record| {
+            let context = HostContext::new(&module_config, record);
+            let result = pool.send_blocking_request(&context, &[], None, None);
+            assert!(matches!(result, Err(CommunicationError::TimeoutError)), "Failed to report the timeout");
+            assert_eq!(0, calls["healthy"].load(Ordering::Acquire), "Timed out request was retried");
+            // A slow renderd is still up, so round robin keeps starting every other request on it
+            let mut timeout_count = 1;
+            for _ in 0..(FAILURE_THRESHOLD * 2) {
+                match pool.send_blocking_request(&context, &[], None, None) {
+                    Ok(_) => {},
+                    Err(CommunicationError::TimeoutError) => timeout_count += 1,
+                    Err(error) => return Err(Box::new(error) as Box<dyn StdError>),
+                };
+            }
+            assert_eq!(FAILURE_THRESHOLD + 1, timeout_count, "Incorrect timeout count");
+            assert_eq!(FAILURE_THRESHOLD + 1, calls["stalled"].load(Ordering::Acquire), "Marked the stalled endpoint down");
+            assert!(pool.endpoints[0].channel.is_available(), "Opened the circuit of the stalled endpoint");
+            Ok(())
+        })
+    }
+}
@@ -1,119 +1,259 @@
-use crate::io::communication::interface::{
-    CommunicationError, BidirectionalChannel,
-};
-use crate::framework::apache2::context::HostContext;
-use crate::schema::apache2::error::InvalidConfigError;
-use crate::schema::apache2::config::ModuleConfig;
+use crate::schema::apache2::config::{RenderdConfig, RenderdEndpoint,};
 
 use std::io::Read;
 use std::io::Write;
-use std::io::ErrorKind::TimedOut;
+use std::io::ErrorKind;
+use std::io::ErrorKind::{BrokenPipe, ConnectionAborted, ConnectionReset, NotConnected, UnexpectedEof,};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs,};
+use std::boxed::Box;
 use std::option::Option;
 use std::os::unix::net::UnixStream;
+use std::path::Path;
 use std::result::Result;
+use std::time::Duration;
+
+
+// Renderd is reachable over either a Unix socket or TCP, which share the same timeout behaviour
+pub trait RenderdStream: Read + Write + Send {
+    fn set_read_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> std::io::Result<()>;
+
+    fn try_clone_stream(&self) -> std::io::Result<Box<dyn RenderdStream>>;
 
-pub struct RenderdSocket {
-    socket: UnixStream,
+    fn shutdown_stream(&self) -> std::io::Result<()>;
 }
 
-#[cfg(not(test))]
-use std::path::Path;
-#[cfg(not(test))]
-impl RenderdSocket {
-    pub fn new(config: &ModuleConfig) -> Result<RenderdSocket, InvalidConfigError> {
-        let path = Path::new(&config.renderd.ipc_uri);
-        match UnixStream::connect(path) {
-            Err(ioerr) => Err(
-                InvalidConfigError {
-                    entry: String::from("ipc_uri"),
-                    reason: ioerr.to_string(),
-                }
-            ),
+impl RenderdStream for UnixStream {
+    fn set_read_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> std::io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn try_clone_stream(&self) -> std::io::Result<Box<dyn RenderdStream>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn shutdown_stream(&self) -> std::io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+impl RenderdStream for TcpStream {
+    fn set_read_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> std::io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn try_clone_stream(&self) -> std::io::Result<Box<dyn RenderdStream>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn shutdown_stream(&self) -> std::io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+// renderd closing an idle connection is only noticed as an early end of stream on the next request
+pub fn is_connection_lost(ioerr: &std::io::Error) -> bool {
+    matches!(ioerr.kind(), BrokenPipe | ConnectionReset | ConnectionAborted | NotConnected | UnexpectedEof)
+}
+
+pub fn open_stream(
+    endpoint: &RenderdEndpoint,
+    config: &RenderdConfig,
+) -> Result<Box<dyn RenderdStream>, std::io::Error> {
+    match endpoint {
+        RenderdEndpoint::Unix(path) => open_unix(Path::new(path), config),
+        RenderdEndpoint::Tcp { host, port } => open_tcp(host, *port, config),
+    }
+}
+
+fn open_unix(
+    path: &Path,
+    config: &RenderdConfig,
+) -> Result<Box<dyn RenderdStream>, std::io::Error> {
+    let socket = UnixStream::connect(path)?;
+    let availability_timeout = config.availability_timeout.clone();
+    if !availability_timeout.is_zero() {
+        socket.set_write_timeout(Some(availability_timeout))?;
+    }
+    Ok(Box::new(socket))
+}
+
+fn open_tcp(
+    host: &str,
+    port: u16,
+    config: &RenderdConfig,
+) -> Result<Box<dyn RenderdStream>, std::io::Error> {
+    let availability_timeout = config.availability_timeout.clone();
+    let mut last_error = None;
+    for address in (host, port).to_socket_addrs()? {
+        let connect_result = if availability_timeout.is_zero() {
+            TcpStream::connect(address)
+        } else {
+            TcpStream::connect_timeout(&address, availability_timeout)
+        };
+        match connect_result {
             Ok(socket) => {
-                let availability_timeout = config.renderd.availability_timeout.clone();
+                // Render requests are small and latency sensitive
+                socket.set_nodelay(true)?;
                 if !availability_timeout.is_zero() {
-                    if let Err(ioerr) = socket.set_write_timeout(Some(availability_timeout)) {
-                        return Err(
-                            InvalidConfigError {
-                                entry: String::from("availability_timeout"),
-                                reason: ioerr.to_string(),
-                            }
-                        );
-                    }
+                    socket.set_write_timeout(Some(availability_timeout))?;
                 }
-                let render_timeout = config.renderd.render_timeout.clone();
-                if !render_timeout.is_zero() {
-                    if let Err(ioerr) = socket.set_read_timeout(Some(render_timeout)) {
-                        return Err(
-                            InvalidConfigError {
-                                entry: String::from("render_timeout"),
-                                reason: ioerr.to_string(),
-                            }
-                        );
-                    }
-                }
-                Ok(
-                    RenderdSocket {
-                        socket,
-                    }
-                )
-            }
-        }
+                return Ok(Box::new(socket));
+            },
+            Err(error) => last_error = Some(error),
+        };
     }
+    return Err(
+        last_error.unwrap_or_else(|| {
+            std::io::Error::new(ErrorKind::NotFound, format!("Host {} has no address", host))
+        })
+    );
 }
 
+
 #[cfg(test)]
-impl RenderdSocket {
-    pub fn new(_config: &ModuleConfig) -> Result<RenderdSocket, InvalidConfigError> {
-        match UnixStream::pair() {
-            Err(ioerr) => Err(
-                InvalidConfigError {
-                    entry: String::from("ipc_uri"),
-                    reason: ioerr.to_string(),
+pub mod test_utils {
+    use super::*;
+    use crate::adapter::render_proto::codec::{frame_tile, remaining_frame_size, HEADER_SIZE,};
+    use crate::adapter::render_proto::codec::test_utils::with_command;
+    use crate::io::storage::meta_tile::test_utils::write_meta_tile;
+    use crate::schema::apache2::config::ModuleConfig;
+    use crate::schema::renderd::response::RenderResponseCommand;
+    use crate::schema::tile::identity::TileIdentity;
+
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex,};
+    use std::sync::atomic::{AtomicBool, Ordering,};
+    use std::thread;
+
+    #[derive(Clone, Copy, Debug)]
+    pub enum MockReply {
+        Done,
+        NotDone,
+        // Renders the tile but replies only after the delay, to trigger timeouts
+        Delay(Duration),
+    }
+
+    struct MockState {
+        config: ModuleConfig,
+        reply: MockReply,
+        requests: Mutex<Vec<TileIdentity>>,
+        is_stopped: AtomicBool,
+    }
+
+    // Stands in for renderd on a temporary Unix socket. Like renderd, it writes the meta tile of each
+    // requested tile into the store of the given config before it replies.
+    pub struct MockRenderd {
+        socket_dir: mktemp::Temp,
+        state: Arc<MockState>,
+    }
+
+    impl MockRenderd {
+        pub fn start(
+            config: &ModuleConfig,
+            reply: MockReply,
+        ) -> Result<MockRenderd, std::io::Error> {
+            let socket_dir = mktemp::Temp::new_dir()?;
+            let listener = UnixListener::bind(socket_dir.join("renderd.sock"))?;
+            let state = Arc::new(
+                MockState {
+                    config: config.clone(),
+                    reply,
+                    requests: Mutex::new(Vec::new()),
+                    is_stopped: AtomicBool::new(false),
                 }
-            ),
-            Ok((client_socket, _)) => Ok(
-                RenderdSocket {
-                    socket: client_socket
+            );
+            let listener_state = state.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if listener_state.is_stopped.load(Ordering::Acquire) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let connection_state = listener_state.clone();
+                        thread::spawn(move || serve(connection_state, stream));
+                    }
+                }
+            });
+            Ok(
+                MockRenderd {
+                    socket_dir,
+                    state,
                 }
             )
         }
+
+        pub fn socket_path(&self) -> PathBuf {
+            self.socket_dir.join("renderd.sock")
+        }
+
+        pub fn requests(&self) -> Vec<TileIdentity> {
+            self.state.requests.lock().unwrap().clone()
+        }
     }
-}
 
-impl BidirectionalChannel for RenderdSocket {
-    fn send_blocking_request(
-        &mut self,
-        _context: &HostContext,
-        request: &[u8],
-        response_buffer: Option<Vec<u8>>,
-    ) -> Result<Vec<u8>, CommunicationError> {
-        let mut output = match response_buffer {
-            Some(buffer) => buffer,
-            None => Vec::new()
+    impl Drop for MockRenderd {
+        fn drop(&mut self) {
+            // Wakes the listener thread so it sees it has been stopped
+            self.state.is_stopped.store(true, Ordering::Release);
+            UnixStream::connect(self.socket_path()).ok();
+        }
+    }
+
+    // Like renderd, requests on one connection are rendered concurrently and answered as each finishes
+    fn serve(
+        state: Arc<MockState>,
+        mut stream: UnixStream,
+    ) -> () {
+        let writer = match stream.try_clone() {
+            Ok(writer) => Arc::new(Mutex::new(writer)),
+            Err(_) => return,
         };
-        if let Err(ioerr) = self.socket.write_all(request) {
-            match ioerr.kind() {
-                TimedOut => return Err(
-                    CommunicationError::TimeoutError
-                ),
-                _ => return Err(
-                    CommunicationError::Io(ioerr)
-                )
+        loop {
+            let mut frame = vec![0u8; HEADER_SIZE];
+            if stream.read_exact(&mut frame).is_err() {
+                return;
             }
-        }
-        self.socket.flush()?;
-        let _bytes_read = match self.socket.read_to_end(&mut output) {
-            Ok(bytes_read) => bytes_read,
-            Err(ioerr) => match ioerr.kind() {
-                TimedOut => return Err(
-                    CommunicationError::TimeoutError
-                ),
-                _ => return Err(
-                    CommunicationError::Io(ioerr)
-                )
+            let remaining = match remaining_frame_size(&frame) {
+                Ok(remaining) => remaining,
+                Err(_) => return,
+            };
+            frame.resize(HEADER_SIZE + remaining, 0);
+            if stream.read_exact(&mut frame[HEADER_SIZE..]).is_err() {
+                return;
             }
-        };
-        return Ok(output);
+            let tile = match frame_tile(&frame) {
+                Ok(tile) => tile,
+                Err(_) => return,
+            };
+            state.requests.lock().unwrap().push(tile.clone());
+            let render_state = state.clone();
+            let render_writer = writer.clone();
+            thread::spawn(move || {
+                let command = match render_state.reply {
+                    MockReply::NotDone => RenderResponseCommand::NotDone,
+                    MockReply::Done | MockReply::Delay(_) => {
+                        if let MockReply::Delay(delay) = render_state.reply {
+                            thread::sleep(delay);
+                        }
+                        match write_meta_tile(&render_state.config, &tile) {
+                            Ok(_) => RenderResponseCommand::Done,
+                            Err(_) => RenderResponseCommand::NotDone,
+                        }
+                    },
+                };
+                render_writer.lock().unwrap().write_all(&with_command(&frame, command)).ok();
+            });
+        }
     }
 }
+
@@ -1,27 +1,63 @@
 use crate::schema::apache2::config::ModuleConfig;
 use crate::schema::apache2::error::InvalidConfigError;
+use crate::schema::communication::health::ChannelHealth;
+use crate::schema::tile::identity::LayerName;
 use crate::io::communication::interface::{BidirectionalChannel, CommunicationInventory,};
-use crate::io::communication::renderd_socket::RenderdSocket;
+use crate::io::communication::renderd_pool::RenderdPool;
 
+use std::collections::HashMap;
+use std::sync::Arc;
 
+
+// Clones share the same renderd pools, so every thread of an Apache child uses the same connections
+#[derive(Clone)]
 pub struct CommunicationState {
-    renderd_socket: RenderdSocket,
+    renderd_pool: Arc<RenderdPool>,
+    renderd_pools_by_layer: HashMap<LayerName, Arc<RenderdPool>>,
 }
 
 impl CommunicationState {
     pub fn new(
         module_config: &ModuleConfig
     ) -> Result<CommunicationState, InvalidConfigError> {
+        let mut renderd_pools_by_layer = HashMap::new();
+        for (layer, layer_config) in &module_config.layers {
+            if !layer_config.renderd_endpoints.is_empty() {
+                renderd_pools_by_layer.insert(
+                    layer.clone(),
+                    Arc::new(RenderdPool::new(layer_config.renderd_endpoints.clone(), &module_config.renderd)),
+                );
+            }
+        }
         Ok(
             CommunicationState {
-                renderd_socket: RenderdSocket::new(module_config)?
+                renderd_pool: Arc::new(RenderdPool::new(module_config.renderd.endpoints(), &module_config.renderd)),
+                renderd_pools_by_layer,
             }
         )
     }
 }
 
 impl CommunicationInventory for CommunicationState {
-    fn primary_renderd_comms(&mut self) -> &mut dyn BidirectionalChannel {
-        &mut self.renderd_socket
+    fn primary_renderd_comms(&self) -> &dyn BidirectionalChannel {
+        self.renderd_pool.as_ref()
+    }
+
+    fn renderd_comms(
+        &self,
+        layer: &LayerName,
+    ) -> &dyn BidirectionalChannel {
+        match self.renderd_pools_by_layer.get(layer) {
+            Some(pool) => pool.as_ref(),
+            None => self.renderd_pool.as_ref(),
+        }
+    }
+
+    fn renderd_health(&self) -> Vec<ChannelHealth> {
+        let mut health = self.renderd_pool.health();
+        for pool in self.renderd_pools_by_layer.values() {
+            health.extend(pool.health());
+        }
+        return health;
     }
 }
@@ -1,6 +1,6 @@
 use crate::schema::apache2::config::ModuleConfig;
 use crate::schema::apache2::error::InvalidConfigError;
-use crate::schema::tile::error::TileReadError;
+use crate::schema::tile::error::{InvalidMetaTileError, TileReadError,};
 use crate::schema::tile::identity::TileIdentity;
 use crate::framework::apache2::context::HostContext;
 use crate::io::storage::interface::TileStorage;
@@ -10,6 +10,7 @@ use crate::io::storage::meta_tile::MetaTile;
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
 use std::convert::Into;
+use std::io::ErrorKind;
 use std::path::PathBuf;
 use std::result::Result;
 
@@ -37,7 +38,13 @@ impl TileStorage for FileSystem {
         id: &TileIdentity,
     ) -> Result<TileRef, TileReadError> {
         let path = MetaTile::identity_to_path(context.module_config, id);
-        let meta_tile = MetaTile::read(&path.meta_tile_path)?;
+        let meta_tile = match MetaTile::read(&path.meta_tile_path) {
+            // A meta tile that hasn't been rendered yet is a miss rather than a broken store
+            Err(InvalidMetaTileError::Io(ioerr)) if ioerr.kind() == ErrorKind::NotFound => {
+                return Err(TileReadError::NotFound(path.meta_tile_path));
+            },
+            result => result?,
+        };
         let cached_tile = match self.cache.entry(path.meta_tile_path.clone()) {
             Entry::Occupied(mut entry) => {
                 // TODO: add cache expiry logic
@@ -53,6 +53,7 @@ pub mod test_utils {
                     end: 0,
                     media_type: mime::IMAGE_PNG,
                     encoding: ContentEncoding::NotCompressed,
+                    modified_time: None,
                 }
             )
         }
@@ -6,22 +6,22 @@ use crate::schema::http::encoding::ContentEncoding;
 use crate::schema::tile::error::{
     InvalidMetaTileError, InvalidCompressionError, TileOffsetOutOfBoundsError,
 };
-use crate::schema::tile::identity::TileIdentity;
+use crate::schema::tile::identity::{TileIdentity, META_TILE_MASK, META_TILE_WIDTH,};
 use crate::schema::tile::tile_ref::TileRef;
 
 use mime::Mime;
 
-use std::cell::{RefCell, Ref,};
-use std::cmp::min;
-use std::ffi::CStr;
+use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::fs;
+use std::mem::size_of;
+use std::option::Option;
 use std::path::PathBuf;
+use std::ptr;
 use std::result::Result;
+use std::time::SystemTime;
 
 
-const META_TILE_WIDTH: i32 = 8;
-const META_TILE_MASK: i32 = META_TILE_WIDTH - 1;
-
 pub struct TilePath {
     pub meta_tile_path: PathBuf,
     pub tile_offset: u32,
@@ -32,22 +32,37 @@ pub struct MetaTile {
     tile_count: u32,
     media_type: Mime,
     encoding: ContentEncoding,
+    modified_time: Option<SystemTime>,
 }
 
 impl MetaTile {
     pub fn read(
         path: &PathBuf
     ) -> Result<MetaTile, InvalidMetaTileError> {
-        let raw_bytes = RefCell::new(fs::read(path)?);
-        let layout = MetaTile::get_layout(raw_bytes.borrow());
-        let encoding = MetaTile::detect_compression(layout)?;
-        let tile_count = MetaTile::detect_tile_count(layout)?;
+        let raw_bytes = fs::read(path)?;
+        let modified_time = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        return MetaTile::from_bytes(raw_bytes, modified_time);
+    }
+
+    // Meta tiles on disk may be truncated or corrupt, so every part of the layout is
+    // checked against the file size before it is dereferenced
+    pub fn from_bytes(
+        raw_bytes: Vec<u8>,
+        modified_time: Option<SystemTime>,
+    ) -> Result<MetaTile, InvalidMetaTileError> {
+        MetaTile::verify_header_size(&raw_bytes)?;
+        let layout = MetaTile::get_layout(&raw_bytes);
+        let encoding = MetaTile::detect_compression(&layout)?;
+        let tile_count = MetaTile::detect_tile_count(&layout)?;
+        MetaTile::verify_index_size(raw_bytes.len(), tile_count)?;
+        let raw_bytes = RefCell::new(raw_bytes);
         // TODO - verify tile media type
         let result = MetaTile {
             raw_bytes,
             tile_count,
             media_type: mime::IMAGE_PNG,
             encoding,
+            modified_time,
         };
         result.verify_tile_lengths()?;
         return Ok(result);
@@ -61,6 +76,10 @@ impl MetaTile {
         let mut path_buf = PathBuf::new();
         path_buf.push(&config.renderd.store_uri);
         path_buf.push(id.layer.as_str());
+        // Like mod_tile, each style parameter has its own tree under the layer
+        if let Some(parameter) = id.parameter.as_ref().filter(|parameter| !parameter.is_empty()) {
+            path_buf.push(parameter);
+        }
         path_buf.push(id.z.to_string());
         path_buf.push(directory_hash[4].to_string());
         path_buf.push(directory_hash[3].to_string());
@@ -89,6 +108,7 @@ impl MetaTile {
                 end: next_tile_start,
                 media_type: self.media_type.clone(),
                 encoding: self.encoding.clone(),
+                modified_time: self.modified_time.clone(),
             }
         );
     }
@@ -116,31 +136,48 @@ impl MetaTile {
         ((id.x & META_TILE_MASK) * META_TILE_WIDTH + (id.y & META_TILE_MASK)) as u32
     }
 
+    fn verify_header_size(
+        raw_bytes: &[u8],
+    ) -> Result<(), InvalidMetaTileError> {
+        if raw_bytes.len() < size_of::<meta_layout>() {
+            return Err(InvalidMetaTileError::InvalidHeaderSize(raw_bytes.len()));
+        }
+        Ok(())
+    }
+
+    fn verify_index_size(
+        file_size: usize,
+        tile_count: u32,
+    ) -> Result<(), InvalidMetaTileError> {
+        let index_end = size_of::<meta_layout>() + (tile_count as usize * size_of::<entry>());
+        if index_end > file_size {
+            return Err(InvalidMetaTileError::InvalidIndexSize(file_size));
+        }
+        Ok(())
+    }
+
+    // The bytes have no alignment guarantee, so the header is copied out rather than read in place
     fn get_layout(
-        raw_bytes: Ref<Vec<u8>>,
-    ) -> &meta_layout {
+        raw_bytes: &[u8],
+    ) -> meta_layout {
+        assert!(raw_bytes.len() >= size_of::<meta_layout>());
         unsafe {
-            (raw_bytes.as_ptr() as *const meta_layout).as_ref().unwrap()
+            ptr::read_unaligned(raw_bytes.as_ptr() as *const meta_layout)
         }
     }
 
     fn detect_compression(
         layout: &meta_layout
     ) -> Result<ContentEncoding, InvalidCompressionError> {
-        let raw_tag = unsafe {
-            CStr::from_ptr(layout.magic.as_ptr()).to_str()?
-        };
+        // The magic is not null terminated, so it can't be read as a C string
+        let raw_magic: Vec<u8> = layout.magic.iter().map(|c| *c as u8).collect();
+        let raw_tag = std::str::from_utf8(&raw_magic)?;
         let expected_uncompressed_tag = std::str::from_utf8(META_MAGIC.strip_suffix(&[0]).unwrap())?;
-        let uncompressed_tag_length = min(expected_uncompressed_tag.len(), raw_tag.len());
-        let actual_uncompressed_tag = &(raw_tag[..uncompressed_tag_length]);
-
         let expected_gzip_tag = std::str::from_utf8(META_MAGIC_COMPRESSED.strip_suffix(&[0]).unwrap())?;
-        let gzip_tag_length = min(expected_gzip_tag.len(), raw_tag.len());
-        let actual_gzip_tag = &(raw_tag[..gzip_tag_length]);
 
-        if actual_uncompressed_tag == expected_uncompressed_tag {
+        if raw_tag == expected_uncompressed_tag {
             Ok(ContentEncoding::NotCompressed)
-        } else if actual_gzip_tag == expected_gzip_tag {
+        } else if raw_tag == expected_gzip_tag {
             Ok(ContentEncoding::Gzip)
         } else {
             Err(InvalidCompressionError::InvalidTag(raw_tag.to_string()))
@@ -162,7 +199,7 @@ impl MetaTile {
     fn get_entry(
         &self,
         tile_offset: u32,
-    ) -> Result<&entry, TileOffsetOutOfBoundsError> {
+    ) -> Result<entry, TileOffsetOutOfBoundsError> {
         if tile_offset >= self.tile_count {
             return Err(
                 TileOffsetOutOfBoundsError {
@@ -170,11 +207,13 @@ impl MetaTile {
                 }
             );
         }
-        let layout = MetaTile::get_layout(self.raw_bytes.borrow());
-        let tile_index = unsafe {
-            layout.index.as_slice(self.tile_count as usize)
+        // The index follows the header, and its size was verified against the file size
+        let position = size_of::<meta_layout>() + (tile_offset as usize * size_of::<entry>());
+        let raw_bytes = self.raw_bytes.borrow();
+        let entry = unsafe {
+            ptr::read_unaligned(raw_bytes[position..(position + size_of::<entry>())].as_ptr() as *const entry)
         };
-        return Ok(&tile_index[tile_offset as usize]);
+        return Ok(entry);
     }
 
     fn verify_tile_lengths(
@@ -182,12 +221,16 @@ impl MetaTile {
     ) -> Result<(), InvalidMetaTileError> {
         for tile_offset in 0..self.tile_count {
             let entry = self.get_entry(tile_offset).unwrap();
-            let end_of_tile_position= (entry.offset + entry.size - 1) as usize;
             let file_size = self.raw_bytes.borrow().len();
-            if end_of_tile_position > file_size {
-                return Err(
+            // The entry fields are signed, so negative values have to be rejected along with overflow
+            let end_of_tile = usize::try_from(entry.offset).ok()
+                .zip(usize::try_from(entry.size).ok())
+                .and_then(|(offset, size)| offset.checked_add(size));
+            match end_of_tile {
+                Some(end) if end <= file_size => (),
+                _ => return Err(
                     InvalidMetaTileError::InvalidTileLength(tile_offset)
-                );
+                ),
             }
         }
         Ok(())
@@ -195,6 +238,57 @@ impl MetaTile {
 }
 
 
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use std::os::raw::c_int;
+    use std::vec::Vec;
+
+    // Each tile of a generated meta tile holds its own coordinates, so a test can tell which tile was served
+    pub fn generated_tile(id: &TileIdentity) -> Vec<u8> {
+        format!("{}/{}/{}/{}", id.layer, id.z, id.x, id.y).into_bytes()
+    }
+
+    // Lays out the meta tile containing the tile the way renderd does once it has rendered it
+    pub fn meta_tile_bytes(id: &TileIdentity) -> Vec<u8> {
+        let meta_tile = id.meta_tile();
+        let tile_count = (META_TILE_WIDTH * META_TILE_WIDTH) as usize;
+        let mut tiles = Vec::with_capacity(tile_count);
+        for x in meta_tile.x..(meta_tile.x + META_TILE_WIDTH) {
+            for y in meta_tile.y..(meta_tile.y + META_TILE_WIDTH) {
+                tiles.push(generated_tile(&TileIdentity { x, y, ..id.clone() }));
+            }
+        }
+        let mut raw_bytes = Vec::new();
+        raw_bytes.extend_from_slice(&META_MAGIC[..4]);
+        for field in [tile_count as c_int, meta_tile.x, meta_tile.y, meta_tile.z] {
+            raw_bytes.extend_from_slice(&field.to_ne_bytes());
+        }
+        let mut offset = raw_bytes.len() + (tile_count * size_of::<entry>());
+        for tile in &tiles {
+            raw_bytes.extend_from_slice(&(offset as c_int).to_ne_bytes());
+            raw_bytes.extend_from_slice(&(tile.len() as c_int).to_ne_bytes());
+            offset += tile.len();
+        }
+        for tile in &tiles {
+            raw_bytes.extend_from_slice(tile);
+        }
+        return raw_bytes;
+    }
+
+    // Writes the meta tile containing the tile to the store
+    pub fn write_meta_tile(
+        config: &ModuleConfig,
+        id: &TileIdentity,
+    ) -> std::io::Result<PathBuf> {
+        let path = MetaTile::identity_to_path(config, id).meta_tile_path;
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, meta_tile_bytes(id))?;
+        return Ok(path);
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,6 +306,7 @@ mod tests {
             y: 4 + 2 + 1,
             z: 5,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let offset1 = MetaTile::calc_offset(&id1);
         assert_eq!((8 * 1) + 7, offset1, "Incorrect offset calculation");
@@ -221,6 +316,7 @@ mod tests {
             y: 4 + 2 + 1,
             z: 5,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let offset2 = MetaTile::calc_offset(&id2);
         assert_eq!((8 * 3) + 7, offset2, "Incorrect offset calculation");
@@ -230,6 +326,7 @@ mod tests {
             y: 4 + 2 + 0,
             z: 5,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let offset3 = MetaTile::calc_offset(&id3);
         assert_eq!((8 * 3) + 6, offset3, "Incorrect offset calculation");
@@ -243,6 +340,7 @@ mod tests {
             y: 0 + 16 + 0 + 0 + 2 + 1,
             z: 3,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let offset1 = MetaTile::calc_offset(&id1);
         assert_eq!((8 * 5) + 3, offset1, "Incorrect offset calculation");
@@ -252,6 +350,7 @@ mod tests {
             y: 0 + 16 + 0 + 0 + 2 + 1,
             z: 3,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let offset2 = MetaTile::calc_offset(&id2);
         assert_eq!((8 * 5) + 3, offset2, "Incorrect offset calculation");
@@ -261,6 +360,7 @@ mod tests {
             y: 32 + 16 + 0 + 0 + 2 + 1,
             z: 3,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let offset3 = MetaTile::calc_offset(&id3);
         assert_eq!((8 * 5) + 3, offset3, "Incorrect offset calculation");
@@ -274,6 +374,7 @@ mod tests {
             y: 000000 + 000000 + 000000 + 00000 + 00000 + 00000 + 0000 + 0000 + 0000 + 0000 + 000 + 000 + 000 + 00 + 00 + 00 + 0 + 4 + 2 + 1,
             z: 5,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let hash1 = MetaTile::calc_directory_hash(&id1);
         assert_eq!(0, hash1[0], "Incorrect directory hash calculation");
@@ -287,6 +388,7 @@ mod tests {
             y: 000000 + 000000 + 000000 + 00000 + 00000 + 00000 + 0000 + 0000 + 0000 + 0000 + 000 + 000 + 000 + 00 + 00 + 00 + 0 + 4 + 2 + 1,
             z: 5,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let hash2 = MetaTile::calc_directory_hash(&id2);
         assert_eq!(128, hash2[0], "Incorrect directory hash calculation");
@@ -300,6 +402,7 @@ mod tests {
             y: 000000 + 000000 + 000000 + 65536 + 00000 + 00000 + 0000 + 4096 + 0000 + 0000 + 000 + 256 + 000 + 00 + 00 + 16 + 8 + 4 + 2 + 1,
             z: 5,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let hash3 = MetaTile::calc_directory_hash(&id3);
         assert_eq!(136, hash3[0], "Incorrect directory hash calculation");
@@ -313,6 +416,7 @@ mod tests {
             y: 000000 + 262144 + 000000 + 00000 + 00000 + 16384 + 0000 + 0000 + 0000 + 1024 + 000 + 000 + 000 + 64 + 00 + 00 + 8 + 4 + 2 + 1,
             z: 5,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let hash4 = MetaTile::calc_directory_hash(&id4);
         assert_eq!(136, hash4[0], "Incorrect directory hash calculation");
@@ -323,6 +427,123 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_identity_to_path_with_parameter() -> Result<(), Box<dyn StdError>> {
+        let mut config = ModuleConfig::new();
+        config.renderd.store_uri = String::from("/var/cache/renderd");
+        let mut id = TileIdentity {
+            x: 1,
+            y: 2,
+            z: 3,
+            layer: LayerName::from("default"),
+            parameter: None,
+        };
+        assert_eq!(
+            PathBuf::from("/var/cache/renderd/default/3/0/0/0/0/0.meta"),
+            MetaTile::identity_to_path(&config, &id).meta_tile_path,
+            "Incorrect path without a parameter"
+        );
+        id.parameter = Some(String::from("de"));
+        assert_eq!(
+            PathBuf::from("/var/cache/renderd/default/de/3/0/0/0/0/0.meta"),
+            MetaTile::identity_to_path(&config, &id).meta_tile_path,
+            "Failed to store the parameter separately"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_generated_meta_tile() -> Result<(), Box<dyn StdError>> {
+        let store_dir = mktemp::Temp::new_dir()?;
+        let mut config = ModuleConfig::new();
+        config.renderd.store_uri = String::from(store_dir.to_str().unwrap());
+        let id = TileIdentity {
+            x: 19,
+            y: 10,
+            z: 5,
+            layer: LayerName::from("default"),
+            parameter: None,
+        };
+        let path = test_utils::write_meta_tile(&config, &id)?;
+        let meta_tile = MetaTile::read(&path)?;
+        let tile_ref = meta_tile.select(MetaTile::identity_to_path(&config, &id).tile_offset)?;
+        tile_ref.with_tile(|raw_bytes| {
+            assert_eq!(test_utils::generated_tile(&id), raw_bytes.to_vec(), "Failed to select the generated tile");
+        });
+        Ok(())
+    }
+
+    fn generated_meta_tile_bytes() -> Vec<u8> {
+        test_utils::meta_tile_bytes(
+            &TileIdentity {
+                x: 19,
+                y: 10,
+                z: 5,
+                layer: LayerName::from("default"),
+                parameter: None,
+            }
+        )
+    }
+
+    fn set_entry(
+        raw_bytes: &mut Vec<u8>,
+        tile_offset: usize,
+        offset: i32,
+        size: i32,
+    ) -> () {
+        let position = size_of::<meta_layout>() + (tile_offset * size_of::<entry>());
+        raw_bytes[position..(position + 4)].copy_from_slice(&offset.to_ne_bytes());
+        raw_bytes[(position + 4)..(position + 8)].copy_from_slice(&size.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_read_truncated_header() -> Result<(), Box<dyn StdError>> {
+        let raw_bytes = generated_meta_tile_bytes()[..(size_of::<meta_layout>() - 1)].to_vec();
+        assert!(
+            matches!(MetaTile::from_bytes(raw_bytes, None), Err(InvalidMetaTileError::InvalidHeaderSize(_))),
+            "Failed to reject a meta tile without a complete header"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_truncated_index() -> Result<(), Box<dyn StdError>> {
+        let raw_bytes = generated_meta_tile_bytes()[..(size_of::<meta_layout>() + size_of::<entry>())].to_vec();
+        assert!(
+            matches!(MetaTile::from_bytes(raw_bytes, None), Err(InvalidMetaTileError::InvalidIndexSize(_))),
+            "Failed to reject a meta tile without a complete index"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_invalid_magic() -> Result<(), Box<dyn StdError>> {
+        let mut raw_bytes = generated_meta_tile_bytes();
+        raw_bytes[..4].copy_from_slice(&[0xff; 4]);
+        assert!(
+            matches!(MetaTile::from_bytes(raw_bytes, None), Err(InvalidMetaTileError::InvalidCompression(_))),
+            "Failed to reject a meta tile with an invalid magic"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_invalid_tile_entries() -> Result<(), Box<dyn StdError>> {
+        let file_size = generated_meta_tile_bytes().len() as i32;
+        for (offset, size) in [(file_size, 1), (-1, 2), (0, -1), (1, i32::MAX)] {
+            let mut raw_bytes = generated_meta_tile_bytes();
+            set_entry(&mut raw_bytes, 3, offset, size);
+            assert!(
+                matches!(MetaTile::from_bytes(raw_bytes, None), Err(InvalidMetaTileError::InvalidTileLength(3))),
+                "Failed to reject tile entry with offset {} and size {}", offset, size
+            );
+        }
+        let mut raw_bytes = generated_meta_tile_bytes();
+        set_entry(&mut raw_bytes, 3, file_size - 1, 1);
+        assert!(MetaTile::from_bytes(raw_bytes, None).is_ok(), "Failed to accept a tile ending at the end of the file");
+        Ok(())
+    }
+
     #[test]
     fn test_read_valid_basic_meta_tile() -> Result<(), InvalidMetaTileError> {
         let mut test_store_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -336,6 +557,7 @@ mod tests {
             y: 000000 + 000000 + 000000 + 00000 + 00000 + 00000 + 0000 + 0000 + 0000 + 0000 + 512 + 000 + 000 + 64 + 32 + 00 + 8 + 0 + 0 + 0,
             z: 10,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let hash = MetaTile::calc_directory_hash(&id);
         assert_eq!(8, hash[0], "Incorrect directory hash calculation");
@@ -369,6 +591,7 @@ mod tests {
             y: 000000 + 000000 + 000000 + 00000 + 00000 + 00000 + 0000 + 0000 + 0000 + 0000 + 000 + 000 + 000 + 00 + 32 + 00 + 0 + 0 + 0 + 0,
             z: 6,
             layer: LayerName::from("default"),
+            parameter: None,
         };
         let hash = MetaTile::calc_directory_hash(&id);
         assert_eq!(128, hash[0], "Incorrect directory hash calculation");
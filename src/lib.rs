@@ -40,6 +40,7 @@ mod schema {
     }
     pub mod communication {
         pub mod error;
+        pub mod health;
     }
     pub mod slippy {
         pub mod error;
@@ -55,21 +56,30 @@ mod schema {
     pub mod handler {
         pub mod error;
     }
+    pub mod telemetry {
+        pub mod span;
+        pub mod window;
+    }
 }
 mod framework {
     pub mod apache2 {
         pub mod config;
         pub mod connection;
         pub mod context;
+        pub mod lint;
         pub mod memory;
         pub mod record;
+        pub mod server_config;
         pub mod virtual_host;
     }
 }
 mod io {
     pub mod communication {
         pub mod interface;
+        pub mod circuit_breaker;
         pub mod http_exchange;
+        pub mod renderd_multiplexer;
+        pub mod renderd_pool;
         pub mod renderd_socket;
         pub mod state;
     }
@@ -79,7 +89,7 @@ mod io {
         pub mod memcached;
         pub mod state;
         pub mod variant;
-        mod meta_tile;
+        pub mod meta_tile;
     }
     pub mod interface;
 }
@@ -88,65 +98,82 @@ mod adapter {
         pub mod reader;
     }
     pub mod render_proto {
+        pub mod codec;
         pub mod interface;
+        #[cfg(test)]
+        pub mod replay;
         pub mod slippy;
     }
     pub mod slippy {
         pub mod interface;
         pub mod inventory;
+        pub mod middleware;
         pub mod reader;
+        pub mod status;
         pub mod writer;
     }
 }
 mod service {
     pub mod telemetry{
         pub mod interface;
+        pub mod access_log;
         pub mod counters;
         pub mod inventory;
+        pub mod registry;
         pub mod response;
         pub mod tile_handling;
+        pub mod trace_export;
         pub mod transaction;
+        pub mod window;
     }
     pub mod rendering {
         pub mod interface;
         pub mod inventory;
         pub mod mapnik;
         pub mod protocol;
-        mod status;
+        pub mod status;
     }
-    pub mod interface;
 }
 mod use_case {
     pub mod interface;
+    pub mod configuration;
     pub mod description;
     pub mod inventory;
     pub mod statistics;
     pub mod tile;
 }
 mod tile_proxy;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+
+// The configuration checker links the library, since it can't load the module the way httpd does
+pub use crate::framework::apache2::config::ParseError;
+pub use crate::framework::apache2::lint::{lint, ConfigProblem,};
 
 
 use crate::binding::apache2::{
     HTTP_INTERNAL_SERVER_ERROR,
     OK, DECLINED,
-    MODULE_MAGIC_COOKIE, MODULE_MAGIC_NUMBER_MAJOR, MODULE_MAGIC_NUMBER_MINOR,
-    apr_pool_t, cmd_parms, module, request_rec, server_rec,
+    MODULE_MAGIC_COOKIE, MODULE_MAGIC_NUMBER_MAJOR, MODULE_MAGIC_NUMBER_MINOR, RSRC_CONF,
+    apr_pool_t, apr_pstrdup, cmd_func, cmd_how_FLAG, cmd_how_TAKE1, cmd_how_TAKE2, cmd_how_TAKE3,
+    cmd_parms, command_rec, module, request_rec, server_rec,
 };
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "fuzzing")))]
 use crate::binding::apache2::{ APR_HOOK_MIDDLE, ap_hook_child_init, ap_hook_handler, };
 
+use crate::framework::apache2::config::ServerConfig;
+use crate::framework::apache2::server_config::{alloc_server_config, find_server_config,};
+use crate::framework::apache2::memory::access_pool_object;
 use crate::framework::apache2::record::ServerRecord;
-use crate::schema::slippy::error::WriteError;
+use crate::adapter::slippy::status::ErrorStatusMapper;
 use crate::tile_proxy::{HandleRequestError, TileProxy,};
 
-use scan_fmt::scan_fmt;
-
 use std::alloc::System;
-use std::ffi::CStr;
+use std::any::type_name;
+use std::ffi::{CStr, CString,};
 use std::path::PathBuf;
 use std::ptr;
 use std::os::raw::{ c_char, c_int, c_void, };
-use std::time::Duration;
 
 
 #[global_allocator]
@@ -164,19 +191,174 @@ pub static mut TILE_MODULE: module = module {
     rewrite_args: None,
     create_dir_config: None,
     merge_dir_config: None,
-    create_server_config: None,
-    merge_server_config: None,
-    cmds: ptr::null_mut(),
+    create_server_config: Some(create_server_config),
+    merge_server_config: Some(merge_server_config),
+    cmds: &TILE_COMMANDS as *const [command_rec; TILE_COMMAND_COUNT] as *mut command_rec,
     register_hooks: Some(register_hooks),
     flags: 0,
 };
 
+const TILE_COMMAND_COUNT: usize = 27;
+
+macro_rules! directive {
+    (@entry $name:expr, $usage:expr, $field:ident, $func:ident, $args_how:ident) => {
+        command_rec {
+            name: cstr!($name),
+            func: cmd_func { $field: Some($func) },
+            cmd_data: ptr::null_mut(),
+            req_override: RSRC_CONF as c_int,
+            args_how: $args_how,
+            errmsg: cstr!($usage),
+        }
+    };
+    (TAKE1, $name:expr, $usage:expr) => {
+        directive!(@entry $name, $usage, take1, load_directive_take1, cmd_how_TAKE1)
+    };
+    (TAKE2, $name:expr, $usage:expr) => {
+        directive!(@entry $name, $usage, take2, load_directive_take2, cmd_how_TAKE2)
+    };
+    (TAKE3, $name:expr, $usage:expr) => {
+        directive!(@entry $name, $usage, take3, load_directive_take3, cmd_how_TAKE3)
+    };
+    (TAKE1, $name:expr, $usage:expr, $func:ident) => {
+        directive!(@entry $name, $usage, take1, $func, cmd_how_TAKE1)
+    };
+    (IGNORED FLAG, $name:expr, $usage:expr) => {
+        directive!(@entry $name, $usage, flag, ignore_directive_flag, cmd_how_FLAG)
+    };
+    (IGNORED TAKE1, $name:expr, $usage:expr) => {
+        directive!(@entry $name, $usage, take1, ignore_directive_take1, cmd_how_TAKE1)
+    };
+    (IGNORED TAKE2, $name:expr, $usage:expr) => {
+        directive!(@entry $name, $usage, take2, ignore_directive_take2, cmd_how_TAKE2)
+    };
+}
+
+// Apache finds the end of the table by its null name entry
+static TILE_COMMANDS: [command_rec; TILE_COMMAND_COUNT] = [
+    directive!(TAKE1, "LoadTileConfigFile", "LoadTileConfigFile takes the path of a renderd.conf file", load_config),
+    directive!(TAKE1, "ModTileRequestTimeout", "ModTileRequestTimeout takes the render timeout in seconds"),
+    directive!(
+        TAKE1,
+        "ModTileMissingRequestTimeout",
+        "ModTileMissingRequestTimeout takes the render timeout for missing tiles in seconds"
+    ),
+    directive!(TAKE2, "AddTileConfig", "AddTileConfig takes a base URL and a layer name"),
+    directive!(
+        TAKE3,
+        "AddTileMimeConfig",
+        "AddTileMimeConfig takes a base URL, a layer name and a file extension"
+    ),
+    directive!(TAKE1, "ModTileTileDir", "ModTileTileDir takes the tile cache directory"),
+    directive!(TAKE1, "ModTileRenderdSocketName", "ModTileRenderdSocketName takes the renderd socket path"),
+    directive!(TAKE1, "ModTileAccessLog", "ModTileAccessLog takes the path of the access log file"),
+    directive!(TAKE1, "ModTileTraceExportUri", "ModTileTraceExportUri takes a file:// or unix:// URI to export spans to"),
+    directive!(TAKE1, "ModTileTraceExportFormat", "ModTileTraceExportFormat takes otlp or zipkin"),
+    directive!(TAKE1, "ModTileStatisticsResetToken", "ModTileStatisticsResetToken takes the bearer token for resetting statistics"),
+    directive!(
+        TAKE1,
+        "ModTileConfigDumpAllowedIps",
+        "ModTileConfigDumpAllowedIps takes a comma separated list of the IP addresses allowed to dump the config"
+    ),
+    // mod_tile configurations set these, but load, cache lifetimes and throttling are left to the rest of the
+    // server, so they are accepted with a warning
+    directive!(IGNORED TAKE1, "ModTileMaxLoadOld", "ModTileMaxLoadOld takes the load above which old tiles are not re-rendered"),
+    directive!(IGNORED TAKE1, "ModTileMaxLoadMissing", "ModTileMaxLoadMissing takes the load above which missing tiles are not rendered"),
+    directive!(IGNORED TAKE1, "ModTileCacheExtendedHostname", "ModTileCacheExtendedHostname takes a host name"),
+    directive!(IGNORED TAKE1, "ModTileCacheExtendedDuration", "ModTileCacheExtendedDuration takes a duration in seconds"),
+    directive!(IGNORED TAKE1, "ModTileCacheDurationMax", "ModTileCacheDurationMax takes a duration in seconds"),
+    directive!(IGNORED TAKE1, "ModTileCacheDurationDirty", "ModTileCacheDurationDirty takes a duration in seconds"),
+    directive!(IGNORED TAKE1, "ModTileCacheDurationMinimum", "ModTileCacheDurationMinimum takes a duration in seconds"),
+    directive!(IGNORED TAKE2, "ModTileCacheDurationMediumZoom", "ModTileCacheDurationMediumZoom takes a zoom level and a duration in seconds"),
+    directive!(IGNORED TAKE2, "ModTileCacheDurationLowZoom", "ModTileCacheDurationLowZoom takes a zoom level and a duration in seconds"),
+    directive!(IGNORED TAKE1, "ModTileCacheLastModifiedFactor", "ModTileCacheLastModifiedFactor takes a decimal factor"),
+    directive!(IGNORED FLAG, "ModTileEnableTileThrottling", "ModTileEnableTileThrottling takes On or Off"),
+    directive!(IGNORED TAKE1, "ModTileEnableTileThrottlingXForward", "ModTileEnableTileThrottlingXForward takes 0, 1 or 2"),
+    directive!(IGNORED TAKE2, "ModTileThrottlingTiles", "ModTileThrottlingTiles takes a pool size and a topup rate per second"),
+    directive!(IGNORED TAKE2, "ModTileThrottlingRenders", "ModTileThrottlingRenders takes a pool size and a topup rate per second"),
+    command_rec {
+        name: ptr::null(),
+        func: cmd_func { take1: None },
+        cmd_data: ptr::null_mut(),
+        req_override: 0,
+        args_how: cmd_how_TAKE1,
+        errmsg: ptr::null(),
+    },
+];
 
 #[no_mangle]
-pub extern "C" fn load_config(
+pub extern "C" fn create_server_config(
+    pool: *mut apr_pool_t,
+    record: *mut server_rec,
+) -> *mut c_void {
+    if pool == ptr::null_mut() {
+        return ptr::null_mut();
+    }
+    let key = CString::new(format!("{}@{:p}", type_name::<ServerConfig>(), record)).unwrap();
+    match alloc_server_config(unsafe { pool.as_mut().unwrap() }, &key, ServerConfig::new()) {
+        Ok(server_config) => server_config as *mut ServerConfig as *mut c_void,
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn merge_server_config(
+    pool: *mut apr_pool_t,
+    base_void: *mut c_void,
+    add_void: *mut c_void,
+) -> *mut c_void {
+    if pool == ptr::null_mut() {
+        return ptr::null_mut();
+    }
+    let merged = match (
+        access_pool_object::<ServerConfig>(base_void),
+        access_pool_object::<ServerConfig>(add_void),
+    ) {
+        (Some(base), Some(add)) => ServerConfig::merge(base, add),
+        (Some(base), None) => base.clone(),
+        (None, Some(add)) => add.clone(),
+        (None, None) => ServerConfig::new(),
+    };
+    let key = CString::new(format!("{}@{:p}+{:p}", type_name::<ServerConfig>(), base_void, add_void)).unwrap();
+    match alloc_server_config(unsafe { pool.as_mut().unwrap() }, &key, merged) {
+        Ok(server_config) => server_config as *mut ServerConfig as *mut c_void,
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn load_directive_take1(
     cmd_ptr: *mut cmd_parms,
     _: *mut c_void,
     value: *const c_char,
+) -> *const c_char {
+    return load_directive(cmd_ptr, &[value]);
+}
+
+#[no_mangle]
+pub extern "C" fn load_directive_take2(
+    cmd_ptr: *mut cmd_parms,
+    _: *mut c_void,
+    value1: *const c_char,
+    value2: *const c_char,
+) -> *const c_char {
+    return load_directive(cmd_ptr, &[value1, value2]);
+}
+
+#[no_mangle]
+pub extern "C" fn load_directive_take3(
+    cmd_ptr: *mut cmd_parms,
+    _: *mut c_void,
+    value1: *const c_char,
+    value2: *const c_char,
+    value3: *const c_char,
+) -> *const c_char {
+    return load_directive(cmd_ptr, &[value1, value2, value3]);
+}
+
+fn load_directive(
+    cmd_ptr: *mut cmd_parms,
+    values: &[*const c_char],
 ) -> *const c_char {
     if cmd_ptr == ptr::null_mut() {
         return cstr!("Null cmd_parms");
@@ -185,27 +367,103 @@ pub extern "C" fn load_config(
     if command.server == ptr::null_mut() {
         return cstr!("Nullptr server_rec");
     }
+    if command.cmd == ptr::null() {
+        return cstr!("Nullptr command_rec");
+    }
     let record = unsafe { command.server.as_mut().unwrap() };
-    debug!(record, "tile_server::load_config - start");
-    let path_str = unsafe { CStr::from_ptr(value).to_str().unwrap() };
-    let tile_server = TileProxy::find_or_allocate_new(record).unwrap();
-    let mut file_path = PathBuf::new();
-    file_path.push(path_str);
+    let directive = unsafe { command.cmd.as_ref().unwrap() };
+    let name = match unsafe { CStr::from_ptr(directive.name).to_str() } {
+        Ok(name) => name,
+        Err(_) => return directive.errmsg,
+    };
+    debug!(record, "tile_server::load_directive - start {}", name);
+    let mut args = Vec::with_capacity(values.len());
+    for value in values {
+        match unsafe { CStr::from_ptr(*value).to_str() } {
+            Ok(arg) => args.push(arg),
+            Err(_) => return directive.errmsg,
+        };
+    }
     let host_name = unsafe { command.server.as_mut().unwrap().get_host_name() };
-    match tile_server.load_config(file_path, host_name) {
+    let tile_server = TileProxy::find_or_allocate_new(record).unwrap();
+    match tile_server.apply_directive(name, &args, host_name) {
         Ok(_) => {
-            info!(record, "tile_server::load_config - loaded config from {}", path_str);
+            if let Some(server_config) = find_server_config(record) {
+                server_config.add_directive(name, &args);
+            }
+            info!(record, "tile_server::load_directive - set {} to {}", name, args.join(" "));
             return ptr::null();
         },
         Err(why) => {
-            error!(record, "tile_server::load_config - failed because {}", why);
-            return cstr!("Failed to load tile config file");
+            error!(record, "tile_server::load_directive - failed because {}", why);
+            return copy_errmsg(command, &why.to_string(), directive.errmsg);
         },
     };
 }
 
+// Apache reports the returned message after the handler returns, so it has to live in the config pool
+fn copy_errmsg(
+    command: &cmd_parms,
+    message: &str,
+    fallback: *const c_char,
+) -> *const c_char {
+    if command.pool == ptr::null_mut() {
+        return fallback;
+    }
+    match CString::new(message) {
+        Ok(message) => unsafe { apr_pstrdup(command.pool, message.as_ptr()) as *const c_char },
+        Err(_) => fallback,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ignore_directive_flag(
+    cmd_ptr: *mut cmd_parms,
+    _: *mut c_void,
+    _: c_int,
+) -> *const c_char {
+    return ignore_directive(cmd_ptr);
+}
+
+#[no_mangle]
+pub extern "C" fn ignore_directive_take1(
+    cmd_ptr: *mut cmd_parms,
+    _: *mut c_void,
+    _: *const c_char,
+) -> *const c_char {
+    return ignore_directive(cmd_ptr);
+}
+
+#[no_mangle]
+pub extern "C" fn ignore_directive_take2(
+    cmd_ptr: *mut cmd_parms,
+    _: *mut c_void,
+    _: *const c_char,
+    _: *const c_char,
+) -> *const c_char {
+    return ignore_directive(cmd_ptr);
+}
+
+fn ignore_directive(cmd_ptr: *mut cmd_parms) -> *const c_char {
+    if cmd_ptr == ptr::null_mut() {
+        return cstr!("Null cmd_parms");
+    }
+    let command = unsafe { cmd_ptr.as_mut().unwrap() };
+    if command.server == ptr::null_mut() {
+        return cstr!("Nullptr server_rec");
+    }
+    if command.cmd == ptr::null() {
+        return cstr!("Nullptr command_rec");
+    }
+    let record = unsafe { command.server.as_mut().unwrap() };
+    let directive = unsafe { command.cmd.as_ref().unwrap() };
+    let name = unsafe { CStr::from_ptr(directive.name).to_string_lossy() };
+    warn!(record, "tile_server::ignore_directive - {} is accepted but has no effect", name);
+    return ptr::null();
+}
+
 #[no_mangle]
-pub extern "C" fn load_request_timeout(
+pub extern "C" fn load_config(
     cmd_ptr: *mut cmd_parms,
     _: *mut c_void,
     value: *const c_char,
@@ -218,22 +476,31 @@ pub extern "C" fn load_request_timeout(
         return cstr!("Nullptr server_rec");
     }
     let record = unsafe { command.server.as_mut().unwrap() };
-    debug!(record, "tile_server::load_request_timeout - start");
-    let timeout_str = unsafe { CStr::from_ptr(value).to_str().unwrap() };
-    let timeout_uint = match scan_fmt!(timeout_str, "{d}", i32) {
-        Ok(timeout) => timeout as u64,
-        Err(_) => {
-            return cstr!("ModTileRequestTimeout needs an integer argument");
-        },
+    debug!(record, "tile_server::load_config - start");
+    let path_str = match unsafe { CStr::from_ptr(value).to_str() } {
+        Ok(path_str) => path_str,
+        Err(_) => return cstr!("LoadTileConfigFile takes a UTF-8 path"),
     };
-    let duration = Duration::new(timeout_uint, 0);
     let tile_server = TileProxy::find_or_allocate_new(record).unwrap();
-    tile_server.set_render_timeout(&duration);
-    info!(record, "tile_server::load_request_timeout - set threshold to {} seconds", timeout_uint);
-    return ptr::null();
+    let mut file_path = PathBuf::new();
+    file_path.push(path_str);
+    let host_name = unsafe { command.server.as_mut().unwrap().get_host_name() };
+    match tile_server.load_config(file_path.clone(), host_name) {
+        Ok(_) => {
+            if let Some(server_config) = find_server_config(record) {
+                server_config.config_file_path = Some(file_path);
+            }
+            info!(record, "tile_server::load_config - loaded config from {}", path_str);
+            return ptr::null();
+        },
+        Err(why) => {
+            error!(record, "tile_server::load_config - failed because {}", why);
+            return cstr!("Failed to load tile config file");
+        },
+    };
 }
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "fuzzing")))]
 #[no_mangle]
 pub extern fn register_hooks(_pool: *mut apr_pool_t) {
     unsafe {
@@ -252,9 +519,9 @@ pub extern fn register_hooks(_pool: *mut apr_pool_t) {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fuzzing"))]
 pub extern fn register_hooks(_pool: *mut apr_pool_t) {
-    // this function is a no-op for tests
+    // this function is a no-op for tests and fuzzing
 }
 
 #[no_mangle]
@@ -264,12 +531,17 @@ pub extern "C" fn initialise(
 ) -> () {
     if child_pool != ptr::null_mut() && record != ptr::null_mut() {
         info!(record, "initialise - start");
-        let server = TileProxy::find_or_allocate_new(unsafe { record.as_mut().unwrap() }).unwrap();
-        if let Err(why) = server.initialise(unsafe { record.as_mut().unwrap() }) {
-            error!(record, "initialise - failed to initialise TileServer: {}", why);
-        } else {
-            info!(record, "initialise - finish");
-        };
+        // Each virtual host follows the main server in the list and has its own TileServer
+        let mut server_ptr = record;
+        while server_ptr != ptr::null_mut() {
+            let server_record = unsafe { server_ptr.as_mut().unwrap() };
+            let server = TileProxy::find_or_allocate_new(server_record).unwrap();
+            if let Err(why) = server.initialise(unsafe { server_ptr.as_mut().unwrap() }) {
+                error!(server_ptr, "initialise - failed to initialise TileServer: {}", why);
+            }
+            server_ptr = server_record.next;
+        }
+        info!(record, "initialise - finish");
     }
 }
 
@@ -286,24 +558,38 @@ pub extern "C" fn handle_request(
     }
 
     debug!(record.server, "tile_server::handle_request - start");
-    let server = unsafe { record.server.as_mut().unwrap() };
-    let tile_server = TileProxy::find_or_allocate_new(server).unwrap();
-    match tile_server.handle_request(record) {
+    let server = unsafe { record.server.as_ref().unwrap() };
+    let handle_result = match TileProxy::with_thread_proxy(server, |proxy| proxy.handle_request(record)) {
+        Ok(handle_result) => handle_result,
+        Err(why) => {
+            error!(record.server, "tile_server::handle_request - no proxy to handle the request: {}", why);
+            return HTTP_INTERNAL_SERVER_ERROR as c_int;
+        },
+    };
+    match handle_result {
         Ok(result) => {
             debug!(record.server, "tile_server::handle_request - request handled");
             return result;
         },
-        Err(why) => match why {
-            HandleRequestError::Write(write_err) => match write_err {
-                WriteError::RequestNotHandled => return DECLINED as c_int,
-                _ => {
-                    error!(record.server, "tile_server::handle_request - write failed: {}", write_err);
-                    return HTTP_INTERNAL_SERVER_ERROR as c_int;
-                }
-            },
-            _ => {
-                error!(record.server, "tile_server::handle_request - failed: {}", why);
-                return HTTP_INTERNAL_SERVER_ERROR as c_int;
+        Err(why) => {
+            let status_code = match &why {
+                HandleRequestError::Read(read_err) => ErrorStatusMapper::read_error_status(read_err),
+                HandleRequestError::Handle(handle_err) => Some(ErrorStatusMapper::handle_error_status(handle_err)),
+                HandleRequestError::Write(write_err) => ErrorStatusMapper::write_error_status(write_err),
+            };
+            match status_code {
+                Some(status_code) => {
+                    if status_code.is_server_error() {
+                        error!(record.server, "tile_server::handle_request - failed: {}", why);
+                    } else {
+                        info!(record.server, "tile_server::handle_request - responding with {}: {}", status_code, why);
+                    }
+                    return status_code.as_u16() as c_int;
+                },
+                None => {
+                    debug!(record.server, "tile_server::handle_request - declined: {}", why);
+                    return DECLINED as c_int;
+                },
             }
         },
     };
@@ -1,14 +1,21 @@
 use crate::schema::tile::identity::LayerName;
 
+use serde::{Serialize, Serializer,};
+
 use std::clone::Clone;
 use std::collections::hash_map::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::option::Option;
 use std::time::Duration;
+use std::vec::Vec;
 
 
 #[derive(Clone, Debug)]
 pub struct ModuleConfig {
     pub renderd: RenderdConfig,
     pub layers: HashMap<LayerName, LayerConfig>,
+    pub telemetry: TelemetryConfig,
 }
 
 impl ModuleConfig {
@@ -16,18 +23,24 @@ impl ModuleConfig {
         let mut value = ModuleConfig {
             renderd: RenderdConfig::new(),
             layers: HashMap::new(),
+            telemetry: TelemetryConfig::new(),
         };
         value.layers.insert(LayerName::from("default"), LayerConfig::new());
         value
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct RenderdConfig {
     pub store_uri: String,
     pub ipc_uri: String,
+    pub ip_host_name: String,
+    pub ip_port: Option<u16>,
     pub availability_timeout: Duration,
     pub render_timeout: Duration,
+    pub missing_render_timeout: Duration,
+    pub failover_endpoints: Vec<RenderdEndpoint>,
+    pub balance_policy: BalancePolicy,
 }
 
 impl RenderdConfig {
@@ -35,16 +48,92 @@ impl RenderdConfig {
         RenderdConfig {
             store_uri: String::from("/var/cache/renderd"),
             ipc_uri: String::from("/var/run/renderd/renderd.sock"),
+            ip_host_name: String::from("localhost"),
+            ip_port: None,
             availability_timeout: Duration::new(0, 0),
             render_timeout: Duration::new(0, 0),
+            // Like mod_tile, a missing tile waits longer than a stale one but never indefinitely
+            missing_render_timeout: Duration::from_secs(10),
+            failover_endpoints: Vec::new(),
+            balance_policy: BalancePolicy::RoundRobin,
         }
     }
+
+    // Like renderd, a TCP port takes precedence over the Unix socket
+    pub fn primary_endpoint(&self) -> RenderdEndpoint {
+        match self.ip_port {
+            Some(port) => RenderdEndpoint::Tcp {
+                host: self.ip_host_name.clone(),
+                port,
+            },
+            None => RenderdEndpoint::Unix(self.ipc_uri.clone()),
+        }
+    }
+
+    pub fn endpoints(&self) -> Vec<RenderdEndpoint> {
+        let mut endpoints = vec![self.primary_endpoint()];
+        endpoints.extend(self.failover_endpoints.iter().cloned());
+        return endpoints;
+    }
 }
 
-pub const MAX_ZOOM_SERVER: usize = 30;
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum RenderdEndpoint {
+    Unix(String),
+    Tcp {
+        host: String,
+        port: u16,
+    },
+}
+
+impl fmt::Display for RenderdEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderdEndpoint::Unix(path) => write!(f, "unix://{}", path),
+            RenderdEndpoint::Tcp { host, port } => write!(f, "tcp://{}:{}", host, port),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum BalancePolicy {
+    RoundRobin,
+    LeastOutstanding,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceExportFormat {
+    OtlpJson,
+    ZipkinJson,
+}
 
 #[derive(Clone, Debug)]
+pub struct TelemetryConfig {
+    pub trace_export_uri: Option<String>,
+    pub trace_export_format: TraceExportFormat,
+    pub statistics_reset_token: Option<String>,
+    pub config_dump_allowed_ips: Vec<IpAddr>,
+    pub access_log_path: Option<String>,
+}
+
+impl TelemetryConfig {
+    pub fn new() -> TelemetryConfig {
+        TelemetryConfig {
+            trace_export_uri: None,
+            trace_export_format: TraceExportFormat::OtlpJson,
+            statistics_reset_token: None,
+            config_dump_allowed_ips: Vec::new(),
+            access_log_path: None,
+        }
+    }
+}
+
+pub const MAX_ZOOM_SERVER: usize = 30;
+pub const ANY_PARAMETER: &str = "*";
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct LayerConfig {
+    #[serde(serialize_with = "serialize_layer_name")]
     pub name: LayerName,
     pub base_url: String,
     pub description: String,
@@ -55,6 +144,9 @@ pub struct LayerConfig {
     pub mime_type: String,
     pub host_name: String,
     pub parameters_allowed: bool,
+    // Parameters are refused unless listed, or unless ANY_PARAMETER is listed
+    pub allowed_parameters: Vec<String>,
+    pub renderd_endpoints: Vec<RenderdEndpoint>,
 }
 
 impl LayerConfig {
@@ -70,6 +162,8 @@ impl LayerConfig {
             mime_type: String::from("image/png"),
             host_name: String::new(),
             parameters_allowed: false,
+            allowed_parameters: Vec::new(),
+            renderd_endpoints: Vec::new(),
         };
         config.set_host_name("localhost");
         config
@@ -82,3 +176,10 @@ impl LayerConfig {
         self.host_name = format!("http://{}", host_name);
     }
 }
+
+fn serialize_layer_name<S: Serializer>(
+    name: &LayerName,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(name.as_str())
+}
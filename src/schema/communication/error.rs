@@ -7,6 +7,8 @@ use std::fmt;
 pub enum CommunicationError {
     #[error("Timeout during communication")]
     TimeoutError,
+    #[error("Renderd is unavailable while its circuit is open")]
+    Unavailable,
     #[error("IO error during communication")]
     Io(#[from] std::io::Error),
 }
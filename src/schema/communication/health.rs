@@ -0,0 +1,19 @@
+use serde::Serialize;
+
+use std::string::String;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ChannelHealth {
+    pub endpoint: String,
+    pub circuit: CircuitState,
+    pub consecutive_failures: u32,
+    pub reconnect_count: u64,
+}
@@ -22,6 +22,8 @@ pub enum HandleError {
     Communication(#[from] CommunicationError),
     #[error("Tile rendering error")]
     Render(#[from] RenderError),
+    #[error("Request is forbidden: {0}")]
+    Forbidden(String),
 }
 
 #[derive(Error, Debug)]
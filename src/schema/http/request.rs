@@ -1,7 +1,10 @@
 use crate::binding::apache2::request_rec;
+use crate::framework::apache2::record::RequestRecord;
 
 use chrono::{DateTime, Utc,};
 
+use std::option::Option;
+
 
 #[derive(Debug)]
 pub struct HttpRequest<'r> {
@@ -23,4 +26,16 @@ impl<'r> HttpRequest<'r> {
             record,
         }
     }
+
+    pub fn header(&self, name: &str) -> Option<&'r str> {
+        self.record.get_header_in(name)
+    }
+
+    pub fn client_ip(&self) -> Option<&'r str> {
+        self.record.get_client_ip()
+    }
+
+    pub fn method(&self) -> Option<&'r str> {
+        self.record.get_method()
+    }
 }
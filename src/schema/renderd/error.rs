@@ -1,4 +1,6 @@
 use crate::schema::communication::error::CommunicationError;
+use crate::schema::renderd::response::RenderResponseCommand;
+use crate::schema::tile::error::TileReadError;
 
 use thiserror::Error;
 
@@ -18,10 +20,31 @@ impl fmt::Display for InvalidParameterError {
     }
 }
 
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    #[error("Response is {actual} bytes but {expected} bytes were expected")]
+    InvalidLength {
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Response version {0} does not match the request")]
+    VersionMismatch(i32),
+    #[error("Response command {0} is not a valid response")]
+    InvalidCommand(u32),
+    #[error("Response is for a different tile than the request")]
+    TileMismatch,
+}
+
 #[derive(Error, Debug)]
 pub enum RenderError {
     #[error("Invalid parameter: {0:?}")]
     InvalidParameter(#[from] InvalidParameterError),
     #[error("Error communicating with rendering service: {0:?}")]
-    Communication(#[from] CommunicationError)
+    Communication(#[from] CommunicationError),
+    #[error("Invalid response from rendering service: {0}")]
+    Protocol(#[from] ProtocolError),
+    #[error("Rendering service did not render the tile: {0:?}")]
+    NotRendered(RenderResponseCommand),
+    #[error("Failed to read the rendered tile: {0}")]
+    TileRead(#[from] TileReadError),
 }
@@ -84,8 +84,8 @@ impl Constructable for protocol {
         );
         let layer_as_i8_slice = unsafe {
             // on x86_64 c_char is aliased to i8
-            core::slice::from_raw_parts_mut(
-                layer.as_u8().as_ptr() as *mut i8,
+            core::slice::from_raw_parts(
+                layer.as_u8().as_ptr() as *const i8,
                 layer.len(),
             )
         };
@@ -104,13 +104,35 @@ impl Constructable for protocol {
         }
         let extension_as_i8_slice = unsafe {
             // on x86_64 c_char is aliased to i8
-            core::slice::from_raw_parts_mut(
-                extension.as_bytes().as_ptr() as *mut i8,
+            core::slice::from_raw_parts(
+                extension.as_bytes().as_ptr() as *const i8,
                 extension.len(),
             )
         };
         result.mimetype.as_mut_slice()[..extension.len()].copy_from_slice(extension_as_i8_slice);
         result.mimetype.as_mut_slice()[extension.len()] = 0;  // C string null terminator
+
+        if let Some(parameter) = &tile_id.parameter {
+            const OPTIONS_LIMIT: usize = size_of_return_type(|p: protocol| p.options);
+            if parameter.len() >= OPTIONS_LIMIT {
+                return Err(
+                    InvalidParameterError {
+                        param: "options".to_string(),
+                        value: parameter.clone(),
+                        reason: format!("Options parameter must be less than {}", OPTIONS_LIMIT),
+                    }
+                )
+            }
+            let parameter_as_i8_slice = unsafe {
+                // on x86_64 c_char is aliased to i8
+                core::slice::from_raw_parts(
+                    parameter.as_bytes().as_ptr() as *const i8,
+                    parameter.len(),
+                )
+            };
+            result.options.as_mut_slice()[..parameter.len()].copy_from_slice(parameter_as_i8_slice);
+            result.options.as_mut_slice()[parameter.len()] = 0;  // C string null terminator
+        }
         Ok(result)
     }
 }
@@ -133,6 +155,7 @@ mod tests {
             y: 2,
             z: 3,
             layer: layer.clone(),
+            parameter: None,
         };
         let extension = "1234567890123456789012345678901234567890";
         let value = protocol::new(
@@ -144,8 +167,26 @@ mod tests {
         )?;
         let actual_layer = unsafe { CStr::from_ptr(value.xmlname.as_ptr() as *const c_char) };
         let actual_extension= unsafe { CStr::from_ptr(value.mimetype.as_ptr() as *const c_char) };
+        let actual_options = unsafe { CStr::from_ptr(value.options.as_ptr() as *const c_char) };
         assert!(layer.len() == actual_layer.to_bytes().len(), "Copied layer name has wrong length");
         assert!(extension.len() == actual_extension.to_bytes().len(), "Copied extension has wrong length");
+        assert!(actual_options.to_bytes().is_empty(), "Options set without a parameter");
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_protocol_with_parameter() -> Result<(), Box<dyn StdError>> {
+        let layer = LayerName::make("osm");
+        let tile_id = TileIdentity {
+            x: 1,
+            y: 2,
+            z: 3,
+            layer: layer.clone(),
+            parameter: Some(String::from("de")),
+        };
+        let value = protocol::new(RenderRequestVersion::Three, RenderRequestCommand::Render, &layer, &tile_id, "png")?;
+        let actual_options = unsafe { CStr::from_ptr(value.options.as_ptr() as *const c_char) };
+        assert_eq!(b"de", actual_options.to_bytes(), "Failed to copy the parameter into options");
         Ok(())
     }
 
@@ -159,6 +200,7 @@ mod tests {
             y: 2,
             z: 3,
             layer: layer.clone(),
+            parameter: None,
         };
         let extension = "12345678901234567890123456789012345678901";
         let result = protocol::new(
@@ -10,6 +10,7 @@ pub enum RenderResponseVersion {
     Three = 3,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RenderResponseCommand {
     Done = protoCmd_cmdDone as isize,
     InvalidRequestIgnored = protoCmd_cmdIgnore as isize,
@@ -1,5 +1,6 @@
 use crate::schema::communication::error::ResponseWriteError;
 
+use http::header::{InvalidHeaderValue, ToStrError,};
 use thiserror::Error;
 use mime::Mime;
 
@@ -12,6 +13,10 @@ use std::str::Utf8Error;
 pub enum ReadError {
     #[error("Invalid parameter: {0:?}")]
     Param(#[from] InvalidParameterError),
+    #[error("URI {0} does not match any known request types")]
+    NotMatched(String),
+    #[error("Method {0} is not allowed for this URI")]
+    MethodNotAllowed(String),
     #[error("An IO error while reading")]
     Io(#[from] Rc<std::io::Error>),
     #[error("Non Utf8 bytes were read")]
@@ -28,6 +33,28 @@ pub enum WriteError {
     ResponseWrite(#[from] ResponseWriteError),
     #[error("IO error while writing")]
     Io(#[from] Rc<std::io::Error>),
+    #[error("Invalid HTTP header: {0}")]
+    InvalidHeader(String),
+    #[error("Failed to serialise the response body")]
+    Serialise(Rc<serde_json::Error>),
+}
+
+impl From<ToStrError> for WriteError {
+    fn from(error: ToStrError) -> Self {
+        WriteError::InvalidHeader(error.to_string())
+    }
+}
+
+impl From<InvalidHeaderValue> for WriteError {
+    fn from(error: InvalidHeaderValue) -> Self {
+        WriteError::InvalidHeader(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for WriteError {
+    fn from(error: serde_json::Error) -> Self {
+        WriteError::Serialise(Rc::new(error))
+    }
 }
 
 #[derive(Error, Debug, Clone)]
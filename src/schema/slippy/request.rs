@@ -28,8 +28,24 @@ pub struct Header {
 #[derive(Debug)]
 pub enum BodyVariant {
     ReportStatistics,
+    ResetStatistics(ResetStatisticsRequest),
+    DumpConfig(DumpConfigRequest),
     DescribeLayer,
     ServeTile(ServeTileRequest),
+    // Stands in for a request that could not be read, so that its error response can still be observed
+    Unread,
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct ResetStatisticsRequest {
+    pub credential: Option<String>,
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct DumpConfigRequest {
+    pub client_ip: Option<String>,
 }
 
 #[derive(PartialEq)]
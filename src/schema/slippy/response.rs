@@ -1,9 +1,14 @@
-use crate::schema::apache2::config::MAX_ZOOM_SERVER;
+use crate::schema::apache2::config::{LayerConfig, RenderdConfig, MAX_ZOOM_SERVER,};
+use crate::schema::communication::health::ChannelHealth;
+use crate::schema::telemetry::window::TimeWindow;
 use crate::schema::tile::age::TileAge;
 use crate::schema::tile::source::TileSource;
 use crate::schema::tile::tile_ref::TileRef;
 
 use chrono::{DateTime, Utc,};
+use http::header::HeaderMap;
+use http::status::StatusCode;
+use enum_iterator::IntoEnumIterator;
 use mime::Mime;
 use serde::Serialize;
 
@@ -29,11 +34,30 @@ pub struct Header {
 
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum BodyVariant {
+    Configuration(ConfigurationDump),
     Description(Description),
     Statistics(Statistics),
+    Status(StatusResponse),
     Tile(TileResponse),
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ConfigurationDump {
+    pub server: ServerIdentity,
+    pub renderd: RenderdConfig,
+    pub layers: Vec<LayerConfig>,
+}
+
+// Identifies the Apache server_rec, which is either the main server or a virtual host
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ServerIdentity {
+    pub host_name: Option<String>,
+    pub port: u16,
+    pub is_virtual: bool,
+    pub defined_in: Option<String>,
+    pub defined_at_line: u32,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct Description {
     pub tilejson: &'static str,
@@ -67,6 +91,8 @@ pub struct Statistics {
     pub duration_tile_response_by_zoom: Vec<u64>,
     pub number_response_200_by_layer: HashMap<String, u64>,
     pub number_response_404_by_layer: HashMap<String, u64>,
+    pub windows: HashMap<String, WindowedStatistics>,
+    pub renderd_health: Vec<ChannelHealth>,
 }
 
 impl Statistics {
@@ -91,10 +117,61 @@ impl Statistics {
             duration_tile_response_by_zoom: vec![Default::default(); MAX_ZOOM_SERVER + 1],
             number_response_200_by_layer: HashMap::new(),
             number_response_404_by_layer:  HashMap::new(),
+            windows: TimeWindow::into_enum_iter().map(|window| {
+                (String::from(window.label()), WindowedStatistics::new())
+            }).collect(),
+            renderd_health: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WindowedStatistics {
+    pub number_response_200: u64,
+    pub number_response_304: u64,
+    pub number_response_404: u64,
+    pub number_response_503: u64,
+    pub number_response_5xx: u64,
+    pub number_response_other: u64,
+    pub number_fresh_cache: u64,
+    pub number_old_cache: u64,
+    pub number_very_old_cache: u64,
+    pub number_fresh_render: u64,
+    pub number_old_render: u64,
+    pub number_very_old_render: u64,
+    pub total_number_tile_response: u64,
+    pub total_duration_tile_response: u64,
+}
+
+impl WindowedStatistics {
+    pub fn new() -> WindowedStatistics {
+        WindowedStatistics {
+            number_response_200: 0,
+            number_response_304: 0,
+            number_response_404: 0,
+            number_response_503: 0,
+            number_response_5xx: 0,
+            number_response_other: 0,
+            number_fresh_cache: 0,
+            number_old_cache: 0,
+            number_very_old_cache: 0,
+            number_fresh_render: 0,
+            number_old_render: 0,
+            number_very_old_render: 0,
+            total_number_tile_response: 0,
+            total_duration_tile_response: 0,
         }
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatusResponse {
+    #[serde(skip_serializing)]
+    pub status_code: StatusCode,
+    #[serde(skip_serializing)]
+    pub http_headers: HeaderMap,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct TileResponse {
     pub source: TileSource,
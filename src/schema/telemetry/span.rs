@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc,};
+
+use std::option::Option;
+use std::string::String;
+use std::vec::Vec;
+
+
+pub type TraceId = [u8; 16];
+pub type SpanId = [u8; 8];
+
+pub const TRACE_PARENT_HEADER: &str = "traceparent";
+const TRACE_PARENT_VERSION: &str = "00";
+const TRACE_FLAG_SAMPLED: u8 = 0x01;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceContext {
+    pub trace_id: TraceId,
+    pub parent_span_id: SpanId,
+    pub flags: u8,
+}
+
+impl TraceContext {
+    pub fn parse(trace_parent: &str) -> Option<TraceContext> {
+        let fields: Vec<&str> = trace_parent.trim().split('-').collect();
+        if fields.len() != 4 || fields[0] != TRACE_PARENT_VERSION {
+            return None;
+        }
+        let mut trace_id: TraceId = [0; 16];
+        let mut parent_span_id: SpanId = [0; 8];
+        let mut flags: [u8; 1] = [0; 1];
+        if !decode_hex(fields[1], &mut trace_id)
+            || !decode_hex(fields[2], &mut parent_span_id)
+            || !decode_hex(fields[3], &mut flags) {
+            return None;
+        }
+        // All zero identifiers are invalid according to the W3C Trace Context specification
+        if trace_id.iter().all(|byte| *byte == 0) || parent_span_id.iter().all(|byte| *byte == 0) {
+            return None;
+        }
+        return Some(
+            TraceContext {
+                trace_id,
+                parent_span_id,
+                flags: flags[0],
+            }
+        );
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        (self.flags & TRACE_FLAG_SAMPLED) != 0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpanKind {
+    Server,
+    Internal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpanStatus {
+    Ok,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub parent_span_id: Option<SpanId>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub status: SpanStatus,
+    pub attributes: Vec<(&'static str, String)>,
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str, output: &mut [u8]) -> bool {
+    if !text.is_ascii() || text.len() != output.len() * 2 {
+        return false;
+    }
+    for (index, byte) in output.iter_mut().enumerate() {
+        match u8::from_str_radix(&text[(index * 2)..(index * 2 + 2)], 16) {
+            Ok(value) => *byte = value,
+            Err(_) => return false,
+        }
+    }
+    return true;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_parse_valid_trace_parent() -> Result<(), Box<dyn StdError>> {
+        let context = TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+            .expect("Failed to parse valid traceparent");
+        assert_eq!("4bf92f3577b34da6a3ce929d0e0e4736", encode_hex(&context.trace_id), "Failed to parse trace_id");
+        assert_eq!("00f067aa0ba902b7", encode_hex(&context.parent_span_id), "Failed to parse parent_span_id");
+        assert!(context.is_sampled(), "Failed to parse sampled flag");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_trace_parent() -> Result<(), Box<dyn StdError>> {
+        assert!(TraceContext::parse("").is_none(), "Empty traceparent was parsed");
+        assert!(
+            TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none(),
+            "Unsupported traceparent version was parsed"
+        );
+        assert!(
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01").is_none(),
+            "Short trace_id was parsed"
+        );
+        assert!(
+            TraceContext::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none(),
+            "All zero trace_id was parsed"
+        );
+        assert!(
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902zz-01").is_none(),
+            "Non hex parent_span_id was parsed"
+        );
+        Ok(())
+    }
+}
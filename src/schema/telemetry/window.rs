@@ -0,0 +1,30 @@
+use enum_iterator::IntoEnumIterator;
+use serde::Serialize;
+
+use std::marker::Copy;
+
+
+#[derive(Copy, Clone, Debug, Eq, Hash, IntoEnumIterator, PartialEq, Serialize)]
+pub enum TimeWindow {
+    OneMinute = 0,
+    FiveMinutes,
+    OneHour,
+}
+
+impl TimeWindow {
+    pub fn minutes(&self) -> i64 {
+        match self {
+            TimeWindow::OneMinute => 1,
+            TimeWindow::FiveMinutes => 5,
+            TimeWindow::OneHour => 60,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TimeWindow::OneMinute => "1m",
+            TimeWindow::FiveMinutes => "5m",
+            TimeWindow::OneHour => "1h",
+        }
+    }
+}
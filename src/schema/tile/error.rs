@@ -26,6 +26,10 @@ pub enum InvalidMetaTileError {
     InvalidTileCount(i32),
     #[error("Invalid tile length found in meta tile: {0}")]
     InvalidTileLength(u32),
+    #[error("Meta tile of {0} bytes is too small for its header")]
+    InvalidHeaderSize(usize),
+    #[error("Meta tile of {0} bytes is too small for its index")]
+    InvalidIndexSize(usize),
 }
 
 #[derive(Error, Debug, Clone)]
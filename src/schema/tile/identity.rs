@@ -1,6 +1,8 @@
 use fixedstr::fstr;
 
 use std::mem::size_of;
+use std::option::Option;
+use std::string::String;
 
 
 pub type LayerName = fstr<16>;
@@ -13,9 +15,28 @@ pub const fn max_layer_name_char_len() -> usize {
 
 }
 
+pub const META_TILE_WIDTH: i32 = 8;
+pub const META_TILE_MASK: i32 = META_TILE_WIDTH - 1;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct TileIdentity {
     pub x: i32,
     pub y: i32,
     pub z: i32,
     pub layer: LayerName,
+    // The style parameter of a parameterized layer, which renderd renders and stores separately
+    pub parameter: Option<String>,
+}
+
+impl TileIdentity {
+    // renderd renders a whole meta tile at once, identified by the tile in its top left corner
+    pub fn meta_tile(&self) -> TileIdentity {
+        TileIdentity {
+            x: self.x & !META_TILE_MASK,
+            y: self.y & !META_TILE_MASK,
+            z: self.z,
+            layer: self.layer.clone(),
+            parameter: self.parameter.clone(),
+        }
+    }
 }
@@ -8,6 +8,8 @@ use std::clone::Clone;
 use std::cmp::PartialEq;
 use std::fmt::Debug;
 use std::cell::RefCell;
+use std::option::Option;
+use std::time::SystemTime;
 
 
 #[derive(Clone, Debug)]
@@ -17,6 +19,7 @@ pub struct TileRef {
     pub end: usize,
     pub media_type: Mime,
     pub encoding: ContentEncoding,
+    pub modified_time: Option<SystemTime>,
 }
 
 impl TileRef {
@@ -1,39 +1,44 @@
-use crate::binding::renderd_protocol::{protocol, protoCmd};
+use crate::binding::renderd_protocol::protocol;
 use crate::schema::apache2::config::RenderdConfig;
-use crate::schema::renderd::error::RenderError;
-use crate::schema::renderd::request::{RenderRequestCommand, RenderRequestVersion};
-use crate::schema::slippy::request::{Header, ServeTileRequest,};
+use crate::schema::renderd::error::{InvalidParameterError, RenderError,};
+use crate::schema::renderd::request::{Constructable, RenderRequestCommand, RenderRequestVersion};
+use crate::schema::slippy::request::ServeTileRequest;
 use crate::schema::tile::identity::TileIdentity;
 use crate::schema::tile::tile_ref::TileRef;
 use crate::io::interface::IOContext;
+use crate::framework::apache2::context::HostContext;
+
+use std::time::Duration;
 
 
 pub fn create_request(
     _config: &RenderdConfig,
-    _header: &Header,
-    _body: &ServeTileRequest,
-) -> protocol {
-    let result = protocol {
-        ver: RenderRequestVersion::Three as std::os::raw::c_int,
-        cmd: RenderRequestCommand::Render as protoCmd,
-        x: 0 as std::os::raw::c_int,
-        y: 0 as std::os::raw::c_int,
-        z: 0 as std::os::raw::c_int,
-        xmlname: [0; 41usize],
-        mimetype: [0; 41usize],
-        options: [0; 41usize],
+    tile_id: &TileIdentity,
+    body: &ServeTileRequest,
+) -> Result<protocol, InvalidParameterError> {
+    let extension = match body {
+        ServeTileRequest::V2(body) => &body.extension,
+        ServeTileRequest::V3(body) => &body.extension,
     };
-    result
+    protocol::new(
+        RenderRequestVersion::Three,
+        RenderRequestCommand::Render,
+        &tile_id.layer,
+        tile_id,
+        extension,
+    )
 }
 
 pub trait TileRenderer {
     fn render_tile(
         &mut self,
+        context: &HostContext,
         io: &mut IOContext,
         tile_id: TileIdentity,
         request: &protocol,
         response: &mut protocol,
         priority: u8,
+        timeout: &Duration,
     ) -> Result<TileRef, RenderError>;
 }
 
@@ -64,11 +69,13 @@ pub mod test_utils {
     impl TileRenderer for MockTileRenderer {
         fn render_tile(
             &mut self,
+            _context: &HostContext,
             io: &mut crate::io::interface::IOContext,
             tile_id: crate::schema::tile::identity::TileIdentity,
             request: &crate::binding::renderd_protocol::protocol,
             response: &mut crate::binding::renderd_protocol::protocol,
             priority: u8,
+            _timeout: &std::time::Duration,
         ) -> Result<TileRef, crate::schema::renderd::error::RenderError> {
             Ok(
                 TileRef {
@@ -77,6 +84,7 @@ pub mod test_utils {
                     end: 1,
                     media_type: mime::IMAGE_PNG,
                     encoding: ContentEncoding::NotCompressed,
+                    modified_time: None,
                 }
             )
         }
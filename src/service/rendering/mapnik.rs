@@ -1,21 +1,22 @@
-use crate::binding::renderd_protocol::protocol;
+use crate::adapter::render_proto::codec::{decode, encode,};
+use crate::binding::renderd_protocol::{protoCmd, protocol,};
 use crate::schema::apache2::config::ModuleConfig;
 use crate::schema::apache2::error::InvalidConfigError;
-use crate::schema::http::encoding::ContentEncoding;
 use crate::schema::renderd::error::RenderError;
+use crate::schema::renderd::request::RenderRequest;
+use crate::schema::renderd::response::RenderResponseCommand;
 use crate::schema::tile::identity::TileIdentity;
 use crate::schema::tile::tile_ref::TileRef;
 use crate::io::interface::IOContext;
+use crate::framework::apache2::context::HostContext;
 use crate::service::rendering::interface::TileRenderer;
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::Duration;
 
-use std::collections::HashMap;
 use std::cell::RefCell;
 
 
 pub struct Mapnik {
-    _request_expiry_by_tile_id: HashMap<TileIdentity, DateTime<Utc>>,
     _render_timeout: Duration,
     response_buffer: RefCell<Vec<u8>>,  // TODO: use a buffer pool
 }
@@ -23,7 +24,6 @@ pub struct Mapnik {
 impl Mapnik {
     pub fn new(config: &ModuleConfig) -> Result<Mapnik, InvalidConfigError> {
         let value = Mapnik {
-            _request_expiry_by_tile_id: HashMap::new(),
             _render_timeout: Duration::from_std(
                 config.renderd.render_timeout.clone()
             ).or_else(|_| {
@@ -43,25 +43,39 @@ impl Mapnik {
 impl TileRenderer for Mapnik {
     fn render_tile(
         &mut self,
-        _io: &mut IOContext,
-        _tile_id: TileIdentity,
-        _request: &protocol,
-        _response: &mut protocol,
+        context: &HostContext,
+        io: &mut IOContext,
+        tile_id: TileIdentity,
+        request: &protocol,
+        response: &mut protocol,
         _priority: u8,
+        timeout: &std::time::Duration,
     ) -> Result<TileRef, RenderError> {
-        Ok(
-            TileRef {
-                raw_bytes: self.response_buffer.clone(),
-                begin: 0,
-                end: 1,
-                media_type: mime::IMAGE_PNG,
-                encoding: ContentEncoding::NotCompressed,
-            }
-        )
+        let render_request = RenderRequest::V3(*request);
+        let mut response_buffer = self.response_buffer.replace(Vec::new());
+        response_buffer.clear();
+        let frame = io.communication.renderd_comms(&tile_id.layer).send_blocking_request(
+            context,
+            &encode(&render_request),
+            Some(response_buffer),
+            Some(*timeout),
+        )?;
+        let decode_result = decode(&render_request, &frame);
+        self.response_buffer.replace(frame);
+        let command = decode_result?;
+        *response = *request;
+        response.cmd = command as protoCmd;
+        match command {
+            // renderd has written the whole meta tile to storage by the time it replies
+            RenderResponseCommand::Done => {
+                let tile_ref = io.storage.primary_tile_store().read_tile(context, &tile_id)?;
+                Ok(tile_ref)
+            },
+            other => Err(RenderError::NotRendered(other)),
+        }
     }
 }
 
-
 mod tests {
 
     use super::*;
@@ -0,0 +1,136 @@
+use crate::schema::http::response::HttpResponse;
+use crate::schema::slippy::error::WriteError;
+use crate::schema::slippy::request::SlippyRequest;
+use crate::schema::slippy::response::SlippyResponse;
+use crate::io::communication::interface::HttpResponseWriter;
+use crate::adapter::slippy::interface::{WriteContext, WriteResponseObserver,};
+use crate::service::telemetry::interface::TelemetryObserver;
+
+use chrono::Utc;
+
+use std::fs::{File, OpenOptions,};
+use std::io::{self, Write,};
+use std::path::Path;
+use std::result::Result;
+use std::string::String;
+
+
+// Written as a line per response, keyed by the request ID so that it can be joined with the exported spans
+pub struct AccessLog {
+    file: File,
+}
+
+impl AccessLog {
+    pub fn open(path: &Path) -> Result<AccessLog, io::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        return Ok(AccessLog { file });
+    }
+}
+
+impl TelemetryObserver for AccessLog {
+    fn write_response_observer(&mut self) -> Option<&mut dyn WriteResponseObserver> {
+        Some(self)
+    }
+}
+
+impl WriteResponseObserver for AccessLog {
+    fn on_write(
+        &mut self,
+        context: &WriteContext,
+        _response: &SlippyResponse,
+        _writer: &dyn HttpResponseWriter,
+        write_result: &Result<HttpResponse, WriteError>,
+        _write_func_name: &'static str,
+        request: &SlippyRequest,
+    ) -> () {
+        let (status, bytes_written) = match write_result {
+            Ok(http_response) => (http_response.status_code.as_u16().to_string(), http_response.bytes_written),
+            Err(_) => (String::from("-"), 0),
+        };
+        // Requests that aren't for a layer still need a field, so the line splits the same way
+        let layer = match request.header.layer.as_str() {
+            "" => "-",
+            layer => layer,
+        };
+        let duration = Utc::now() - request.header.received_timestamp;
+        let line = format!(
+            "{} {} {} \"{}\" {} {} {}\n",
+            request.header.received_timestamp.to_rfc3339(),
+            request.header.request_id,
+            layer,
+            request.header.uri,
+            status,
+            bytes_written,
+            duration.num_microseconds().unwrap_or(i64::MAX),
+        );
+        if let Err(err) = self.file.write_all(line.as_bytes()) {
+            warn!(context.host().record, "AccessLog::on_write - failed to write the access log: {}", err);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::identifier::generate_id;
+    use crate::schema::apache2::config::ModuleConfig;
+    use crate::schema::slippy::request::{BodyVariant, Header,};
+    use crate::schema::slippy::response;
+    use crate::schema::tile::identity::LayerName;
+    use crate::framework::apache2::context::HostContext;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+    use crate::io::communication::http_exchange::test_utils::CapturingWriter;
+    use http::header::HeaderMap;
+    use http::status::StatusCode;
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+    use std::fs;
+
+    #[test]
+    fn test_line_per_response() -> Result<(), Box<dyn StdError>> {
+        let log_file = mktemp::Temp::new_file()?;
+        let mut access_log = AccessLog::open(&log_file)?;
+        let module_config = ModuleConfig::new();
+        with_request_rec(|record| {
+            let request = SlippyRequest {
+                header: Header {
+                    layer: LayerName::from("default"),
+                    request_id: generate_id(),
+                    uri: String::from("/mod_tile_rs"),
+                    received_timestamp: Utc::now(),
+                },
+                body: BodyVariant::ReportStatistics,
+            };
+            let context = WriteContext {
+                host_context: HostContext::new(&module_config, record),
+                request: &request,
+            };
+            let response = SlippyResponse {
+                header: response::Header {
+                    mime_type: mime::APPLICATION_JSON,
+                    before_timestamp: Utc::now(),
+                    after_timestamp: Utc::now(),
+                },
+                body: response::BodyVariant::Statistics(response::Statistics::new()),
+            };
+            let write_result = Ok(
+                HttpResponse {
+                    status_code: StatusCode::OK,
+                    bytes_written: 42,
+                    http_headers: HeaderMap::new(),
+                }
+            );
+            access_log.on_write(&context, &response, &CapturingWriter::new(), &write_result, "test", &request);
+            let lines: Vec<String> = fs::read_to_string(&log_file)?.lines().map(|line| line.to_string()).collect();
+            assert_eq!(1, lines.len(), "Incorrect number of lines");
+            let fields: Vec<&str> = lines[0].split(' ').collect();
+            assert_eq!(request.header.request_id.to_string(), fields[1], "Incorrect request ID");
+            assert_eq!("default", fields[2], "Incorrect layer");
+            assert_eq!("\"/mod_tile_rs\"", fields[3], "Incorrect URI");
+            assert_eq!("200", fields[4], "Incorrect status");
+            assert_eq!("42", fields[5], "Incorrect number of bytes");
+            Ok(())
+        })
+    }
+}
@@ -6,6 +6,7 @@ use crate::schema::http::response::HttpResponse;
 use crate::schema::slippy::error::{ReadError, WriteError,};
 use crate::schema::slippy::request::{Header, ServeTileRequest, SlippyRequest,};
 use crate::schema::slippy::response::SlippyResponse;
+use crate::schema::telemetry::window::TimeWindow;
 use crate::io::communication::interface::HttpResponseWriter;
 use crate::adapter::slippy::interface::{
     ReadContext,
@@ -18,18 +19,34 @@ use crate::use_case::interface::{
     StatisticsUseCaseObserver,
     TileUseCaseObserver,
 };
+use crate::service::telemetry::window::RollingCounter;
+
+use chrono::Utc;
 
 
 pub struct ReadCounter {
     pub count: u32,
+    window: RollingCounter,
 }
 
 impl ReadCounter {
     pub fn new(_config: &ModuleConfig) -> Result<ReadCounter, InvalidConfigError> {
         Ok(
-            ReadCounter { count: 0 }
+            ReadCounter {
+                count: 0,
+                window: RollingCounter::new(),
+            }
         )
     }
+
+    pub fn count_in_window(&self, window: &TimeWindow) -> u64 {
+        self.window.sum(&Utc::now(), window)
+    }
+
+    pub fn reset(&mut self) -> () {
+        self.count = 0;
+        self.window.reset();
+    }
 }
 
 impl ReadRequestObserver for ReadCounter {
@@ -41,19 +58,33 @@ impl ReadRequestObserver for ReadCounter {
         _read_func_name: &'static str,
     ) -> () {
         self.count += 1;
+        self.window.add(&Utc::now(), 1);
     }
 }
 
 pub struct HandleCounter {
     pub count: u32,
+    window: RollingCounter,
 }
 
 impl HandleCounter {
     pub fn new(_config: &ModuleConfig) -> Result<HandleCounter, InvalidConfigError> {
         Ok(
-            HandleCounter { count: 0 }
+            HandleCounter {
+                count: 0,
+                window: RollingCounter::new(),
+            }
         )
     }
+
+    pub fn count_in_window(&self, window: &TimeWindow) -> u64 {
+        self.window.sum(&Utc::now(), window)
+    }
+
+    pub fn reset(&mut self) -> () {
+        self.count = 0;
+        self.window.reset();
+    }
 }
 
 impl DescriptionUseCaseObserver for HandleCounter {
@@ -64,6 +95,7 @@ impl DescriptionUseCaseObserver for HandleCounter {
         _handler_name: &'static str,
     ) -> () {
         self.count += 1;
+        self.window.add(&Utc::now(), 1);
     }
 }
 
@@ -75,6 +107,7 @@ impl StatisticsUseCaseObserver for HandleCounter {
         _handler_name: &'static str,
     ) -> () {
         self.count += 1;
+        self.window.add(&Utc::now(), 1);
     }
 }
 
@@ -87,19 +120,33 @@ impl TileUseCaseObserver for HandleCounter {
         _handler_name: &'static str,
     ) -> () {
         self.count += 1;
+        self.window.add(&Utc::now(), 1);
     }
 }
 
 pub struct WriteCounter {
     pub count: u32,
+    window: RollingCounter,
 }
 
 impl WriteCounter {
     pub fn new(_config: &ModuleConfig) -> Result<WriteCounter, InvalidConfigError> {
         Ok(
-            WriteCounter { count: 0 }
+            WriteCounter {
+                count: 0,
+                window: RollingCounter::new(),
+            }
         )
     }
+
+    pub fn count_in_window(&self, window: &TimeWindow) -> u64 {
+        self.window.sum(&Utc::now(), window)
+    }
+
+    pub fn reset(&mut self) -> () {
+        self.count = 0;
+        self.window.reset();
+    }
 }
 
 impl WriteResponseObserver for WriteCounter {
@@ -113,5 +160,6 @@ impl WriteResponseObserver for WriteCounter {
         _request: &SlippyRequest,
     ) -> () {
         self.count += 1;
+        self.window.add(&Utc::now(), 1);
     }
 }
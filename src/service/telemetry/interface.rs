@@ -1,3 +1,4 @@
+use crate::schema::telemetry::window::TimeWindow;
 use crate::schema::tile::age::TileAge;
 use crate::schema::tile::identity::LayerName;
 use crate::schema::tile::source::TileSource;
@@ -13,6 +14,7 @@ use http::status::StatusCode;
 use mockall::{automock, mock, predicate::*};
 
 use std::ops::Range;
+use std::option::Option;
 use std::vec::Vec;
 
 
@@ -39,6 +41,12 @@ pub trait ResponseMetrics {
     fn tally_tile_response_duration_by_zoom_level(&self, zoom: u32) -> u64;
 
     fn count_response_by_layer_and_status_code(&self, layer: &LayerName, status_code: &StatusCode) -> u64;
+
+    fn count_response_by_status_code_in_window(&self, status_code: &StatusCode, window: &TimeWindow) -> u64;
+
+    fn count_total_tile_response_in_window(&self, window: &TimeWindow) -> u64;
+
+    fn tally_total_tile_response_duration_in_window(&self, window: &TimeWindow) -> u64;
 }
 
 #[cfg_attr(test, automock)]
@@ -50,6 +58,31 @@ pub trait TileHandlingMetrics {
     fn count_handled_tile_by_source_and_age(&self, source: &TileSource, age: &TileAge) -> u64;
 
     fn tally_tile_handle_duration_by_source_and_age(&self, source: &TileSource, age: &TileAge) -> u64;
+
+    fn count_handled_tile_by_source_and_age_in_window(&self, source: &TileSource, age: &TileAge, window: &TimeWindow) -> u64;
+}
+
+// Observers are shared by every thread of an Apache child
+pub trait TelemetryObserver: Send {
+    fn read_request_observer(&mut self) -> Option<&mut dyn ReadRequestObserver> {
+        None
+    }
+
+    fn description_use_case_observer(&mut self) -> Option<&mut dyn DescriptionUseCaseObserver> {
+        None
+    }
+
+    fn statistics_use_case_observer(&mut self) -> Option<&mut dyn StatisticsUseCaseObserver> {
+        None
+    }
+
+    fn tile_use_case_observer(&mut self) -> Option<&mut dyn TileUseCaseObserver> {
+        None
+    }
+
+    fn write_response_observer(&mut self) -> Option<&mut dyn WriteResponseObserver> {
+        None
+    }
 }
 
 pub trait TelemetryInventory {
@@ -59,15 +92,17 @@ pub trait TelemetryInventory {
     fn tile_handling_metrics(&self) -> &dyn TileHandlingMetrics;
     // TODO: add a method that returns the concrete type name
 
-    fn read_request_observers(&mut self) -> [&mut dyn ReadRequestObserver; 2];
+    fn read_request_observers(&mut self) -> Vec<&mut dyn ReadRequestObserver>;
+
+    fn description_use_case_observers(&mut self) -> Vec<&mut dyn DescriptionUseCaseObserver>;
 
-    fn description_use_case_observers(&mut self) -> [&mut dyn DescriptionUseCaseObserver; 2];
+    fn statistics_use_case_observers(&mut self) -> Vec<&mut dyn StatisticsUseCaseObserver>;
 
-    fn statistics_use_case_observers(&mut self) -> [&mut dyn StatisticsUseCaseObserver; 2];
+    fn tile_use_case_observers(&mut self) -> Vec<&mut dyn TileUseCaseObserver>;
 
-    fn tile_use_case_observers(&mut self) -> [&mut dyn TileUseCaseObserver; 2];
+    fn write_response_observers(&mut self) -> Vec<&mut dyn WriteResponseObserver>;
 
-    fn write_response_observers(&mut self) -> [&mut dyn WriteResponseObserver; 4];
+    fn reset_metrics(&mut self) -> ();
 }
 
 
@@ -124,6 +159,12 @@ pub mod test_utils {
         fn tally_tile_response_duration_by_zoom_level(&self, _zoom: u32) -> u64 { 0 }
 
         fn count_response_by_layer_and_status_code(&self, _layer: &LayerName, _status_code: &StatusCode) -> u64 { 0 }
+
+        fn count_response_by_status_code_in_window(&self, _status_code: &StatusCode, _window: &TimeWindow) -> u64 { 0 }
+
+        fn count_total_tile_response_in_window(&self, _window: &TimeWindow) -> u64 { 0 }
+
+        fn tally_total_tile_response_duration_in_window(&self, _window: &TimeWindow) -> u64 { 0 }
     }
 
     pub struct ZeroTileHandlingMetrics { }
@@ -146,6 +187,8 @@ pub mod test_utils {
         fn count_handled_tile_by_source_and_age(&self, _source: &TileSource, _age: &TileAge) -> u64 { 0 }
 
         fn tally_tile_handle_duration_by_source_and_age(&self, _source: &TileSource, _age: &TileAge) -> u64 { 0 }
+
+        fn count_handled_tile_by_source_and_age_in_window(&self, _source: &TileSource, _age: &TileAge, _window: &TimeWindow) -> u64 { 0 }
     }
 
     pub struct NoOpZeroTelemetryInventory {
@@ -199,29 +242,32 @@ pub mod test_utils {
             &self.tile_handling_metrics
         }
 
-        fn read_request_observers(&mut self) -> [&mut dyn ReadRequestObserver; 2] {
-            [&mut self.read_observer_0, &mut self.read_observer_1]
+        fn read_request_observers(&mut self) -> Vec<&mut dyn ReadRequestObserver> {
+            vec![&mut self.read_observer_0, &mut self.read_observer_1]
         }
 
-        fn description_use_case_observers(&mut self) -> [&mut dyn DescriptionUseCaseObserver; 2] {
-            [&mut self.description_use_case_observer_0, &mut self.description_use_case_observer_1]
+        fn description_use_case_observers(&mut self) -> Vec<&mut dyn DescriptionUseCaseObserver> {
+            vec![&mut self.description_use_case_observer_0, &mut self.description_use_case_observer_1]
         }
 
-        fn statistics_use_case_observers(&mut self) -> [&mut dyn StatisticsUseCaseObserver; 2] {
-            [&mut self.statistics_use_case_observer_0, &mut self.statistics_use_case_observer_1]
+        fn statistics_use_case_observers(&mut self) -> Vec<&mut dyn StatisticsUseCaseObserver> {
+            vec![&mut self.statistics_use_case_observer_0, &mut self.statistics_use_case_observer_1]
         }
 
-        fn tile_use_case_observers(&mut self) -> [&mut dyn TileUseCaseObserver; 2] {
-            [&mut self.tile_use_case_observer_0, &mut self.tile_use_case_observer_1]
+        fn tile_use_case_observers(&mut self) -> Vec<&mut dyn TileUseCaseObserver> {
+            vec![&mut self.tile_use_case_observer_0, &mut self.tile_use_case_observer_1]
         }
 
-        fn write_response_observers(&mut self) -> [&mut dyn WriteResponseObserver; 4] {
-            [
+        fn write_response_observers(&mut self) -> Vec<&mut dyn WriteResponseObserver> {
+            vec![
                 &mut self.write_observer_0,
                 &mut self.write_observer_1,
                 &mut self.write_observer_2,
                 &mut self.write_observer_3,
             ]
         }
+
+        fn reset_metrics(&mut self) -> () {
+        }
     }
 }
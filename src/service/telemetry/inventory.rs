@@ -2,11 +2,12 @@ use crate::schema::apache2::config::ModuleConfig;
 use crate::schema::apache2::error::InvalidConfigError;
 use crate::adapter::slippy::interface::{ReadRequestObserver, WriteResponseObserver,};
 use crate::service::telemetry::interface::{
-    ResponseMetrics, TelemetryInventory, TileHandlingMetrics,
+    ResponseMetrics, TelemetryInventory, TelemetryObserver, TileHandlingMetrics,
 };
 use crate::service::telemetry::counters::{
     HandleCounter, ReadCounter, WriteCounter,
 };
+use crate::service::telemetry::registry::ObserverRegistry;
 use crate::service::telemetry::response::ResponseAnalysis;
 use crate::service::telemetry::tile_handling::TileHandlingAnalysis;
 use crate::service::telemetry::transaction::TransactionTrace;
@@ -16,7 +17,10 @@ use crate::use_case::interface::{
     TileUseCaseObserver,
 };
 
+use std::boxed::Box;
+use std::mem;
 use std::result::Result;
+use std::vec::Vec;
 
 
 pub struct TelemetryState {
@@ -26,6 +30,7 @@ pub struct TelemetryState {
     read_counter: ReadCounter,
     handle_counter: HandleCounter,
     write_counter: WriteCounter,
+    registry: ObserverRegistry,
 }
 
 impl TelemetryState {
@@ -38,9 +43,25 @@ impl TelemetryState {
                 read_counter: ReadCounter::new(config)?,
                 handle_counter: HandleCounter::new(config)?,
                 write_counter: WriteCounter::new(config)?,
+                registry: ObserverRegistry::new(),
             }
         )
     }
+
+    pub fn register_observer(
+        &mut self,
+        observer: Box<dyn TelemetryObserver>,
+    ) -> () {
+        self.registry.register(observer);
+    }
+
+    // Registered observers aren't built from the config, so they move to the state built for a reload
+    pub fn adopt_observers(
+        &mut self,
+        previous: &mut TelemetryState,
+    ) -> () {
+        mem::swap(&mut self.registry, &mut previous.registry);
+    }
 }
 
 impl TelemetryInventory for TelemetryState {
@@ -52,29 +73,60 @@ impl TelemetryInventory for TelemetryState {
         &self.tile_handling_analysis
     }
 
-    fn read_request_observers(&mut self) -> [&mut dyn ReadRequestObserver; 2] {
-        [&mut self.trans_trace, &mut self.read_counter]
+    // The built-in observers are called first, followed by the registered observers in registration order
+    fn read_request_observers(&mut self) -> Vec<&mut dyn ReadRequestObserver> {
+        let mut observers: Vec<&mut dyn ReadRequestObserver> = vec![
+            &mut self.trans_trace,
+            &mut self.read_counter,
+        ];
+        observers.extend(self.registry.read_request_observers());
+        return observers;
     }
 
-    fn description_use_case_observers(&mut self) -> [&mut dyn DescriptionUseCaseObserver; 2] {
-        [&mut self.trans_trace, &mut self.handle_counter]
+    fn description_use_case_observers(&mut self) -> Vec<&mut dyn DescriptionUseCaseObserver> {
+        let mut observers: Vec<&mut dyn DescriptionUseCaseObserver> = vec![
+            &mut self.trans_trace,
+            &mut self.handle_counter,
+        ];
+        observers.extend(self.registry.description_use_case_observers());
+        return observers;
     }
 
-    fn statistics_use_case_observers(&mut self) -> [&mut dyn StatisticsUseCaseObserver; 2] {
-        [&mut self.trans_trace, &mut self.handle_counter]
+    fn statistics_use_case_observers(&mut self) -> Vec<&mut dyn StatisticsUseCaseObserver> {
+        let mut observers: Vec<&mut dyn StatisticsUseCaseObserver> = vec![
+            &mut self.trans_trace,
+            &mut self.handle_counter,
+        ];
+        observers.extend(self.registry.statistics_use_case_observers());
+        return observers;
     }
 
-    fn tile_use_case_observers(&mut self) -> [&mut dyn TileUseCaseObserver; 2] {
-        [&mut self.trans_trace, &mut self.handle_counter]
+    fn tile_use_case_observers(&mut self) -> Vec<&mut dyn TileUseCaseObserver> {
+        let mut observers: Vec<&mut dyn TileUseCaseObserver> = vec![
+            &mut self.trans_trace,
+            &mut self.handle_counter,
+        ];
+        observers.extend(self.registry.tile_use_case_observers());
+        return observers;
     }
 
-    fn write_response_observers(&mut self) -> [&mut dyn WriteResponseObserver; 4] {
-        [
+    fn write_response_observers(&mut self) -> Vec<&mut dyn WriteResponseObserver> {
+        let mut observers: Vec<&mut dyn WriteResponseObserver> = vec![
             &mut self.trans_trace,
             &mut self.response_analysis,
             &mut self.tile_handling_analysis,
             &mut self.write_counter,
-        ]
+        ];
+        observers.extend(self.registry.write_response_observers());
+        return observers;
+    }
+
+    fn reset_metrics(&mut self) -> () {
+        self.response_analysis.reset();
+        self.tile_handling_analysis.reset();
+        self.read_counter.reset();
+        self.handle_counter.reset();
+        self.write_counter.reset();
     }
 }
 
@@ -93,3 +145,27 @@ impl TelemetryState {
         &self.write_counter
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::telemetry::registry::test_utils::RecordingObserver;
+
+    use std::error::Error as StdError;
+    use std::sync::{Arc, Mutex,};
+
+    #[test]
+    fn test_registered_observers_follow_built_in_observers() -> Result<(), Box<dyn StdError>> {
+        let module_config = ModuleConfig::new();
+        let mut telemetry = TelemetryState::new(&module_config)?;
+        assert_eq!(4, telemetry.write_response_observers().len(), "Incorrect number of built-in write observers");
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        telemetry.register_observer(Box::new(RecordingObserver::new("audit", &calls)));
+        telemetry.register_observer(Box::new(RecordingObserver::new("access_log", &calls)));
+        assert_eq!(4, telemetry.read_request_observers().len(), "Failed to append registered read observers");
+        assert_eq!(2, telemetry.description_use_case_observers().len(), "Registered observer was added to the wrong phase");
+        assert_eq!(6, telemetry.write_response_observers().len(), "Failed to append registered write observers");
+        Ok(())
+    }
+}
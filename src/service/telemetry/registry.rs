@@ -0,0 +1,193 @@
+use crate::adapter::slippy::interface::{ReadRequestObserver, WriteResponseObserver,};
+use crate::service::telemetry::interface::TelemetryObserver;
+use crate::use_case::interface::{
+    DescriptionUseCaseObserver,
+    StatisticsUseCaseObserver,
+    TileUseCaseObserver,
+};
+
+use std::boxed::Box;
+use std::vec::Vec;
+
+
+pub struct ObserverRegistry {
+    observers: Vec<Box<dyn TelemetryObserver>>,
+}
+
+impl ObserverRegistry {
+    pub fn new() -> ObserverRegistry {
+        ObserverRegistry {
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        observer: Box<dyn TelemetryObserver>,
+    ) -> () {
+        self.observers.push(observer);
+    }
+
+    pub fn len(&self) -> usize {
+        self.observers.len()
+    }
+
+    // Observers are always returned in the order they were registered
+    pub fn read_request_observers(&mut self) -> Vec<&mut dyn ReadRequestObserver> {
+        self.observers.iter_mut().filter_map(|observer| observer.read_request_observer()).collect()
+    }
+
+    pub fn description_use_case_observers(&mut self) -> Vec<&mut dyn DescriptionUseCaseObserver> {
+        self.observers.iter_mut().filter_map(|observer| observer.description_use_case_observer()).collect()
+    }
+
+    pub fn statistics_use_case_observers(&mut self) -> Vec<&mut dyn StatisticsUseCaseObserver> {
+        self.observers.iter_mut().filter_map(|observer| observer.statistics_use_case_observer()).collect()
+    }
+
+    pub fn tile_use_case_observers(&mut self) -> Vec<&mut dyn TileUseCaseObserver> {
+        self.observers.iter_mut().filter_map(|observer| observer.tile_use_case_observer()).collect()
+    }
+
+    pub fn write_response_observers(&mut self) -> Vec<&mut dyn WriteResponseObserver> {
+        self.observers.iter_mut().filter_map(|observer| observer.write_response_observer()).collect()
+    }
+}
+
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::schema::handler::error::HandleError;
+    use crate::schema::http::request::HttpRequest;
+    use crate::schema::http::response::HttpResponse;
+    use crate::schema::slippy::error::{ReadError, WriteError,};
+    use crate::schema::slippy::request::{Header, SlippyRequest,};
+    use crate::schema::slippy::response::SlippyResponse;
+    use crate::io::communication::interface::HttpResponseWriter;
+    use crate::adapter::slippy::interface::{ReadContext, WriteContext,};
+
+    use std::string::String;
+    use std::sync::{Arc, Mutex,};
+
+    pub struct RecordingObserver {
+        pub name: &'static str,
+        pub calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RecordingObserver {
+        pub fn new(
+            name: &'static str,
+            calls: &Arc<Mutex<Vec<String>>>,
+        ) -> RecordingObserver {
+            RecordingObserver {
+                name,
+                calls: calls.clone(),
+            }
+        }
+    }
+
+    impl TelemetryObserver for RecordingObserver {
+        fn read_request_observer(&mut self) -> Option<&mut dyn ReadRequestObserver> {
+            Some(self)
+        }
+
+        fn statistics_use_case_observer(&mut self) -> Option<&mut dyn StatisticsUseCaseObserver> {
+            Some(self)
+        }
+
+        fn write_response_observer(&mut self) -> Option<&mut dyn WriteResponseObserver> {
+            Some(self)
+        }
+    }
+
+    impl ReadRequestObserver for RecordingObserver {
+        fn on_read(
+            &mut self,
+            _context: &ReadContext,
+            _request: &HttpRequest,
+            _read_result: &Result<SlippyRequest, ReadError>,
+            _read_func_name: &'static str,
+        ) -> () {
+            self.calls.lock().unwrap().push(format!("{}.on_read", self.name));
+        }
+    }
+
+    impl StatisticsUseCaseObserver for RecordingObserver {
+        fn on_report_statistics(
+            &mut self,
+            _header: &Header,
+            _handle_result: &Result<SlippyResponse, HandleError>,
+            _handler_name: &'static str,
+        ) -> () {
+            self.calls.lock().unwrap().push(format!("{}.on_report_statistics", self.name));
+        }
+    }
+
+    impl WriteResponseObserver for RecordingObserver {
+        fn on_write(
+            &mut self,
+            _context: &WriteContext,
+            _response: &SlippyResponse,
+            _writer: &dyn HttpResponseWriter,
+            _write_result: &Result<HttpResponse, WriteError>,
+            _write_func_name: &'static str,
+            _request: &SlippyRequest,
+        ) -> () {
+            self.calls.lock().unwrap().push(format!("{}.on_write", self.name));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_utils::RecordingObserver;
+    use crate::core::identifier::generate_id;
+    use crate::schema::handler::error::HandleError;
+    use crate::schema::slippy::request::Header;
+    use crate::schema::tile::identity::LayerName;
+
+    use chrono::Utc;
+
+    use std::error::Error as StdError;
+    use std::sync::{Arc, Mutex,};
+
+    #[test]
+    fn test_observers_called_in_registration_order() -> Result<(), Box<dyn StdError>> {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = ObserverRegistry::new();
+        registry.register(Box::new(RecordingObserver::new("audit", &calls)));
+        registry.register(Box::new(RecordingObserver::new("access_log", &calls)));
+        let header = Header {
+            layer: LayerName::new(),
+            request_id: generate_id(),
+            uri: String::from("/mod_tile_rs"),
+            received_timestamp: Utc::now(),
+        };
+        let handle_result = Err(HandleError::Forbidden(String::from("test")));
+        for observer in registry.statistics_use_case_observers() {
+            observer.on_report_statistics(&header, &handle_result, "test");
+        }
+        assert_eq!(
+            vec![String::from("audit.on_report_statistics"), String::from("access_log.on_report_statistics")],
+            *calls.lock().unwrap(),
+            "Failed to call observers in registration order"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_observers_filtered_by_phase() -> Result<(), Box<dyn StdError>> {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = ObserverRegistry::new();
+        registry.register(Box::new(RecordingObserver::new("audit", &calls)));
+        assert_eq!(1, registry.len(), "Failed to register observer");
+        assert_eq!(1, registry.read_request_observers().len(), "Failed to find read observer");
+        assert_eq!(0, registry.description_use_case_observers().len(), "Found unregistered description observer");
+        assert_eq!(0, registry.tile_use_case_observers().len(), "Found unregistered tile observer");
+        assert_eq!(1, registry.write_response_observers().len(), "Failed to find write observer");
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use crate::schema::http::response::HttpResponse;
 use crate::schema::slippy::error::WriteError;
 use crate::schema::slippy::request;
 use crate::schema::slippy::response;
+use crate::schema::telemetry::window::TimeWindow;
 use crate::schema::tile::identity::LayerName;
 use crate::io::communication::interface::HttpResponseWriter;
 use crate::adapter::slippy::interface::{
@@ -11,8 +12,9 @@ use crate::adapter::slippy::interface::{
     WriteResponseObserver,
 };
 use crate::service::telemetry::interface::ResponseMetrics;
+use crate::service::telemetry::window::RollingCounter;
 
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc,};
 use http::status::StatusCode;
 
 use std::collections::hash_map::HashMap;
@@ -26,6 +28,9 @@ const VALID_ZOOM_RANGE: Range<u32> = 0..(MAX_ZOOM_SERVER as u32 + 1);
 pub struct ResponseAnalysis {
     analysis_by_layer: HashMap<LayerName, LayerResponseAnalysis>,
     status_codes_responded: HashSet<StatusCode>,
+    response_count_by_status_in_window: HashMap<StatusCode, RollingCounter>,
+    tile_response_count_in_window: RollingCounter,
+    tile_response_millis_in_window: RollingCounter,
 }
 
 impl ResponseAnalysis {
@@ -34,10 +39,21 @@ impl ResponseAnalysis {
             ResponseAnalysis {
                 analysis_by_layer: HashMap::new(),
                 status_codes_responded: HashSet::new(),
+                response_count_by_status_in_window: HashMap::new(),
+                tile_response_count_in_window: RollingCounter::new(),
+                tile_response_millis_in_window: RollingCounter::new(),
             }
         )
     }
 
+    pub fn reset(&mut self) -> () {
+        self.analysis_by_layer.clear();
+        self.status_codes_responded.clear();
+        self.response_count_by_status_in_window.clear();
+        self.tile_response_count_in_window.reset();
+        self.tile_response_millis_in_window.reset();
+    }
+
     fn mut_layer<'s>(
         &'s mut self,
         request: &request::SlippyRequest,
@@ -61,7 +77,7 @@ impl ResponseAnalysis {
         &mut self,
         context: &WriteContext,
         request: &request::SlippyRequest,
-        _response: &response::SlippyResponse,
+        response: &response::SlippyResponse,
         response_duration: &Duration,
     ) -> () {
         let zoom_level = match &request.body {
@@ -75,6 +91,10 @@ impl ResponseAnalysis {
         if zoom_level < zoom_limit {
             let counter = &mut(self.mut_layer(request).tile_response_duration_by_zoom[zoom_level]);
             *counter = *counter + *response_duration;
+            self.tile_response_millis_in_window.add(
+                &response.header.after_timestamp,
+                response_duration.num_milliseconds().max(0) as u64,
+            );
         } else {
             warn!(
                 context.host().record,
@@ -89,7 +109,7 @@ impl ResponseAnalysis {
         &mut self,
         context: &WriteContext,
         request: &request::SlippyRequest,
-        _response: &response::SlippyResponse,
+        response: &response::SlippyResponse,
     ) -> () {
         let zoom_level = match &request.body {
             request::BodyVariant::ServeTile(tile_request) => match tile_request {
@@ -103,6 +123,7 @@ impl ResponseAnalysis {
         let zoom_limit = self.mut_layer(request).tile_response_count_by_zoom.len();
         if zoom_level < zoom_limit {
             self.mut_layer(request).tile_response_count_by_zoom[zoom_level] += 1;
+            self.tile_response_count_in_window.add(&response.header.after_timestamp, 1);
         } else {
             warn!(
                 context.host().record,
@@ -116,6 +137,7 @@ impl ResponseAnalysis {
         context: &WriteContext,
         request: &request::SlippyRequest,
         http_response: &HttpResponse,
+        responded_timestamp: &DateTime<Utc>,
     ) -> () {
         self.mut_layer(request)
             .response_count_by_status_and_zoom.entry(http_response.status_code.clone())
@@ -134,6 +156,10 @@ impl ResponseAnalysis {
         let zoom_limit = count_by_zoom.len();
         if zoom_level < zoom_limit {
             count_by_zoom[zoom_level] += 1;
+            self.response_count_by_status_in_window
+                .entry(http_response.status_code.clone())
+                .or_insert(RollingCounter::new())
+                .add(responded_timestamp, 1);
         } else {
             warn!(
                 context.host().record,
@@ -175,7 +201,7 @@ impl WriteResponseObserver for ResponseAnalysis {
             _ => (),
         }
         if let Ok(http_response) = write_result {
-            self.on_http_response_write(context, request, http_response);
+            self.on_http_response_write(context, request, http_response, &response.header.after_timestamp);
         };
     }
 }
@@ -270,6 +296,21 @@ impl ResponseMetrics for ResponseAnalysis {
         return total;
     }
 
+    fn count_response_by_status_code_in_window(&self, status_code: &StatusCode, window: &TimeWindow) -> u64 {
+        match self.response_count_by_status_in_window.get(status_code) {
+            Some(counter) => counter.sum(&Utc::now(), window),
+            None => 0,
+        }
+    }
+
+    fn count_total_tile_response_in_window(&self, window: &TimeWindow) -> u64 {
+        self.tile_response_count_in_window.sum(&Utc::now(), window)
+    }
+
+    fn tally_total_tile_response_duration_in_window(&self, window: &TimeWindow) -> u64 {
+        self.tile_response_millis_in_window.sum(&Utc::now(), window) / 1000
+    }
+
     fn count_response_by_layer_and_status_code(&self, layer: &LayerName, status_code: &StatusCode) -> u64 {
         match self.analysis_by_layer.get(layer) {
             Some(layer_analysis) => {
@@ -348,6 +389,7 @@ mod tests {
                 end: 1,
                 media_type: mime::IMAGE_PNG,
                 encoding: ContentEncoding::NotCompressed,
+                modified_time: None,
             };
             let response = response::SlippyResponse {
                 header: response::Header {
@@ -423,6 +465,32 @@ mod tests {
                 analysis.count_response_by_layer_and_status_code(&layer_name, &StatusCode::OK),
                 "Response count not incremented"
             );
+            assert_eq!(
+                1,
+                analysis.count_response_by_status_code_in_window(&StatusCode::OK, &TimeWindow::OneMinute),
+                "Windowed response count not incremented"
+            );
+            assert_eq!(
+                1,
+                analysis.count_total_tile_response_in_window(&TimeWindow::OneHour),
+                "Windowed tile response count not incremented"
+            );
+            assert_eq!(
+                response_duration.num_seconds() as u64,
+                analysis.tally_total_tile_response_duration_in_window(&TimeWindow::FiveMinutes),
+                "Windowed tile response duration not tallied"
+            );
+            analysis.reset();
+            assert_eq!(
+                0,
+                analysis.count_response_by_status_code_and_zoom_level(&StatusCode::OK, 3),
+                "Response count not reset"
+            );
+            assert_eq!(
+                0,
+                analysis.count_response_by_status_code_in_window(&StatusCode::OK, &TimeWindow::OneHour),
+                "Windowed response count not reset"
+            );
             Ok(())
         })
     }
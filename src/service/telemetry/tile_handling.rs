@@ -4,14 +4,16 @@ use crate::schema::http::response::HttpResponse;
 use crate::schema::slippy::error::WriteError;
 use crate::schema::slippy::request;
 use crate::schema::slippy::response;
+use crate::schema::telemetry::window::TimeWindow;
 use crate::schema::tile::age::TileAge;
 use crate::schema::tile::identity::LayerName;
 use crate::schema::tile::source::TileSource;
 use crate::io::communication::interface::HttpResponseWriter;
 use crate::adapter::slippy::interface::{WriteContext, WriteResponseObserver,};
 use crate::service::telemetry::interface::TileHandlingMetrics;
+use crate::service::telemetry::window::RollingCounter;
 
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc,};
 use enum_iterator::IntoEnumIterator;
 
 use std::collections::hash_map::HashMap;
@@ -20,6 +22,7 @@ use std::marker::Copy;
 
 pub struct TileHandlingAnalysis {
     analysis_by_layer: HashMap<LayerName, TileLayerHandlingAnalysis>,
+    tile_handle_count_in_window: TileMetricTable<RollingCounter>,
 }
 
 impl TileHandlingAnalysis {
@@ -27,10 +30,16 @@ impl TileHandlingAnalysis {
         Ok(
             TileHandlingAnalysis {
                 analysis_by_layer: HashMap::new(),
+                tile_handle_count_in_window: TileMetricTable::new(),
             }
         )
     }
 
+    pub fn reset(&mut self) -> () {
+        self.analysis_by_layer.clear();
+        self.tile_handle_count_in_window = TileMetricTable::new();
+    }
+
     fn mut_layer<'s>(
         &'s mut self,
         request: &request::SlippyRequest,
@@ -45,7 +54,9 @@ impl TileHandlingAnalysis {
         request: &request::SlippyRequest,
         response: &response::TileResponse,
         handle_duration: &Duration,
+        handled_timestamp: &DateTime<Utc>,
     ) -> () {
+        self.tile_handle_count_in_window.update(&response.source, &response.age).add(handled_timestamp, 1);
         self.increase_tile_handle_count(context, request, response);
         self.accrue_tile_handle_duration(context, request, response, handle_duration);
     }
@@ -90,11 +101,12 @@ impl WriteResponseObserver for TileHandlingAnalysis {
     ) -> () {
         let handle_duration = response.header.after_timestamp - response.header.before_timestamp;
         match &response.body {
-            response::BodyVariant::Tile(response) => self.on_handled_tile(
+            response::BodyVariant::Tile(tile_response) => self.on_handled_tile(
                 context,
                 request,
-                response,
+                tile_response,
                 &handle_duration,
+                &response.header.after_timestamp,
             ),
             _ => (),
         }
@@ -147,6 +159,15 @@ impl TileHandlingMetrics for TileHandlingAnalysis {
         }
         return total.num_seconds() as u64;
     }
+
+    fn count_handled_tile_by_source_and_age_in_window(
+        &self,
+        source: &TileSource,
+        age: &TileAge,
+        window: &TimeWindow,
+    ) -> u64 {
+        self.tile_handle_count_in_window.read(source, age).sum(&Utc::now(), window)
+    }
 }
 
 trait DefaultMetric {
@@ -165,6 +186,12 @@ impl DefaultMetric for u64 {
     }
 }
 
+impl DefaultMetric for RollingCounter {
+    fn default() -> Self {
+        RollingCounter::new()
+    }
+}
+
 struct TileMetricTable<T>
 where T: DefaultMetric,
 {
@@ -222,6 +249,15 @@ pub mod test_utils {
         ) -> u64 {
             0
         }
+
+        fn count_handled_tile_by_source_and_age_in_window(
+            &self,
+            _source: &TileSource,
+            _age: &TileAge,
+            _window: &TimeWindow,
+        ) -> u64 {
+            0
+        }
     }
 
     impl WriteResponseObserver for MockNoOpTileHandlingAnalysis {
@@ -298,6 +334,7 @@ mod tests {
                 end: 1,
                 media_type: mime::IMAGE_PNG,
                 encoding: ContentEncoding::NotCompressed,
+                modified_time: None,
             };
             let response = response::SlippyResponse {
                 header: response::Header {
@@ -333,6 +370,30 @@ mod tests {
                 analysis.count_handled_tile_by_source_and_age(&TileSource::Render, &TileAge::Fresh),
                 "Tile handle count not incremented"
             );
+            assert_eq!(
+                1,
+                analysis.count_handled_tile_by_source_and_age_in_window(
+                    &TileSource::Render,
+                    &TileAge::Fresh,
+                    &TimeWindow::FiveMinutes,
+                ),
+                "Windowed tile handle count not incremented"
+            );
+            analysis.reset();
+            assert_eq!(
+                0,
+                analysis.count_handled_tile_by_source_and_age(&TileSource::Render, &TileAge::Fresh),
+                "Tile handle count not reset"
+            );
+            assert_eq!(
+                0,
+                analysis.count_handled_tile_by_source_and_age_in_window(
+                    &TileSource::Render,
+                    &TileAge::Fresh,
+                    &TimeWindow::FiveMinutes,
+                ),
+                "Windowed tile handle count not reset"
+            );
             Ok(())
         })
     }
@@ -376,6 +437,7 @@ mod tests {
                 end: 1,
                 media_type: mime::IMAGE_PNG,
                 encoding: ContentEncoding::NotCompressed,
+                modified_time: None,
             };
             let response = response::SlippyResponse {
                 header: response::Header {
@@ -466,6 +528,7 @@ mod tests {
                         end: 1,
                         media_type: mime::IMAGE_PNG,
                         encoding: ContentEncoding::NotCompressed,
+                        modified_time: None,
                     };
                     let response = response::SlippyResponse {
                         header: response::Header {
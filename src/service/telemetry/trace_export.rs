@@ -0,0 +1,287 @@
+use crate::schema::apache2::config::{TelemetryConfig, TraceExportFormat,};
+use crate::schema::apache2::error::InvalidConfigError;
+use crate::schema::telemetry::span::{encode_hex, Span, SpanKind, SpanStatus,};
+
+use chrono::{DateTime, Utc,};
+use serde_json::{json, Value,};
+
+use std::fs::OpenOptions;
+use std::io::{self, Write,};
+use std::option::Option;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::result::Result;
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+
+const SERVICE_NAME: &str = "mod_tile_rs";
+const FILE_SCHEME: &str = "file://";
+const UNIX_SCHEME: &str = "unix://";
+// Exporting happens on the request thread, so a stalled collector must not hold up the response
+const EXPORT_WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+enum TraceSink {
+    File(PathBuf),
+    Socket {
+        path: PathBuf,
+        stream: Option<UnixStream>,
+    },
+}
+
+pub struct TraceExporter {
+    format: TraceExportFormat,
+    sink: TraceSink,
+}
+
+impl TraceExporter {
+    pub fn new(config: &TelemetryConfig) -> Result<Option<TraceExporter>, InvalidConfigError> {
+        let uri = match &config.trace_export_uri {
+            Some(uri) => uri,
+            None => return Ok(None),
+        };
+        let sink = if let Some(path) = uri.strip_prefix(FILE_SCHEME) {
+            TraceSink::File(PathBuf::from(path))
+        } else if let Some(path) = uri.strip_prefix(UNIX_SCHEME) {
+            TraceSink::Socket {
+                path: PathBuf::from(path),
+                stream: None,
+            }
+        } else {
+            return Err(
+                InvalidConfigError {
+                    entry: String::from("trace_export_uri"),
+                    reason: format!("URI {} does not begin with {} or {}", uri, FILE_SCHEME, UNIX_SCHEME),
+                }
+            );
+        };
+        Ok(
+            Some(
+                TraceExporter {
+                    format: config.trace_export_format,
+                    sink,
+                }
+            )
+        )
+    }
+
+    pub fn export(
+        &mut self,
+        spans: &[Span],
+    ) -> Result<(), io::Error> {
+        let document = match self.format {
+            TraceExportFormat::OtlpJson => to_otlp_json(spans),
+            TraceExportFormat::ZipkinJson => to_zipkin_json(spans),
+        };
+        let mut line = document.to_string();
+        line.push('\n');
+        match &mut self.sink {
+            TraceSink::File(path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                file.write_all(line.as_bytes())?;
+            },
+            TraceSink::Socket { path, stream } => {
+                if stream.is_none() {
+                    let connected = UnixStream::connect(&path)?;
+                    connected.set_write_timeout(Some(EXPORT_WRITE_TIMEOUT))?;
+                    *stream = Some(connected);
+                }
+                let write_result = stream.as_mut().unwrap().write_all(line.as_bytes());
+                if write_result.is_err() {
+                    // Drop the connection so the next export reconnects to the collector
+                    *stream = None;
+                }
+                write_result?;
+            },
+        };
+        return Ok(());
+    }
+}
+
+fn to_unix_nanos(timestamp: &DateTime<Utc>) -> i64 {
+    timestamp.timestamp_nanos()
+}
+
+fn to_unix_micros(timestamp: &DateTime<Utc>) -> i64 {
+    timestamp.timestamp_nanos() / 1000
+}
+
+pub fn to_otlp_json(spans: &[Span]) -> Value {
+    let otlp_spans: Vec<Value> = spans.iter().map(|span| {
+        let mut otlp_span = json!({
+            "traceId": encode_hex(&span.trace_id),
+            "spanId": encode_hex(&span.span_id),
+            "name": span.name,
+            "kind": match span.kind {
+                SpanKind::Internal => 1,
+                SpanKind::Server => 2,
+            },
+            // OTLP/JSON encodes 64 bit integers as strings
+            "startTimeUnixNano": to_unix_nanos(&span.start_time).to_string(),
+            "endTimeUnixNano": to_unix_nanos(&span.end_time).to_string(),
+            "attributes": span.attributes.iter().map(|(key, value)| {
+                json!({ "key": key, "value": { "stringValue": value } })
+            }).collect::<Vec<Value>>(),
+            "status": {
+                "code": match span.status {
+                    SpanStatus::Ok => 1,
+                    SpanStatus::Error => 2,
+                },
+            },
+        });
+        if let Some(parent_span_id) = &span.parent_span_id {
+            otlp_span["parentSpanId"] = json!(encode_hex(parent_span_id));
+        }
+        otlp_span
+    }).collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": SERVICE_NAME } },
+                ],
+            },
+            "scopeSpans": [{
+                "scope": { "name": SERVICE_NAME },
+                "spans": otlp_spans,
+            }],
+        }],
+    })
+}
+
+pub fn to_zipkin_json(spans: &[Span]) -> Value {
+    let zipkin_spans: Vec<Value> = spans.iter().map(|span| {
+        let mut tags = serde_json::Map::new();
+        for (key, value) in &span.attributes {
+            tags.insert(key.to_string(), json!(value));
+        }
+        if let SpanStatus::Error = span.status {
+            tags.insert(String::from("error"), json!("true"));
+        }
+        let start_micros = to_unix_micros(&span.start_time);
+        let mut zipkin_span = json!({
+            "traceId": encode_hex(&span.trace_id),
+            "id": encode_hex(&span.span_id),
+            "name": span.name,
+            "timestamp": start_micros,
+            "duration": (to_unix_micros(&span.end_time) - start_micros).max(1),
+            "localEndpoint": { "serviceName": SERVICE_NAME },
+            "tags": tags,
+        });
+        if let SpanKind::Server = span.kind {
+            zipkin_span["kind"] = json!("SERVER");
+        }
+        if let Some(parent_span_id) = &span.parent_span_id {
+            zipkin_span["parentId"] = json!(encode_hex(parent_span_id));
+        }
+        zipkin_span
+    }).collect();
+    Value::Array(zipkin_spans)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+
+    fn make_spans() -> Vec<Span> {
+        let start_time = Utc.timestamp_millis(1_600_000_000_000);
+        let end_time = Utc.timestamp_millis(1_600_000_000_250);
+        vec![
+            Span {
+                trace_id: [0x4b; 16],
+                span_id: [0x01; 8],
+                parent_span_id: Some([0x0f; 8]),
+                name: String::from("handle_request"),
+                kind: SpanKind::Server,
+                start_time,
+                end_time,
+                status: SpanStatus::Ok,
+                attributes: vec![("http.target", String::from("/osm/1/2/3.png"))],
+            },
+            Span {
+                trace_id: [0x4b; 16],
+                span_id: [0x02; 8],
+                parent_span_id: Some([0x01; 8]),
+                name: String::from("read_request"),
+                kind: SpanKind::Internal,
+                start_time,
+                end_time,
+                status: SpanStatus::Error,
+                attributes: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_otlp_json_layout() -> Result<(), Box<dyn StdError>> {
+        let document = to_otlp_json(&make_spans());
+        let spans = &document["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(2, spans.as_array().unwrap().len(), "Failed to export all spans");
+        assert_eq!("4b4b4b4b4b4b4b4b4b4b4b4b4b4b4b4b", spans[0]["traceId"], "Failed to export traceId");
+        assert_eq!("0f0f0f0f0f0f0f0f", spans[0]["parentSpanId"], "Failed to export parentSpanId");
+        assert_eq!("1600000000000000000", spans[0]["startTimeUnixNano"], "Failed to export start time");
+        assert_eq!("1600000000250000000", spans[0]["endTimeUnixNano"], "Failed to export end time");
+        assert_eq!(2, spans[0]["kind"], "Failed to export server kind");
+        assert_eq!("http.target", spans[0]["attributes"][0]["key"], "Failed to export attribute key");
+        assert_eq!(2, spans[1]["status"]["code"], "Failed to export error status");
+        Ok(())
+    }
+
+    #[test]
+    fn test_zipkin_json_layout() -> Result<(), Box<dyn StdError>> {
+        let document = to_zipkin_json(&make_spans());
+        assert_eq!("0101010101010101", document[0]["id"], "Failed to export id");
+        assert_eq!("SERVER", document[0]["kind"], "Failed to export server kind");
+        assert_eq!(1_600_000_000_000_000i64, document[0]["timestamp"], "Failed to export timestamp");
+        assert_eq!(250_000, document[0]["duration"], "Failed to export duration");
+        assert_eq!("/osm/1/2/3.png", document[0]["tags"]["http.target"], "Failed to export tag");
+        assert_eq!("0101010101010101", document[1]["parentId"], "Failed to export parentId");
+        assert_eq!("true", document[1]["tags"]["error"], "Failed to export error tag");
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_appends_to_file() -> Result<(), Box<dyn StdError>> {
+        let temp_file = mktemp::Temp::new_file()?;
+        let mut config = TelemetryConfig::new();
+        config.trace_export_uri = Some(format!("{}{}", FILE_SCHEME, temp_file.to_path_buf().display()));
+        let mut exporter = TraceExporter::new(&config)?.expect("Failed to create exporter");
+        exporter.export(&make_spans())?;
+        exporter.export(&make_spans())?;
+        let contents = std::fs::read_to_string(temp_file.to_path_buf())?;
+        assert_eq!(2, contents.lines().count(), "Failed to append one line per export");
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_to_stalled_collector_times_out() -> Result<(), Box<dyn StdError>> {
+        let socket_dir = mktemp::Temp::new_dir()?;
+        let socket_path = socket_dir.to_path_buf().join("collector.sock");
+        // The collector accepts the connection but never reads from it
+        let _listener = std::os::unix::net::UnixListener::bind(&socket_path)?;
+        let mut config = TelemetryConfig::new();
+        config.trace_export_uri = Some(format!("{}{}", UNIX_SCHEME, socket_path.display()));
+        let mut exporter = TraceExporter::new(&config)?.expect("Failed to create exporter");
+        let start = std::time::Instant::now();
+        let mut export_result = Ok(());
+        while export_result.is_ok() && start.elapsed() < Duration::from_secs(30) {
+            export_result = exporter.export(&make_spans());
+        }
+        assert!(export_result.is_err(), "Failed to time out on a stalled collector");
+        assert!(start.elapsed() < Duration::from_secs(30), "Export blocked on a stalled collector");
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_export_uri() -> Result<(), Box<dyn StdError>> {
+        let mut config = TelemetryConfig::new();
+        config.trace_export_uri = Some(String::from("http://localhost:4318/v1/traces"));
+        assert!(TraceExporter::new(&config).is_err(), "Unsupported export URI was accepted");
+        Ok(())
+    }
+}
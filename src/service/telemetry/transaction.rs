@@ -1,3 +1,4 @@
+use crate::core::identifier::generate_id;
 use crate::schema::apache2::config::ModuleConfig;
 use crate::schema::apache2::error::InvalidConfigError;
 use crate::schema::handler::error::HandleError;
@@ -5,7 +6,10 @@ use crate::schema::http::request::HttpRequest;
 use crate::schema::http::response::HttpResponse;
 use crate::schema::slippy::error::{ReadError, WriteError,};
 use crate::schema::slippy::request::{Header, ServeTileRequest, SlippyRequest,};
-use crate::schema::slippy::response::SlippyResponse;
+use crate::schema::slippy::response::{BodyVariant, SlippyResponse,};
+use crate::schema::telemetry::span::{
+    Span, SpanId, SpanKind, SpanStatus, TraceContext, TraceId, TRACE_PARENT_HEADER,
+};
 use crate::io::communication::interface::HttpResponseWriter;
 use crate::adapter::slippy::interface::{
     ReadContext,
@@ -13,74 +17,424 @@ use crate::adapter::slippy::interface::{
     WriteContext,
     WriteResponseObserver,
 };
+use crate::service::telemetry::trace_export::TraceExporter;
 use crate::use_case::interface::{
     DescriptionUseCaseObserver,
     StatisticsUseCaseObserver,
     TileUseCaseObserver,
 };
 
+use chrono::{DateTime, Utc,};
+
+use std::collections::HashMap;
+use std::io;
+use std::option::Option;
+use std::string::String;
+use std::vec::Vec;
+
+
+struct PendingTrace {
+    root: Span,
+    children: Vec<Span>,
+    last_phase_end: DateTime<Utc>,
+}
 
-pub struct TransactionTrace {}
+pub struct TransactionTrace {
+    exporter: Option<TraceExporter>,
+    pending_by_request_id: HashMap<i64, PendingTrace>,
+    export_error: Option<io::Error>,
+}
 
 impl TransactionTrace {
-    pub fn new(_config: &ModuleConfig) -> Result<TransactionTrace, InvalidConfigError> {
+    pub fn new(config: &ModuleConfig) -> Result<TransactionTrace, InvalidConfigError> {
         Ok(
-            TransactionTrace { }
+            TransactionTrace {
+                exporter: TraceExporter::new(&config.telemetry)?,
+                pending_by_request_id: HashMap::new(),
+                export_error: None,
+            }
         )
     }
+
+    fn generate_span_id(&mut self) -> SpanId {
+        generate_id().to_be_bytes()
+    }
+
+    fn generate_trace_id(&mut self) -> TraceId {
+        let mut trace_id: TraceId = [0; 16];
+        trace_id[..8].copy_from_slice(&self.generate_span_id());
+        trace_id[8..].copy_from_slice(&self.generate_span_id());
+        trace_id
+    }
+
+    fn make_child_span(
+        &mut self,
+        parent: &Span,
+        name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        status: SpanStatus,
+    ) -> Span {
+        Span {
+            trace_id: parent.trace_id,
+            span_id: self.generate_span_id(),
+            parent_span_id: Some(parent.span_id),
+            name: String::from(name),
+            kind: SpanKind::Internal,
+            start_time,
+            end_time,
+            status,
+            attributes: Vec::new(),
+        }
+    }
+
+    fn record_handle_phase(
+        &mut self,
+        header: &Header,
+        handle_result: &Result<SlippyResponse, HandleError>,
+        handler_name: &'static str,
+    ) -> () {
+        let mut pending = match self.pending_by_request_id.remove(&header.request_id) {
+            Some(pending) => pending,
+            None => return,
+        };
+        let (start_time, end_time, status) = match handle_result {
+            Ok(response) => (response.header.before_timestamp, response.header.after_timestamp, SpanStatus::Ok),
+            Err(_) => (pending.last_phase_end, Utc::now(), SpanStatus::Error),
+        };
+        let mut span = self.make_child_span(&pending.root, "call_handlers", start_time, end_time, status);
+        span.attributes.push(("handler.name", String::from(handler_name)));
+        if let Ok(SlippyResponse { body: BodyVariant::Tile(tile), .. }) = handle_result {
+            span.attributes.push(("tile.source", format!("{:?}", tile.source)));
+            span.attributes.push(("tile.age", format!("{:?}", tile.age)));
+        }
+        if let Err(err) = handle_result {
+            span.attributes.push(("error.message", err.to_string()));
+        }
+        pending.children.push(span);
+        pending.last_phase_end = end_time;
+        // A handle error is still written as a status response, which ends the transaction
+        self.pending_by_request_id.insert(header.request_id, pending);
+    }
+
+    fn finish(
+        &mut self,
+        mut pending: PendingTrace,
+        end_time: DateTime<Utc>,
+        status: SpanStatus,
+    ) -> () {
+        pending.root.end_time = end_time;
+        pending.root.status = status;
+        let mut spans = Vec::with_capacity(pending.children.len() + 1);
+        spans.push(pending.root);
+        spans.append(&mut pending.children);
+        if let Some(exporter) = &mut self.exporter {
+            if let Err(err) = exporter.export(&spans) {
+                self.export_error = Some(err);
+            }
+        }
+    }
 }
 
 impl ReadRequestObserver for TransactionTrace {
     fn on_read(
         &mut self,
-        _context: &ReadContext,
-        _request: &HttpRequest,
-        _read_result: &Result<SlippyRequest, ReadError>,
-        _read_func_name: &'static str,
+        context: &ReadContext,
+        request: &HttpRequest,
+        read_result: &Result<SlippyRequest, ReadError>,
+        read_func_name: &'static str,
     ) -> () {
+        if let Some(err) = self.export_error.take() {
+            warn!(context.host().record, "TransactionTrace::on_read - failed to export spans: {}", err);
+        }
+        if self.exporter.is_none() {
+            return;
+        }
+        if let Err(ReadError::NotMatched(_)) = read_result {
+            // The request is declined for another Apache handler, so it is no transaction of this module
+            return;
+        }
+        let parent_context = request.header(TRACE_PARENT_HEADER).and_then(TraceContext::parse);
+        if let Some(parent) = &parent_context {
+            if !parent.is_sampled() {
+                return;
+            }
+        }
+        let now = Utc::now();
+        let (trace_id, parent_span_id) = match parent_context {
+            Some(parent) => (parent.trace_id, Some(parent.parent_span_id)),
+            None => (self.generate_trace_id(), None),
+        };
+        let mut root = Span {
+            trace_id,
+            span_id: self.generate_span_id(),
+            parent_span_id,
+            name: String::from("handle_request"),
+            kind: SpanKind::Server,
+            start_time: request.received_time,
+            end_time: now,
+            status: SpanStatus::Ok,
+            attributes: Vec::new(),
+        };
+        root.attributes.push(("http.target", String::from(request.uri)));
+        let read_status = match read_result {
+            Ok(_) => SpanStatus::Ok,
+            Err(_) => SpanStatus::Error,
+        };
+        let mut read_span = self.make_child_span(&root, "read_request", request.received_time, now, read_status);
+        read_span.attributes.push(("reader.name", String::from(read_func_name)));
+        let pending = PendingTrace {
+            root,
+            children: vec![read_span],
+            last_phase_end: now,
+        };
+        match read_result {
+            Ok(slippy_request) => {
+                self.pending_by_request_id.insert(slippy_request.header.request_id, pending);
+            },
+            Err(_) => {
+                // The response to a read error is written for a request ID the trace can't know, so it ends here
+                self.finish(pending, now, SpanStatus::Error);
+            },
+        };
     }
 }
 
 impl DescriptionUseCaseObserver for TransactionTrace {
     fn on_describe_layer(
         &mut self,
-        _header: &Header,
-        _handle_result: &Result<SlippyResponse, HandleError>,
-        _handler_name: &'static str,
+        header: &Header,
+        handle_result: &Result<SlippyResponse, HandleError>,
+        handler_name: &'static str,
     ) -> () {
+        self.record_handle_phase(header, handle_result, handler_name);
     }
 }
 
 impl StatisticsUseCaseObserver for TransactionTrace {
     fn on_report_statistics(
         &mut self,
-        _header: &Header,
-        _handle_result: &Result<SlippyResponse, HandleError>,
-        _handler_name: &'static str,
+        header: &Header,
+        handle_result: &Result<SlippyResponse, HandleError>,
+        handler_name: &'static str,
     ) -> () {
+        self.record_handle_phase(header, handle_result, handler_name);
     }
 }
 
 impl TileUseCaseObserver for TransactionTrace {
     fn on_fetch_tile(
         &mut self,
-        _header: &Header,
+        header: &Header,
         _body: &ServeTileRequest,
-        _handle_result: &Result<SlippyResponse, HandleError>,
-        _handler_name: &'static str,
+        handle_result: &Result<SlippyResponse, HandleError>,
+        handler_name: &'static str,
     ) -> () {
+        self.record_handle_phase(header, handle_result, handler_name);
     }
 }
 
 impl WriteResponseObserver for TransactionTrace {
     fn on_write(
         &mut self,
-        _context: &WriteContext,
+        context: &WriteContext,
         _response: &SlippyResponse,
         _writer: &dyn HttpResponseWriter,
-        _write_result: &Result<HttpResponse, WriteError>,
-        _write_func_name: &'static str,
-        _request: &SlippyRequest,
+        write_result: &Result<HttpResponse, WriteError>,
+        write_func_name: &'static str,
+        request: &SlippyRequest,
     ) -> () {
+        let mut pending = match self.pending_by_request_id.remove(&request.header.request_id) {
+            Some(pending) => pending,
+            None => return,
+        };
+        let now = Utc::now();
+        let status = match write_result {
+            Ok(_) => SpanStatus::Ok,
+            Err(_) => SpanStatus::Error,
+        };
+        let mut write_span = self.make_child_span(&pending.root, "write_response", pending.last_phase_end, now, status);
+        write_span.attributes.push(("writer.name", String::from(write_func_name)));
+        if let Ok(response) = write_result {
+            write_span.attributes.push(("http.status_code", response.status_code.as_u16().to_string()));
+            pending.root.attributes.push(("http.status_code", response.status_code.as_u16().to_string()));
+        }
+        pending.children.push(write_span);
+        self.finish(pending, now, status);
+        if let Some(err) = self.export_error.take() {
+            warn!(context.host().record, "TransactionTrace::on_write - failed to export spans: {}", err);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::http::encoding::ContentEncoding;
+    use crate::schema::slippy::request::{self, ServeTileRequestV2,};
+    use crate::schema::slippy::response::{self, TileResponse,};
+    use crate::schema::tile::age::TileAge;
+    use crate::schema::tile::identity::LayerName;
+    use crate::schema::tile::source::TileSource;
+    use crate::schema::tile::tile_ref::TileRef;
+    use crate::io::communication::http_exchange::test_utils::MockWriter;
+    use crate::framework::apache2::context::HostContext;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+    use http::header::HeaderMap;
+    use http::status::StatusCode;
+    use std::boxed::Box;
+    use std::cell::RefCell;
+    use std::error::Error as StdError;
+    use std::ffi::CString;
+
+    fn make_tile_request(request_id: i64) -> SlippyRequest {
+        SlippyRequest {
+            header: Header {
+                layer: LayerName::from("default"),
+                request_id,
+                uri: String::from("/osm/1/2/3.png"),
+                received_timestamp: Utc::now(),
+            },
+            body: request::BodyVariant::ServeTile(
+                ServeTileRequest::V2(
+                    ServeTileRequestV2 {
+                        x: 2,
+                        y: 3,
+                        z: 1,
+                        extension: String::from("png"),
+                        option: None,
+                    }
+                )
+            ),
+        }
+    }
+
+    fn make_tile_response() -> SlippyResponse {
+        SlippyResponse {
+            header: response::Header {
+                mime_type: mime::IMAGE_PNG,
+                before_timestamp: Utc::now(),
+                after_timestamp: Utc::now(),
+            },
+            body: BodyVariant::Tile(
+                TileResponse {
+                    source: TileSource::Cache,
+                    age: TileAge::Fresh,
+                    tile_ref: TileRef {
+                        raw_bytes: RefCell::new(Vec::new()),
+                        begin: 0,
+                        end: 0,
+                        media_type: mime::IMAGE_PNG,
+                        encoding: ContentEncoding::NotCompressed,
+                        modified_time: None,
+                    },
+                }
+            ),
+        }
+    }
+
+    #[test]
+    fn test_spans_exported_after_write() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let uri = CString::new("/osm/1/2/3.png")?;
+            record.uri = uri.into_raw();
+            let temp_file = mktemp::Temp::new_file()?;
+            let mut module_config = ModuleConfig::new();
+            module_config.telemetry.trace_export_uri = Some(format!("file://{}", temp_file.to_path_buf().display()));
+            let mut trace = TransactionTrace::new(&module_config)?;
+
+            let read_result = Ok(make_tile_request(42));
+            let slippy_request = read_result.as_ref().unwrap();
+            let http_request = HttpRequest::new("/osm/1/2/3.png", Utc::now(), record);
+            let read_context = ReadContext {
+                host_context: HostContext::new(&module_config, record),
+            };
+            trace.on_read(&read_context, &http_request, &read_result, "read");
+            assert!(
+                trace.pending_by_request_id.contains_key(&42),
+                "Failed to hold the trace open after reading"
+            );
+
+            let handle_result = Ok(make_tile_response());
+            let response = handle_result.as_ref().unwrap();
+            if let request::BodyVariant::ServeTile(body) = &slippy_request.body {
+                trace.on_fetch_tile(&slippy_request.header, body, &handle_result, "handler");
+            }
+
+            let write_context = WriteContext {
+                host_context: HostContext::new(&module_config, record),
+                request: slippy_request,
+            };
+            let writer = MockWriter::new();
+            let write_result = Ok(
+                HttpResponse {
+                    status_code: StatusCode::OK,
+                    bytes_written: 0,
+                    http_headers: HeaderMap::new(),
+                }
+            );
+            trace.on_write(&write_context, response, &writer, &write_result, "write", slippy_request);
+            assert!(
+                trace.pending_by_request_id.is_empty(),
+                "Failed to close the trace after writing"
+            );
+
+            let contents = std::fs::read_to_string(temp_file.to_path_buf())?;
+            let document: serde_json::Value = serde_json::from_str(contents.trim())?;
+            let spans = document["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap().clone();
+            let names: Vec<&str> = spans.iter().map(|span| span["name"].as_str().unwrap()).collect();
+            assert_eq!(
+                vec!["handle_request", "read_request", "call_handlers", "write_response"],
+                names,
+                "Failed to export a span for each phase"
+            );
+            for child in &spans[1..] {
+                assert_eq!(spans[0]["spanId"], child["parentSpanId"], "Failed to parent phase span to root span");
+                assert_eq!(spans[0]["traceId"], child["traceId"], "Failed to share trace_id with root span");
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_no_trace_kept_for_read_errors() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let uri = CString::new("/mod_tile_rs/reset")?;
+            record.uri = uri.into_raw();
+            let temp_file = mktemp::Temp::new_file()?;
+            let mut module_config = ModuleConfig::new();
+            module_config.telemetry.trace_export_uri = Some(format!("file://{}", temp_file.to_path_buf().display()));
+            let mut trace = TransactionTrace::new(&module_config)?;
+            let http_request = HttpRequest::new("/mod_tile_rs/reset", Utc::now(), record);
+            let read_context = ReadContext {
+                host_context: HostContext::new(&module_config, record),
+            };
+            let not_matched = Err(ReadError::NotMatched(String::from("/index.html")));
+            trace.on_read(&read_context, &http_request, &not_matched, "read");
+            let not_allowed = Err(ReadError::MethodNotAllowed(String::from("GET")));
+            trace.on_read(&read_context, &http_request, &not_allowed, "read");
+            assert!(trace.pending_by_request_id.is_empty(), "Failed to end the traces of the read errors");
+            let contents = std::fs::read_to_string(temp_file.to_path_buf())?;
+            assert_eq!(1, contents.lines().count(), "Incorrect number of exported traces");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_no_spans_kept_when_export_disabled() -> Result<(), Box<dyn StdError>> {
+        with_request_rec(|record| {
+            let uri = CString::new("/osm/1/2/3.png")?;
+            record.uri = uri.into_raw();
+            let module_config = ModuleConfig::new();
+            let mut trace = TransactionTrace::new(&module_config)?;
+            let http_request = HttpRequest::new("/osm/1/2/3.png", Utc::now(), record);
+            let read_context = ReadContext {
+                host_context: HostContext::new(&module_config, record),
+            };
+            trace.on_read(&read_context, &http_request, &Ok(make_tile_request(7)), "read");
+            assert!(trace.pending_by_request_id.is_empty(), "Trace was recorded while export is disabled");
+            Ok(())
+        })
     }
 }
@@ -0,0 +1,106 @@
+use crate::schema::telemetry::window::TimeWindow;
+
+use chrono::{DateTime, Utc,};
+
+use std::marker::Copy;
+
+
+const BUCKET_COUNT: usize = 60;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RollingCounter {
+    buckets: [u64; BUCKET_COUNT],
+    latest_minute: i64,
+}
+
+impl RollingCounter {
+    pub fn new() -> RollingCounter {
+        RollingCounter {
+            buckets: [0; BUCKET_COUNT],
+            latest_minute: 0,
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        timestamp: &DateTime<Utc>,
+        amount: u64,
+    ) -> () {
+        let minute = timestamp.timestamp().div_euclid(60);
+        if minute > self.latest_minute {
+            // Clear the buckets of the minutes that passed without any activity
+            let elapsed = (minute - self.latest_minute).min(BUCKET_COUNT as i64);
+            for offset in 0..elapsed {
+                self.buckets[Self::index(minute - offset)] = 0;
+            }
+            self.latest_minute = minute;
+        } else if minute <= self.latest_minute - BUCKET_COUNT as i64 {
+            return;
+        }
+        self.buckets[Self::index(minute)] += amount;
+    }
+
+    pub fn sum(
+        &self,
+        now: &DateTime<Utc>,
+        window: &TimeWindow,
+    ) -> u64 {
+        let now_minute = now.timestamp().div_euclid(60);
+        let oldest_minute = (now_minute - window.minutes() + 1).max(self.latest_minute - BUCKET_COUNT as i64 + 1);
+        let mut total = 0;
+        // Timestamps ahead of now are still counted so that clock skew between threads does not lose any activity
+        for minute in oldest_minute..=self.latest_minute {
+            total += self.buckets[Self::index(minute)];
+        }
+        return total;
+    }
+
+    pub fn reset(&mut self) -> () {
+        *self = RollingCounter::new();
+    }
+
+    fn index(minute: i64) -> usize {
+        minute.rem_euclid(BUCKET_COUNT as i64) as usize
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone,};
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn test_sum_within_windows() -> Result<(), Box<dyn StdError>> {
+        let start = Utc.timestamp(1_600_000_020, 0);
+        let mut counter = RollingCounter::new();
+        counter.add(&start, 1);
+        counter.add(&(start + Duration::minutes(3)), 2);
+        counter.add(&(start + Duration::minutes(10)), 4);
+        let now = start + Duration::minutes(10);
+        assert_eq!(4, counter.sum(&now, &TimeWindow::OneMinute), "Failed to sum the last minute");
+        assert_eq!(4, counter.sum(&now, &TimeWindow::FiveMinutes), "Failed to sum the last 5 minutes");
+        assert_eq!(7, counter.sum(&now, &TimeWindow::OneHour), "Failed to sum the last hour");
+        let later = start + Duration::minutes(14);
+        assert_eq!(0, counter.sum(&later, &TimeWindow::OneMinute), "Failed to expire the last minute");
+        assert_eq!(4, counter.sum(&later, &TimeWindow::FiveMinutes), "Failed to sum the last 5 minutes");
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_after_an_hour() -> Result<(), Box<dyn StdError>> {
+        let start = Utc.timestamp(1_600_000_020, 0);
+        let mut counter = RollingCounter::new();
+        counter.add(&start, 5);
+        counter.add(&(start + Duration::minutes(61)), 1);
+        let now = start + Duration::minutes(61);
+        assert_eq!(1, counter.sum(&now, &TimeWindow::OneHour), "Failed to expire buckets older than an hour");
+        counter.add(&start, 3);
+        assert_eq!(1, counter.sum(&now, &TimeWindow::OneHour), "Failed to ignore a timestamp older than an hour");
+        counter.reset();
+        assert_eq!(0, counter.sum(&now, &TimeWindow::OneHour), "Failed to reset");
+        Ok(())
+    }
+}
@@ -1,48 +1,61 @@
 use crate::binding::apache2::{
-    APR_BADARG, APR_SUCCESS,
+    APR_BADARG, APR_SUCCESS, OK,
     apr_status_t, request_rec, server_rec,
 };
 use crate::schema::apache2::config::ModuleConfig;
+use crate::schema::apache2::error::InvalidConfigError;
 use crate::schema::apache2::virtual_host::VirtualHost;
+use crate::schema::core::processed::ProcessOutcome;
 use crate::schema::handler::error::HandleError;
 use crate::schema::http::response::HttpResponse;
 use crate::schema::slippy::request::{
-    BodyVariant, Header,
+    BodyVariant, DumpConfigRequest, Header, ResetStatisticsRequest,
     ServeTileRequest, SlippyRequest,
 };
 use crate::schema::slippy::error::{ReadError, WriteError,};
-use crate::schema::slippy::response::SlippyResponse;
+use crate::schema::slippy::response::{self, SlippyResponse,};
+use crate::schema::tile::identity::LayerName;
+use crate::core::identifier::generate_id;
 use crate::core::memory::PoolStored;
 use crate::io::communication::interface::HttpResponseWriter;
 use crate::io::interface::IOContext;
 use crate::framework::apache2::context::HostContext;
-use crate::framework::apache2::config::Loadable;
-use crate::framework::apache2::memory::{ access_pool_object, alloc, retrieve };
+use crate::framework::apache2::config::{apply_directive, validate, ParseError, ServerConfig,};
+use crate::framework::apache2::server_config::find_server_config;
+use crate::framework::apache2::memory::{ access_pool_object, alloc_init, retrieve, retrieve_shared, };
 use crate::framework::apache2::record::ServerRecord;
 use crate::io::communication::state::CommunicationState;
 use crate::use_case::inventory::{HandlerObserverInventory, HandlerState,};
 use crate::adapter::http::reader::read_apache2_request;
-use crate::adapter::slippy::interface::{ReadContext, WriteContext,};
+use crate::adapter::slippy::interface::{MiddlewareFactory, ReadContext, WriteContext,};
 use crate::adapter::slippy::inventory::{SlippyInventory, SlippyObserverInventory,};
+use crate::adapter::slippy::middleware::MiddlewarePipeline;
+use crate::adapter::slippy::status::ErrorStatusMapper;
 use crate::io::storage::state::StorageState;
-use crate::service::interface::ServicesContext;
 use crate::service::rendering::inventory::RenderingState;
+use crate::service::telemetry::access_log::AccessLog;
+use crate::service::telemetry::interface::TelemetryObserver;
 use crate::service::telemetry::inventory::TelemetryState;
+use crate::use_case::configuration::ConfigurationContext;
 use crate::use_case::description::DescriptionContext;
-use crate::use_case::statistics::StatisticsContext;
+use crate::use_case::statistics::{StatisticsContext, StatisticsResetContext,};
 use crate::use_case::tile::TileContext;
 
 use thiserror::Error;
-use chrono::Utc;
+use chrono::{TimeZone, Utc,};
 
 use std::any::type_name;
 use std::boxed::Box;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error as StdError;
-use std::ffi::CString;
+use std::ffi::{CStr, CString,};
 use std::option::Option;
 use std::os::raw::{ c_int, c_void, };
-use std::path::PathBuf;
+use std::path::{Path, PathBuf,};
 use std::result::Result;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError,};
+#[cfg(test)]
 use std::time::Duration;
 
 
@@ -56,14 +69,40 @@ pub enum HandleRequestError {
     Write(#[from] WriteError),
 }
 
+#[derive(Error, Debug)]
+pub enum FindProxyError {
+    #[error("No TileProxy has been allocated for server {0:?}")]
+    NotAllocated(CString),
+    #[error("Copy of the TileProxy could not be built: {0}")]
+    Copy(#[from] InvalidConfigError),
+}
+
+#[derive(Error, Debug)]
+pub enum ReloadError {
+    #[error("Config could not be parsed: {0}")]
+    Parse(#[from] ParseError),
+    #[error("Config failed validation with {} errors", .0.len())]
+    Invalid(Vec<InvalidConfigError>),
+    #[error("State could not be created from the config: {0}")]
+    State(#[from] InvalidConfigError),
+}
+
 pub struct TileProxy {
     config: ModuleConfig,
-    config_file_path: Option<PathBuf>,
+    server_config: ServerConfig,
     comms_state: CommunicationState,
     storage_state: StorageState,
     rendering_state: RenderingState,
-    telemetry_state: TelemetryState,
+    // Shared by the copies on every thread, so statistics and observers cover all of the child's requests
+    telemetry_state: Arc<Mutex<TelemetryState>>,
     handler_state: HandlerState,
+    middleware: MiddlewarePipeline,
+    // Changes on every reload, so the copy each thread handles requests with can tell it is out of date
+    generation: u64,
+}
+
+thread_local! {
+    static THREAD_PROXIES: RefCell<HashMap<CString, TileProxy>> = RefCell::new(HashMap::new());
 }
 
 impl TileProxy {
@@ -101,18 +140,23 @@ impl TileProxy {
         module_config: ModuleConfig,
     ) -> Result<&mut Self, Box<dyn StdError>> {
         info!(record, "TileServer::create - start");
-        let new_server = alloc::<TileProxy>(
+        let value = TileProxy {
+            comms_state: CommunicationState::new(&module_config)?,
+            storage_state: StorageState::new(&module_config)?,
+            rendering_state: RenderingState::new(&module_config)?,
+            telemetry_state: Arc::new(Mutex::new(TelemetryState::new(&module_config)?)),
+            handler_state: HandlerState::new(&module_config)?,
+            middleware: MiddlewarePipeline::new(),
+            config: module_config,
+            server_config: ServerConfig::new(),
+            generation: 0,
+        };
+        let new_server = alloc_init(
             record.get_pool()?,
             &(Self::get_id(record)),
             Some(drop_tile_server),
+            value,
         )?.0;
-        new_server.config = module_config;
-        new_server.config_file_path = None;
-        new_server.comms_state = CommunicationState::new(&new_server.config)?;
-        new_server.storage_state = StorageState::new(&new_server.config)?;
-        new_server.rendering_state = RenderingState::new(&new_server.config)?;
-        new_server.telemetry_state = TelemetryState::new(&new_server.config)?;
-        new_server.handler_state = HandlerState::new(&new_server.config)?;
         info!(record, "TileServer::create - finish");
         return Ok(new_server);
     }
@@ -122,14 +166,96 @@ impl TileProxy {
         file_path: PathBuf,
         server_name: Option<&str>,
     ) -> Result<(), Box<dyn StdError>> {
-        let original_request_timeout = self.config.renderd.render_timeout.clone();
-        let module_config = ModuleConfig::load(file_path.as_path(), server_name)?;
+        let mut server_config = self.server_config.clone();
+        server_config.config_file_path = Some(file_path);
+        self.configure(server_config, server_name)?;
+        return Ok(());
+    }
+
+    // Nothing is replaced until the new config is valid and every state has been built from it,
+    // so a failed reload leaves the previous working state in place
+    pub fn configure(
+        &mut self,
+        server_config: ServerConfig,
+        server_name: Option<&str>,
+    ) -> Result<(), ReloadError> {
+        let mut module_config = server_config.load_config_file(server_name)?;
+        module_config.renderd.render_timeout = self.config.renderd.render_timeout.clone();
+        module_config.renderd.missing_render_timeout = self.config.renderd.missing_render_timeout.clone();
+        // Directives in httpd.conf take precedence over the config file regardless of their order
+        server_config.apply_directives(&mut module_config, server_name)?;
+        let errors = validate(&module_config);
+        if !errors.is_empty() {
+            return Err(ReloadError::Invalid(errors));
+        }
+        let comms_state = CommunicationState::new(&module_config)?;
+        let storage_state = StorageState::new(&module_config)?;
+        let rendering_state = RenderingState::new(&module_config)?;
+        let handler_state = HandlerState::new(&module_config)?;
+        let mut telemetry_state = TelemetryState::new(&module_config)?;
+        let middleware = self.middleware.rebuild(&module_config)?;
+        {
+            // Replaced in place, so the thread copies that share it see the rebuilt telemetry straight away
+            let mut current_telemetry = self.telemetry();
+            telemetry_state.adopt_observers(&mut current_telemetry);
+            *current_telemetry = telemetry_state;
+        }
+        self.comms_state = comms_state;
+        self.storage_state = storage_state;
+        self.rendering_state = rendering_state;
+        self.handler_state = handler_state;
+        self.middleware = middleware;
         self.config = module_config;
-        self.config.renderd.render_timeout = original_request_timeout;
-        self.config_file_path = Some(file_path.clone());
+        self.server_config = server_config;
+        self.generation += 1;
         return Ok(());
     }
 
+    // Apache handles requests on several threads, so each thread handles them with its own copy of the proxy
+    // rather than sharing the one allocated for the server. The copy is built on first use and again after a reload.
+    pub fn with_thread_proxy<F, R>(
+        record: &server_rec,
+        func: F,
+    ) -> Result<R, FindProxyError>
+    where F: FnOnce(&mut TileProxy) -> R {
+        let id = Self::get_id(record);
+        let server_proxy = record.get_process_record().ok()
+            .and_then(|process| unsafe { process.pool.as_ref() })
+            .and_then(|pool| retrieve_shared::<TileProxy>(pool, &id))
+            .ok_or_else(|| FindProxyError::NotAllocated(id.clone()))?;
+        THREAD_PROXIES.with(|proxies| {
+            let mut proxies = proxies.borrow_mut();
+            let is_current = proxies.get(&id)
+                .map(|proxy| proxy.generation == server_proxy.generation)
+                .unwrap_or(false);
+            if !is_current {
+                proxies.insert(id.clone(), server_proxy.copy_for_thread()?);
+            }
+            Ok(func(proxies.get_mut(&id).unwrap()))
+        })
+    }
+
+    // The renderd channels, the outstanding renders and the telemetry are shared with the copy, and each of them
+    // synchronises itself. Storage, rendering and middleware only hold per request state, so each copy has its own.
+    fn copy_for_thread(&self) -> Result<TileProxy, InvalidConfigError> {
+        let mut handler_state = HandlerState::new(&self.config)?;
+        handler_state.tile = self.handler_state.tile.clone();
+        Ok(
+            TileProxy {
+                config: self.config.clone(),
+                server_config: self.server_config.clone(),
+                comms_state: self.comms_state.clone(),
+                storage_state: StorageState::new(&self.config)?,
+                rendering_state: RenderingState::new(&self.config)?,
+                telemetry_state: self.telemetry_state.clone(),
+                handler_state,
+                middleware: self.middleware.rebuild(&self.config)?,
+                generation: self.generation,
+            }
+        )
+    }
+
+    #[cfg(test)]
     pub fn set_render_timeout(
         &mut self,
         timeout: &Duration,
@@ -137,13 +263,69 @@ impl TileProxy {
         self.config.renderd.render_timeout = *timeout;
     }
 
+    pub fn apply_directive(
+        &mut self,
+        name: &str,
+        args: &[&str],
+        server_name: Option<&str>,
+    ) -> Result<(), ParseError> {
+        apply_directive(&mut self.config, name, args, server_name)?;
+        self.server_config.add_directive(name, args);
+        return Ok(());
+    }
+
+    #[cfg(test)]
+    pub fn set_missing_render_timeout(
+        &mut self,
+        timeout: &Duration,
+    ) -> () {
+        self.config.renderd.missing_render_timeout = *timeout;
+    }
+
+    pub fn register_observer(
+        &mut self,
+        observer: Box<dyn TelemetryObserver>,
+    ) -> () {
+        self.telemetry().register_observer(observer);
+    }
+
+    // Observers are called while the lock is held, and a panicking observer must not stop every later request
+    // from being observed
+    fn telemetry(&self) -> MutexGuard<'_, TelemetryState> {
+        return self.telemetry_state.lock().unwrap_or_else(PoisonError::into_inner);
+    }
+
+    pub fn add_middleware(
+        &mut self,
+        factory: Arc<MiddlewareFactory>,
+    ) -> Result<(), InvalidConfigError> {
+        return self.middleware.add(&self.config, factory);
+    }
+
     pub fn initialise(
         &mut self,
         record: &mut server_rec,
     ) -> Result<(), Box<dyn StdError>> {
-        if let Some(original_path) = &self.config_file_path {
-            let copied_path = original_path.clone();
-            self.load_config(copied_path, record.get_host_name())?;
+        // Apache has already merged the main server directives into those of a virtual host
+        let server_config = match find_server_config(record) {
+            Some(merged_config) => merged_config.clone(),
+            None => self.server_config.clone(),
+        };
+        if server_config.config_file_path.is_some() || !server_config.directives.is_empty() {
+            if let Err(why) = self.configure(server_config, record.get_host_name()) {
+                if let ReloadError::Invalid(errors) = &why {
+                    for error in errors {
+                        error!(record, "TileServer::initialise - {}", error);
+                    }
+                }
+                warn!(record, "TileServer::initialise - keeping the previous config because {}", why);
+                return Err(Box::new(why));
+            }
+        }
+        // Registered observers are kept through a reload, so the access log is opened once for the child
+        if let Some(path) = &self.config.telemetry.access_log_path {
+            let access_log = AccessLog::open(Path::new(path))?;
+            self.register_observer(Box::new(access_log));
         }
         return Ok(());
     }
@@ -151,19 +333,60 @@ impl TileProxy {
     pub fn handle_request(
         &mut self,
         record: &mut request_rec,
+    ) -> Result<c_int, HandleRequestError> {
+        // Work around the borrow checker below, but its necessary since request_rec from a foreign C framework
+        let write_record = record as *mut request_rec;
+        let writer: &mut dyn HttpResponseWriter = unsafe { write_record.as_mut().unwrap() };
+        return self.handle_request_with_writer(record, writer);
+    }
+
+    // The response is written through the given writer rather than the request record,
+    // so it can be captured when there is no Apache to send it
+    pub fn handle_request_with_writer(
+        &mut self,
+        record: &mut request_rec,
+        writer: &mut dyn HttpResponseWriter,
     ) -> Result<c_int, HandleRequestError> {
         debug!(record.server, "TileServer::handle_request - start");
-        let request = self.read_request(record)?;
-        let response = self.call_handlers(record, &request)?;
-        let write_result = self.write_response(record, &request, &response);
-        let result: Result<c_int, HandleRequestError> = match write_result {
-            Ok(response) => Ok(response.status_code.as_u16() as c_int),
-            Err(write_err) => Err(
-                HandleRequestError::Write(write_err)
-            ),
+        let request = match self.read_request(record) {
+            Ok(request) => request,
+            // Declined, so another Apache handler can serve the URI
+            Err(read_err @ ReadError::NotMatched(_)) => return Err(HandleRequestError::Read(read_err)),
+            Err(read_err) => {
+                // Written as a status response like a handle error, so the write observers count it too
+                info!(record.server, "TileServer::handle_request - responding to read error: {}", read_err);
+                let request = unread_request(record);
+                let mut response = ErrorStatusMapper::handle_error_response(&HandleError::RequestNotRead(read_err));
+                // Every stage has seen the read, so every stage sees the response to it
+                let entered_stages = self.middleware.stage_count();
+                let write_result = self.write_response(record, &request, entered_stages, &mut response, writer);
+                debug!(record.server, "TileServer::handle_request - finish");
+                return as_handler_status(&response, write_result);
+            },
         };
+        let (entered_stages, outcome) = {
+            let context = HostContext::new(&self.config, record);
+            self.middleware.before_handle(&context, &request)
+        };
+        let mut handle_result = match outcome {
+            ProcessOutcome::Processed(response) => Ok(response),
+            ProcessOutcome::Ignored => self.call_handlers(record, &request),
+        };
+        {
+            let context = HostContext::new(&self.config, record);
+            self.middleware.after_handle(entered_stages, &context, &request, &mut handle_result);
+        }
+        let mut response = match handle_result {
+            Ok(response) => response,
+            Err(handle_err) => {
+                // Write the error as a status response so the write observers can count it
+                info!(record.server, "TileServer::handle_request - responding to handle error: {}", handle_err);
+                ErrorStatusMapper::handle_error_response(&handle_err)
+            },
+        };
+        let write_result = self.write_response(record, &request, entered_stages, &mut response, writer);
         debug!(record.server, "TileServer::handle_request - finish");
-        return result;
+        return as_handler_status(&response, write_result);
     }
 
     fn read_request(
@@ -179,8 +402,11 @@ impl TileProxy {
             }
         };
         let request = read_apache2_request(record)?;
-        let read_result = read(&context, &request);
-        for observer_iter in SlippyObserverInventory::read_observers(&mut self.telemetry_state).iter_mut() {
+        let mut read_result = read(&context, &request);
+        // The observers see the read as the middleware left it, which is what the rest of the request follows
+        self.middleware.after_read(&context.host_context, &mut read_result);
+        let mut telemetry = self.telemetry();
+        for observer_iter in SlippyObserverInventory::read_observers(&mut *telemetry).iter_mut() {
             debug!(context.host().record, "TileServer::read_request - calling observer {:p}", *observer_iter);
             (*observer_iter).on_read(&context, &request, &read_result, read_func_name);
         }
@@ -202,9 +428,18 @@ impl TileProxy {
             BodyVariant::ReportStatistics => {
                 self.call_statistics_handler(record, &request.header)
             },
+            BodyVariant::ResetStatistics(body) => {
+                self.call_statistics_reset_handler(record, &request.header, body)
+            },
+            BodyVariant::DumpConfig(body) => {
+                self.call_configuration_handler(record, &request.header, body)
+            },
             BodyVariant::ServeTile(body) => {
                 self.call_tile_handler(record, &request.header, body)
-            }
+            },
+            BodyVariant::Unread => {
+                Err(HandleError::RequestNotRead(ReadError::NotMatched(request.header.uri.clone())))
+            },
         };
         debug!(record.server, "TileServer::call_handlers - finish");
         return handle_result;
@@ -226,7 +461,8 @@ impl TileProxy {
             )
         };
         let handler_name = self.handler_state.description.type_name();
-        for observer_iter in HandlerObserverInventory::description_use_case_observers(&mut self.telemetry_state).iter_mut() {
+        let mut telemetry = self.telemetry();
+        for observer_iter in HandlerObserverInventory::description_use_case_observers(&mut *telemetry).iter_mut() {
             (*observer_iter).on_describe_layer(header, &handle_result, handler_name);
         }
         debug!(record.server, "TileServer::call_description_handler - finish");
@@ -240,12 +476,11 @@ impl TileProxy {
     ) -> Result<SlippyResponse, HandleError> {
         debug!(record.server, "TileServer::call_statistics_handler - start");
         let handle_result = {
+            let telemetry = self.telemetry();
             let context = StatisticsContext {
                 host: HostContext::new(&self.config, record),
-                services: ServicesContext {
-                    telemetry: &self.telemetry_state,
-                    rendering: &mut self.rendering_state,
-                },
+                communication: &self.comms_state,
+                telemetry: &*telemetry,
             };
             self.handler_state.statistics.report_statistics(
                 &context,
@@ -253,13 +488,64 @@ impl TileProxy {
             )
         };
         let handler_name = self.handler_state.statistics.type_name();
-        for observer_iter in HandlerObserverInventory::statistics_use_case_observers(&mut self.telemetry_state).iter_mut() {
+        let mut telemetry = self.telemetry();
+        for observer_iter in HandlerObserverInventory::statistics_use_case_observers(&mut *telemetry).iter_mut() {
             (*observer_iter).on_report_statistics(header, &handle_result, handler_name);
         }
         debug!(record.server, "TileServer::call_statistics_handler - finish");
         return handle_result;
     }
 
+    fn call_statistics_reset_handler(
+        &mut self,
+        record: &mut request_rec,
+        header: &Header,
+        body: &ResetStatisticsRequest,
+    ) -> Result<SlippyResponse, HandleError> {
+        debug!(record.server, "TileServer::call_statistics_reset_handler - start");
+        let handle_result = {
+            let mut telemetry = self.telemetry();
+            let mut context = StatisticsResetContext {
+                host: HostContext::new(&self.config, record),
+                communication: &self.comms_state,
+                telemetry: &mut *telemetry,
+            };
+            self.handler_state.statistics.reset_statistics(
+                &mut context,
+                header,
+                body,
+            )
+        };
+        let handler_name = self.handler_state.statistics.type_name();
+        let mut telemetry = self.telemetry();
+        for observer_iter in HandlerObserverInventory::statistics_use_case_observers(&mut *telemetry).iter_mut() {
+            (*observer_iter).on_report_statistics(header, &handle_result, handler_name);
+        }
+        debug!(record.server, "TileServer::call_statistics_reset_handler - finish");
+        return handle_result;
+    }
+
+    fn call_configuration_handler(
+        &mut self,
+        record: &mut request_rec,
+        header: &Header,
+        body: &DumpConfigRequest,
+    ) -> Result<SlippyResponse, HandleError> {
+        debug!(record.server, "TileServer::call_configuration_handler - start");
+        let handle_result = {
+            let context = ConfigurationContext {
+                host: HostContext::new(&self.config, record),
+            };
+            self.handler_state.configuration.dump_config(
+                &context,
+                header,
+                body,
+            )
+        };
+        debug!(record.server, "TileServer::call_configuration_handler - finish");
+        return handle_result;
+    }
+
     fn call_tile_handler(
         &mut self,
         record: &mut request_rec,
@@ -274,10 +560,7 @@ impl TileProxy {
                     communication: &mut self.comms_state,
                     storage: &mut self.storage_state,
                 },
-                services: ServicesContext {
-                    telemetry: &self.telemetry_state,
-                    rendering: &mut self.rendering_state,
-                },
+                rendering: &mut self.rendering_state,
             };
             self.handler_state.tile.fetch_tile(
                 &mut context,
@@ -286,7 +569,8 @@ impl TileProxy {
             )
         };
         let handler_name = self.handler_state.tile.type_name();
-        for observer_iter in HandlerObserverInventory::tile_use_case_observers(&mut self.telemetry_state).iter_mut() {
+        let mut telemetry = self.telemetry();
+        for observer_iter in HandlerObserverInventory::tile_use_case_observers(&mut *telemetry).iter_mut() {
             (*observer_iter).on_fetch_tile(header, body, &handle_result, &handler_name);
         }
         debug!(record.server, "TileServer::call_tile_handler - finish");
@@ -297,19 +581,21 @@ impl TileProxy {
         &mut self,
         record: &mut request_rec,
         request: &SlippyRequest,
-        response: &SlippyResponse,
+        entered_stages: usize,
+        response: &mut SlippyResponse,
+        writer: &mut dyn HttpResponseWriter,
     ) -> Result<HttpResponse, WriteError> {
         debug!(record.server, "TileServer::write_response - start");
         let (write, write_func_name) = SlippyInventory::write_response_func();
-        // Work around the borrow checker below, but its necessary since request_rec from a foreign C framework
-        let write_record = record as *mut request_rec;
-        let writer: &mut dyn HttpResponseWriter = unsafe { write_record.as_mut().unwrap() };
         let context = WriteContext {
             host_context: HostContext::new(&self.config, record),
             request,
         };
-        let write_result = write(&context, &response, writer);
-        for observer_iter in SlippyObserverInventory::write_observers(&mut self.telemetry_state).iter_mut() {
+        self.middleware.before_write(entered_stages, &context, response, writer);
+        let write_result = write(&context, response, writer);
+        self.middleware.after_write(entered_stages, &context, response, &write_result);
+        let mut telemetry = self.telemetry();
+        for observer_iter in SlippyObserverInventory::write_observers(&mut *telemetry).iter_mut() {
             debug!(
                 context.host().record,
                 "TileServer::write_response - calling observer {:p}", *observer_iter
@@ -321,6 +607,37 @@ impl TileProxy {
     }
 }
 
+// A request that failed to be read still has a header, so its response can be observed like any other
+fn unread_request(record: &request_rec) -> SlippyRequest {
+    let uri = if record.uri.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(record.uri) }.to_string_lossy().into_owned()
+    };
+    SlippyRequest {
+        header: Header {
+            layer: LayerName::new(),
+            request_id: generate_id(),
+            uri,
+            received_timestamp: Utc.timestamp_millis_opt(record.request_time).single().unwrap_or_else(Utc::now),
+        },
+        body: BodyVariant::Unread,
+    }
+}
+
+// Apache generates the body of a status response from the status returned by the handler,
+// while any other response has already been written
+fn as_handler_status(
+    response: &SlippyResponse,
+    write_result: Result<HttpResponse, WriteError>,
+) -> Result<c_int, HandleRequestError> {
+    let http_response = write_result?;
+    return match response.body {
+        response::BodyVariant::Status(_) => Ok(http_response.status_code.as_u16() as c_int),
+        _ => Ok(OK as c_int),
+    };
+}
+
 #[no_mangle]
 extern "C" fn drop_tile_server(server_void: *mut c_void) -> apr_status_t {
     let server_ref = match access_pool_object::<TileProxy>(server_void) {
@@ -334,17 +651,176 @@ extern "C" fn drop_tile_server(server_void: *mut c_void) -> apr_status_t {
 }
 
 
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::binding::apache2::{apr_table_make, apr_table_setn,};
+    use crate::framework::apache2::record::test_utils::{with_request_rec, with_server_rec,};
+    use crate::io::communication::http_exchange::test_utils::CapturingWriter;
+
+    use http::header::HeaderMap;
+    use mime::Mime;
+
+    use std::os::raw::c_char;
+    use std::string::String;
+    use std::vec::Vec;
+
+    // The parts of a request that Apache would have parsed into the request_rec
+    #[derive(Clone, Debug)]
+    pub struct TestRequest {
+        pub method: String,
+        pub uri: String,
+        pub headers: Vec<(String, String)>,
+        pub client_ip: Option<String>,
+    }
+
+    impl TestRequest {
+        pub fn get(uri: &str) -> TestRequest {
+            TestRequest {
+                method: String::from("GET"),
+                uri: String::from(uri),
+                headers: Vec::new(),
+                client_ip: None,
+            }
+        }
+
+        pub fn with_method(
+            mut self,
+            method: &str,
+        ) -> TestRequest {
+            self.method = String::from(method);
+            self
+        }
+
+        pub fn with_header(
+            mut self,
+            name: &str,
+            value: &str,
+        ) -> TestRequest {
+            self.headers.push((String::from(name), String::from(value)));
+            self
+        }
+
+        pub fn with_client_ip(
+            mut self,
+            client_ip: &str,
+        ) -> TestRequest {
+            self.client_ip = Some(String::from(client_ip));
+            self
+        }
+    }
+
+    // What the client would have received
+    #[derive(Debug)]
+    pub struct TestResponse {
+        pub status: c_int,
+        pub headers: HeaderMap,
+        pub error_headers: HeaderMap,
+        pub content_type: Option<Mime>,
+        pub content_length: Option<usize>,
+        pub body: Vec<u8>,
+    }
+
+    pub fn with_tile_proxy<F>(
+        module_config: ModuleConfig,
+        func: F,
+    ) -> Result<(), Box<dyn StdError>>
+    where F: FnOnce(&mut TileProxy) -> Result<(), Box<dyn StdError>> {
+        with_server_rec(|record| {
+            let proxy = TileProxy::new(record, module_config)?;
+            func(proxy)
+        })
+    }
+
+    // Runs the request through reading, handling and writing just as handle_request does for Apache
+    pub fn send_request(
+        proxy: &mut TileProxy,
+        request: &TestRequest,
+    ) -> Result<Result<TestResponse, HandleRequestError>, Box<dyn StdError>> {
+        let method = CString::new(request.method.as_str())?;
+        let uri = CString::new(request.uri.as_str())?;
+        let mut headers = Vec::new();
+        for (name, value) in &request.headers {
+            headers.push((CString::new(name.as_str())?, CString::new(value.as_str())?));
+        }
+        let client_ip = match &request.client_ip {
+            Some(client_ip) => Some(CString::new(client_ip.as_str())?),
+            None => None,
+        };
+        let mut result = None;
+        with_request_rec(|record| {
+            record.method = method.as_ptr();
+            record.header_only = (request.method == "HEAD") as c_int;
+            record.uri = uri.as_ptr() as *mut c_char;
+            record.request_time = Utc::now().timestamp_millis();
+            unsafe {
+                record.headers_in = apr_table_make(record.pool, headers.len() as c_int);
+                for (name, value) in &headers {
+                    apr_table_setn(record.headers_in, name.as_ptr(), value.as_ptr());
+                }
+            }
+            if let Some(client_ip) = &client_ip {
+                record.useragent_ip = client_ip.as_ptr() as *mut c_char;
+            }
+            let mut writer = CapturingWriter::new();
+            let handle_result = proxy.handle_request_with_writer(record, &mut writer);
+            result = Some(
+                handle_result.map(|status| {
+                    TestResponse {
+                        status,
+                        headers: writer.headers,
+                        error_headers: writer.error_headers,
+                        content_type: writer.content_type,
+                        content_length: writer.content_length,
+                        body: writer.body,
+                    }
+                })
+            );
+            Ok(())
+        })?;
+        Ok(result.unwrap())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::identifier::generate_id;
     use crate::schema::slippy::request;
     use crate::schema::slippy::response;
-    use crate::schema::tile::identity::LayerName;
+    use crate::schema::tile::identity::{LayerName, TileIdentity,};
     use crate::framework::apache2::record::test_utils::{ with_request_rec, with_server_rec };
+    use crate::io::communication::http_exchange::test_utils::CapturingWriter;
+    use crate::io::storage::meta_tile::MetaTile;
+    use super::test_utils::{send_request, with_tile_proxy, TestRequest,};
     use chrono::Utc;
+    use crate::io::communication::renderd_socket::test_utils::{MockRenderd, MockReply,};
+    use http::header::{ACCESS_CONTROL_ALLOW_ORIGIN, ETAG, RETRY_AFTER, HeaderValue,};
+    use http::status::StatusCode;
+    use crate::adapter::slippy::interface::RequestMiddleware;
+    use crate::service::telemetry::registry::test_utils::RecordingObserver;
     use std::boxed::Box;
     use std::string::String;
+    use std::thread;
+
+    struct NoOpMiddleware {}
+
+    impl RequestMiddleware for NoOpMiddleware {}
+
+    struct CorsMiddleware {}
+
+    impl RequestMiddleware for CorsMiddleware {
+        fn before_write(
+            &mut self,
+            _context: &WriteContext,
+            response: &mut SlippyResponse,
+            _writer: &mut dyn HttpResponseWriter,
+        ) -> () {
+            if let response::BodyVariant::Status(status) = &mut response.body {
+                status.http_headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+            }
+        }
+    }
 
     #[test]
     fn test_new() -> Result<(), Box<dyn StdError>> {
@@ -355,6 +831,151 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_failed_reload_keeps_previous_config() -> Result<(), Box<dyn StdError>> {
+        with_server_rec(|record| {
+            let module_config = ModuleConfig::new();
+            let proxy = TileProxy::new(record, module_config)?;
+            let mut config_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            config_path.push("resources/test/tile/basic_valid.conf");
+            proxy.load_config(config_path, record.get_host_name())?;
+
+            let mut invalid_config = proxy.server_config.clone();
+            invalid_config.add_directive("ModTileRenderdSocketName", &[""]);
+            match proxy.configure(invalid_config, record.get_host_name()) {
+                Err(ReloadError::Invalid(errors)) => {
+                    assert_eq!("socketname", errors[0].entry, "Failed to report the invalid entry");
+                },
+                _ => panic!("Invalid config was not rejected"),
+            }
+            assert_eq!("/var/run/test.sock", proxy.config.renderd.ipc_uri, "Failed to keep the previous config");
+            assert!(proxy.server_config.directives.is_empty(), "Failed to keep the previous directives");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_thread_proxy_follows_reload() -> Result<(), Box<dyn StdError>> {
+        with_server_rec(|record| {
+            TileProxy::find_or_allocate_new(record)?;
+            let mut config_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            config_path.push("resources/test/tile/basic_valid.conf");
+            let first_uri = TileProxy::with_thread_proxy(record, |copy| copy.config.renderd.ipc_uri.clone())?;
+            assert_eq!("/var/run/renderd/renderd.sock", first_uri, "Incorrect config in the thread proxy");
+
+            let host_name = record.get_host_name().map(|name| name.to_string());
+            let proxy = TileProxy::find_or_allocate_new(record)?;
+            proxy.load_config(config_path, host_name.as_deref())?;
+            let reloaded_uri = TileProxy::with_thread_proxy(record, |copy| copy.config.renderd.ipc_uri.clone())?;
+            assert_eq!("/var/run/test.sock", reloaded_uri, "Failed to rebuild the thread proxy after a reload");
+            // Apache hands the same server_rec to every request thread, which the raw pointers in it hide from Rust
+            let record_address = record as *const server_rec as usize;
+            let other_thread_uri = thread::scope(|scope| {
+                scope.spawn(move || {
+                    let record = unsafe { &*(record_address as *const server_rec) };
+                    TileProxy::with_thread_proxy(record, |copy| copy.config.renderd.ipc_uri.clone())
+                        .map_err(|err| err.to_string())
+                }).join().unwrap()
+            })?;
+            assert_eq!("/var/run/test.sock", other_thread_uri, "Incorrect config in another thread's proxy");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_thread_proxies_share_telemetry() -> Result<(), Box<dyn StdError>> {
+        with_server_rec(|record| {
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            TileProxy::find_or_allocate_new(record)?
+                .register_observer(Box::new(RecordingObserver::new("audit", &calls)));
+            let record_address = record as *const server_rec as usize;
+            let statuses: Vec<Result<c_int, String>> = thread::scope(|scope| {
+                let handles: Vec<_> = (0..2).map(|_| {
+                    scope.spawn(move || {
+                        let record = unsafe { &*(record_address as *const server_rec) };
+                        TileProxy::with_thread_proxy(record, |copy| {
+                            match send_request(copy, &TestRequest::get("/mod_tile_rs")) {
+                                Ok(Ok(response)) => Ok(response.status),
+                                Ok(Err(err)) => Err(err.to_string()),
+                                Err(err) => Err(err.to_string()),
+                            }
+                        }).map_err(|err| err.to_string())?
+                    })
+                }).collect();
+                handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+            });
+            for status in statuses {
+                assert_eq!(OK as c_int, status?, "Incorrect status");
+            }
+
+            let proxy = TileProxy::find_or_allocate_new(record)?;
+            assert_eq!(2, proxy.telemetry().read_counter().count, "Failed to count the reads of both threads");
+            assert_eq!(2, proxy.telemetry().write_counter().count, "Failed to count the writes of both threads");
+            let observed_writes = calls.lock().unwrap().iter().filter(|call| *call == "audit.on_write").count();
+            assert_eq!(2, observed_writes, "Failed to call the registered observer on both threads");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_access_log_directive_registers_observer() -> Result<(), Box<dyn StdError>> {
+        let log_file = mktemp::Temp::new_file()?;
+        with_server_rec(|record| {
+            let record_ptr = record as *mut server_rec;
+            let proxy = TileProxy::find_or_allocate_new(record)?;
+            proxy.server_config.add_directive("ModTileAccessLog", &[log_file.to_str().unwrap()]);
+            // Apache hands child_init the same server_rec that the proxy is stored in
+            proxy.initialise(unsafe { record_ptr.as_mut().unwrap() })?;
+            let response = TileProxy::with_thread_proxy(record, |copy| {
+                send_request(copy, &TestRequest::get("/mod_tile_rs")).map_err(|err| err.to_string())
+            })??.map_err(|err| err.to_string())?;
+            assert_eq!(OK as c_int, response.status, "Incorrect status");
+            let log = std::fs::read_to_string(&log_file)?;
+            let lines: Vec<&str> = log.lines().collect();
+            assert_eq!(1, lines.len(), "Failed to log the request handled by the thread proxy");
+            assert!(lines[0].contains("\"/mod_tile_rs\" 200 "), "Incorrect access log line");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_reload_rebuilds_telemetry_and_middleware() -> Result<(), Box<dyn StdError>> {
+        with_server_rec(|server| {
+            with_request_rec(|request| {
+                let module_config = ModuleConfig::new();
+                let proxy = TileProxy::new(server, module_config)?;
+                let calls = Arc::new(Mutex::new(Vec::new()));
+                proxy.register_observer(Box::new(RecordingObserver::new("audit", &calls)));
+                let built_from = Arc::new(Mutex::new(Vec::new()));
+                let factory_built_from = built_from.clone();
+                proxy.add_middleware(
+                    Arc::new(move |config: &ModuleConfig| -> Result<Box<dyn RequestMiddleware>, InvalidConfigError> {
+                        factory_built_from.lock().unwrap().push(config.renderd.ipc_uri.clone());
+                        Ok(Box::new(NoOpMiddleware {}))
+                    })
+                )?;
+                let uri = CString::new("/mod_tile_rs")?;
+                request.uri = uri.into_raw();
+                proxy.read_request(request)?;
+                assert_eq!(1, proxy.telemetry().read_counter().count, "Read observer not called");
+
+                let mut config_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+                config_path.push("resources/test/tile/basic_valid.conf");
+                proxy.load_config(config_path, server.get_host_name())?;
+                assert_eq!(0, proxy.telemetry().read_counter().count, "Failed to rebuild the telemetry");
+                assert_eq!(
+                    vec!["/var/run/renderd/renderd.sock", "/var/run/test.sock"],
+                    *built_from.lock().unwrap(),
+                    "Failed to rebuild the middleware from the reloaded config"
+                );
+                proxy.read_request(request)?;
+                assert_eq!(vec!["audit.on_read", "audit.on_read"], *calls.lock().unwrap(), "Failed to keep the registered observer");
+                Ok(())
+            })
+        })
+    }
+
     #[test]
     fn test_proxy_reload() -> Result<(), Box<dyn StdError>> {
         with_server_rec(|record| {
@@ -363,14 +984,28 @@ mod tests {
 
             let expected_timeout = Duration::new(30, 50);
             proxy.set_render_timeout(&expected_timeout);
+            let expected_missing_timeout = Duration::new(60, 0);
+            proxy.set_missing_render_timeout(&expected_missing_timeout);
+            proxy.apply_directive("ModTileTileDir", &["/var/cache/directive"], record.get_host_name())?;
             let mut expected_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
             expected_path.push("resources/test/tile/basic_valid.conf");
             proxy.load_config(expected_path.clone(), record.get_host_name())?;
 
             let actual_timeout = proxy.config.renderd.render_timeout.clone();
             assert_eq!(expected_timeout, actual_timeout, "Failed to preserve request timeout during reload");
-            assert!(proxy.config_file_path.is_some(), "Config file path is None");
-            if let Some(actual_path) = &proxy.config_file_path {
+            let actual_missing_timeout = proxy.config.renderd.missing_render_timeout.clone();
+            assert_eq!(
+                expected_missing_timeout,
+                actual_missing_timeout,
+                "Failed to preserve missing request timeout during reload"
+            );
+            assert_eq!(
+                "/var/cache/directive",
+                proxy.config.renderd.store_uri,
+                "Failed to prefer the directive over the config file during reload"
+            );
+            assert!(proxy.server_config.config_file_path.is_some(), "Config file path is None");
+            if let Some(actual_path) = &proxy.server_config.config_file_path {
                 assert_eq!(&expected_path, actual_path, "Failed to preserve config file path during reload");
             }
             Ok(())
@@ -387,7 +1022,7 @@ mod tests {
                 request.uri = uri.into_raw();
                 let result = proxy.read_request(request);
                 result.expect("Unexpected request read error");
-                let actual_count = proxy.telemetry_state.read_counter().count;
+                let actual_count = proxy.telemetry().read_counter().count;
                 assert_eq!(1, actual_count, "Read observer not called");
                 Ok(())
             })
@@ -412,7 +1047,7 @@ mod tests {
                     body: request::BodyVariant::ReportStatistics,
                 };
                 proxy.call_handlers(request, &slippy_request)?;
-                let actual_count = proxy.telemetry_state.handle_counter().count;
+                let actual_count = proxy.telemetry().handle_counter().count;
                 assert_eq!(1, actual_count, "Handle observer not called");
                 Ok(())
             })
@@ -434,7 +1069,7 @@ mod tests {
                     received_timestamp: Utc::now(),
                 };
                 proxy.call_description_handler(request, &header)?;
-                let actual_count = proxy.telemetry_state.handle_counter().count;
+                let actual_count = proxy.telemetry().handle_counter().count;
                 assert_eq!(1, actual_count, "Handle observer not called");
                 Ok(())
             })
@@ -456,7 +1091,7 @@ mod tests {
                     received_timestamp: Utc::now(),
                 };
                 proxy.call_statistics_handler(request, &header)?;
-                let actual_count = proxy.telemetry_state.handle_counter().count;
+                let actual_count = proxy.telemetry().handle_counter().count;
                 assert_eq!(1, actual_count, "Handle observer not called");
                 Ok(())
             })
@@ -480,7 +1115,7 @@ mod tests {
                     },
                     body: request::BodyVariant::ReportStatistics,
                 };
-                let slippy_response = response::SlippyResponse {
+                let mut slippy_response = response::SlippyResponse {
                     header: response::Header {
                         mime_type: mime::APPLICATION_JSON.clone(),
                         before_timestamp: Utc::now(),
@@ -499,11 +1134,165 @@ mod tests {
                         }
                     ),
                 };
-                proxy.write_response(request, &slippy_request, &slippy_response)?;
-                let actual_count = proxy.telemetry_state.write_counter().count;
+                let mut writer = CapturingWriter::new();
+                proxy.write_response(request, &slippy_request, 0, &mut slippy_response, &mut writer)?;
+                let actual_count = proxy.telemetry().write_counter().count;
                 assert_eq!(1, actual_count, "Write observer not called");
                 Ok(())
             })
         })
     }
+
+    fn test_store_config() -> ModuleConfig {
+        let mut module_config = ModuleConfig::new();
+        let mut store_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        store_path.push("resources/test/meta_tile");
+        module_config.renderd.store_uri = String::from(store_path.to_str().unwrap());
+        module_config
+    }
+
+    #[test]
+    fn test_serve_cached_tile() -> Result<(), Box<dyn StdError>> {
+        let module_config = test_store_config();
+        let tile_id = TileIdentity {
+            x: 944,
+            y: 616,
+            z: 10,
+            layer: LayerName::from("default"),
+            parameter: None,
+        };
+        let tile_path = MetaTile::identity_to_path(&module_config, &tile_id);
+        let meta_tile = MetaTile::read(&tile_path.meta_tile_path)?;
+        let expected_body = meta_tile.select(tile_path.tile_offset)?.with_tile(|raw_bytes| raw_bytes.to_vec());
+        with_tile_proxy(module_config, |proxy| {
+            let request = TestRequest::get("/osm/944/616/10.png").with_client_ip("192.168.0.1");
+            let response = send_request(proxy, &request)??;
+            assert_eq!(OK as c_int, response.status, "Incorrect status");
+            assert_eq!(Some(mime::IMAGE_PNG), response.content_type, "Incorrect content type");
+            assert_eq!(expected_body, response.body, "Failed to serve the tile from the meta tile");
+            assert_eq!(Some(expected_body.len()), response.content_length, "Incorrect content length");
+            assert!(response.headers.contains_key(ETAG), "Failed to set the ETag");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_serve_description_then_statistics() -> Result<(), Box<dyn StdError>> {
+        with_tile_proxy(test_store_config(), |proxy| {
+            let description = send_request(proxy, &TestRequest::get("/osm/tile-layer.json"))??;
+            assert_eq!(OK as c_int, description.status, "Incorrect status");
+            assert_eq!(Some(mime::APPLICATION_JSON), description.content_type, "Incorrect content type");
+            let layer: serde_json::Value = serde_json::from_slice(&description.body)?;
+            assert_eq!("xyz", layer["schema"], "Failed to describe the layer");
+            let statistics = send_request(proxy, &TestRequest::get("/mod_tile_rs"))??;
+            assert_eq!(OK as c_int, statistics.status, "Incorrect status");
+            assert_eq!(2, proxy.telemetry().write_counter().count, "Failed to count both responses");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_decline_unmatched_uri() -> Result<(), Box<dyn StdError>> {
+        with_tile_proxy(test_store_config(), |proxy| {
+            let result = send_request(proxy, &TestRequest::get("/index.html").with_method("HEAD"))?;
+            assert!(
+                matches!(result, Err(HandleRequestError::Read(ReadError::NotMatched(_)))),
+                "Failed to decline the request"
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_count_read_error_response() -> Result<(), Box<dyn StdError>> {
+        with_tile_proxy(test_store_config(), |proxy| {
+            let response = send_request(proxy, &TestRequest::get("/mod_tile_rs/reset"))??;
+            assert_eq!(
+                StatusCode::METHOD_NOT_ALLOWED.as_u16() as c_int,
+                response.status,
+                "Failed to respond to the read error"
+            );
+            assert_eq!(1, proxy.telemetry().write_counter().count, "Failed to count the read error response");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_middleware_decorates_read_error_response() -> Result<(), Box<dyn StdError>> {
+        with_tile_proxy(test_store_config(), |proxy| {
+            proxy.add_middleware(
+                Arc::new(|_config: &ModuleConfig| -> Result<Box<dyn RequestMiddleware>, InvalidConfigError> {
+                    Ok(Box::new(CorsMiddleware {}))
+                })
+            )?;
+            let response = send_request(proxy, &TestRequest::get("/mod_tile_rs/reset"))??;
+            assert_eq!(
+                Some(&HeaderValue::from_static("*")),
+                response.error_headers.get(ACCESS_CONTROL_ALLOW_ORIGIN),
+                "Failed to decorate the response to the read error"
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_missing_tile_without_renderd() -> Result<(), Box<dyn StdError>> {
+        let mut module_config = test_store_config();
+        let socket_dir = mktemp::Temp::new_dir()?;
+        module_config.renderd.ipc_uri = String::from(socket_dir.join("renderd.sock").to_str().unwrap());
+        with_tile_proxy(module_config, |proxy| {
+            let response = send_request(proxy, &TestRequest::get("/osm/0/0/10.png"))??;
+            assert_eq!(
+                StatusCode::SERVICE_UNAVAILABLE.as_u16() as c_int,
+                response.status,
+                "Failed to report that renderd is unreachable"
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_reset_statistics_with_credential() -> Result<(), Box<dyn StdError>> {
+        let mut module_config = test_store_config();
+        module_config.telemetry.statistics_reset_token = Some(String::from("secret"));
+        with_tile_proxy(module_config, |proxy| {
+            let with_get = send_request(
+                proxy,
+                &TestRequest::get("/mod_tile_rs/reset").with_header("Authorization", "Bearer secret"),
+            )??;
+            assert_eq!(
+                StatusCode::METHOD_NOT_ALLOWED.as_u16() as c_int,
+                with_get.status,
+                "Failed to reject a reset with GET"
+            );
+            let rejected = send_request(
+                proxy,
+                &TestRequest::get("/mod_tile_rs/reset").with_method("POST").with_header("Authorization", "Bearer wrong"),
+            )??;
+            assert_eq!(StatusCode::FORBIDDEN.as_u16() as c_int, rejected.status, "Failed to reject the credential");
+            let accepted = send_request(
+                proxy,
+                &TestRequest::get("/mod_tile_rs/reset").with_method("POST").with_header("Authorization", "Bearer secret"),
+            )??;
+            assert_eq!(OK as c_int, accepted.status, "Failed to accept the credential");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_missing_tile_render_timeout() -> Result<(), Box<dyn StdError>> {
+        let store_dir = mktemp::Temp::new_dir()?;
+        let mut module_config = ModuleConfig::new();
+        module_config.renderd.store_uri = String::from(store_dir.to_str().unwrap());
+        let renderd = MockRenderd::start(&module_config, MockReply::Delay(Duration::from_secs(3)))?;
+        module_config.renderd.ipc_uri = String::from(renderd.socket_path().to_str().unwrap());
+        with_tile_proxy(module_config, |proxy| {
+            proxy.set_missing_render_timeout(&Duration::from_secs(1));
+            let response = send_request(proxy, &TestRequest::get("/osm/0/0/10.png"))??;
+            assert_eq!(StatusCode::SERVICE_UNAVAILABLE.as_u16() as c_int, response.status, "Failed to time out");
+            let retry_after = response.error_headers.get(RETRY_AFTER).map(|value| value.to_str().unwrap());
+            assert_eq!(Some("1"), retry_after, "Incorrect retry delay");
+            Ok(())
+        })
+    }
 }
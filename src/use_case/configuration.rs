@@ -0,0 +1,194 @@
+use crate::binding::apache2::server_rec;
+use crate::schema::apache2::config::{LayerConfig, ModuleConfig,};
+use crate::schema::apache2::error::InvalidConfigError;
+use crate::schema::apache2::virtual_host::VirtualHost;
+use crate::schema::handler::error::HandleError;
+use crate::schema::slippy::request;
+use crate::schema::slippy::response;
+use crate::framework::apache2::context::HostContext;
+use crate::framework::apache2::record::ServerRecord;
+
+use chrono::Utc;
+use mime;
+
+use std::any::type_name;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+
+
+pub struct ConfigurationContext<'c> {
+    pub host: HostContext<'c>,
+}
+
+impl<'c> ConfigurationContext<'c> {
+    pub fn module_config(&self) -> &'c ModuleConfig {
+        self.host.module_config
+    }
+
+    pub fn host(&self) -> &'c VirtualHost<'c> {
+        self.host.host
+    }
+}
+
+
+pub struct ConfigurationHandlerState { }
+
+impl ConfigurationHandlerState {
+    pub fn new(_config: &ModuleConfig) -> Result<ConfigurationHandlerState, InvalidConfigError> {
+        Ok(
+            ConfigurationHandlerState {  }
+        )
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+
+    pub fn dump_config(
+        &self,
+        context: &ConfigurationContext,
+        _header: &request::Header,
+        body: &request::DumpConfigRequest,
+    ) -> Result<response::SlippyResponse, HandleError> {
+        let before_timestamp = Utc::now();
+        let allowed_ips = &context.module_config().telemetry.config_dump_allowed_ips;
+        if allowed_ips.is_empty() {
+            return Err(HandleError::Forbidden(String::from("Config dump is not enabled")));
+        }
+        let client_ip = body.client_ip.as_ref().and_then(|ip| IpAddr::from_str(ip).ok());
+        match client_ip {
+            Some(ip) if allowed_ips.contains(&ip) => (),
+            _ => {
+                warn!(
+                    context.host().record,
+                    "ConfigurationHandlerState::dump_config - rejected client {}",
+                    body.client_ip.as_deref().unwrap_or("unknown"),
+                );
+                return Err(HandleError::Forbidden(String::from("Client is not allowed to dump the config")));
+            },
+        };
+        let configuration = dump(context.module_config(), context.host().record);
+        let after_timestamp = Utc::now();
+        let response = response::SlippyResponse {
+            header: response::Header {
+                mime_type: mime::APPLICATION_JSON.clone(),
+                before_timestamp,
+                after_timestamp,
+            },
+            body: response::BodyVariant::Configuration(configuration),
+        };
+        return Ok(response);
+    }
+}
+
+fn dump(
+    config: &ModuleConfig,
+    record: &server_rec,
+) -> response::ConfigurationDump {
+    let mut layers: Vec<LayerConfig> = config.layers.values().cloned().collect();
+    layers.sort_by(|left, right| left.name.as_str().cmp(right.name.as_str()));
+    response::ConfigurationDump {
+        server: response::ServerIdentity {
+            host_name: record.get_host_name().map(String::from),
+            port: record.port,
+            is_virtual: record.is_virtual != 0,
+            defined_in: record.get_definition_name().map(String::from),
+            defined_at_line: record.defn_line_number,
+        },
+        renderd: config.renderd.clone(),
+        layers,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::identifier::generate_id;
+    use crate::schema::tile::identity::LayerName;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+
+    use std::boxed::Box;
+    use std::error::Error as StdError;
+    use std::ffi::CString;
+
+    fn make_header(uri: CString) -> Result<request::Header, Box<dyn StdError>> {
+        Ok(
+            request::Header {
+                layer: LayerName::new(),
+                request_id: generate_id(),
+                uri: uri.into_string()?,
+                received_timestamp: Utc::now(),
+            }
+        )
+    }
+
+    #[test]
+    fn test_dump_config_to_allowed_client() -> Result<(), Box<dyn StdError>> {
+        let mut module_config = ModuleConfig::new();
+        module_config.telemetry.config_dump_allowed_ips.push(IpAddr::from([127, 0, 0, 1]));
+        let handler_state = ConfigurationHandlerState::new(&module_config)?;
+        with_request_rec(|record| {
+            let uri = CString::new("/mod_tile_rs/config")?;
+            record.uri = uri.clone().into_raw();
+            let header = make_header(uri)?;
+            let context = ConfigurationContext {
+                host: HostContext::new(&module_config, record),
+            };
+            let body = request::DumpConfigRequest {
+                client_ip: Some(String::from("127.0.0.1")),
+            };
+            let actual_response = handler_state.dump_config(&context, &header, &body)?;
+            match actual_response.body {
+                response::BodyVariant::Configuration(configuration) => {
+                    assert_eq!(Some(String::from("localhost")), configuration.server.host_name, "Incorrect server");
+                    assert_eq!(module_config.renderd, configuration.renderd, "Incorrect renderd config");
+                    assert_eq!(
+                        vec![module_config.layers[&LayerName::from("default")].clone()],
+                        configuration.layers,
+                        "Incorrect layer config"
+                    );
+                },
+                _ => panic!("Expected a configuration response"),
+            };
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_dump_config_to_unknown_client() -> Result<(), Box<dyn StdError>> {
+        let mut module_config = ModuleConfig::new();
+        let handler_state = ConfigurationHandlerState::new(&module_config)?;
+        with_request_rec(|record| {
+            let uri = CString::new("/mod_tile_rs/config")?;
+            record.uri = uri.clone().into_raw();
+            let header = make_header(uri)?;
+            let body = request::DumpConfigRequest {
+                client_ip: Some(String::from("127.0.0.1")),
+            };
+            let context = ConfigurationContext {
+                host: HostContext::new(&module_config, record),
+            };
+            let result = handler_state.dump_config(&context, &header, &body);
+            assert!(matches!(result, Err(HandleError::Forbidden(_))), "Failed to reject a dump when not enabled");
+            Ok(())
+        })?;
+        module_config.telemetry.config_dump_allowed_ips.push(IpAddr::from([10, 0, 0, 1]));
+        with_request_rec(|record| {
+            let uri = CString::new("/mod_tile_rs/config")?;
+            record.uri = uri.clone().into_raw();
+            let header = make_header(uri)?;
+            let context = ConfigurationContext {
+                host: HostContext::new(&module_config, record),
+            };
+            for client_ip in vec![None, Some(String::from("127.0.0.1"))] {
+                let body = request::DumpConfigRequest { client_ip };
+                let result = handler_state.dump_config(&context, &header, &body);
+                assert!(matches!(result, Err(HandleError::Forbidden(_))), "Failed to reject the client");
+            }
+            Ok(())
+        })
+    }
+}
@@ -6,12 +6,14 @@ use crate::use_case::interface::{
     StatisticsUseCaseObserver,
     TileUseCaseObserver,
 };
+use crate::use_case::configuration::ConfigurationHandlerState;
 use crate::use_case::description::DescriptionHandlerState;
 use crate::use_case::statistics::StatisticsHandlerState;
 use crate::use_case::tile::TileHandlerState;
 
 
 pub struct HandlerState {
+    pub configuration: ConfigurationHandlerState,
     pub description: DescriptionHandlerState,
     pub statistics: StatisticsHandlerState,
     pub tile: TileHandlerState,
@@ -21,6 +23,7 @@ impl HandlerState {
     pub fn new(config: &ModuleConfig) -> Result<HandlerState, InvalidConfigError> {
         Ok(
             HandlerState {
+                configuration: ConfigurationHandlerState::new(config)?,
                 description: DescriptionHandlerState::new(config)?,
                 statistics: StatisticsHandlerState::new(config)?,
                 tile: TileHandlerState::new(config)?,
@@ -34,22 +37,19 @@ pub struct HandlerObserverInventory { }
 impl HandlerObserverInventory {
     pub fn description_use_case_observers<'i>(
         telemetry: &'i mut dyn TelemetryInventory
-    ) -> [&'i mut dyn DescriptionUseCaseObserver; 2] {
-        let [read_observer_0, read_observer_1] = telemetry.description_use_case_observers();
-        return [read_observer_0, read_observer_1];
+    ) -> Vec<&'i mut dyn DescriptionUseCaseObserver> {
+        return telemetry.description_use_case_observers();
     }
 
     pub fn statistics_use_case_observers<'i>(
         telemetry: &'i mut dyn TelemetryInventory
-    ) -> [&'i mut dyn StatisticsUseCaseObserver; 2] {
-        let [read_observer_0, read_observer_1] = telemetry.statistics_use_case_observers();
-        return [read_observer_0, read_observer_1];
+    ) -> Vec<&'i mut dyn StatisticsUseCaseObserver> {
+        return telemetry.statistics_use_case_observers();
     }
 
     pub fn tile_use_case_observers<'i>(
         telemetry: &'i mut dyn TelemetryInventory
-    ) -> [&'i mut dyn TileUseCaseObserver; 2] {
-        let [read_observer_0, read_observer_1] = telemetry.tile_use_case_observers();
-        return [read_observer_0, read_observer_1];
+    ) -> Vec<&'i mut dyn TileUseCaseObserver> {
+        return telemetry.tile_use_case_observers();
     }
 }
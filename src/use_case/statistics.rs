@@ -4,21 +4,26 @@ use crate::schema::apache2::virtual_host::VirtualHost;
 use crate::schema::handler::error::HandleError;
 use crate::schema::slippy::request;
 use crate::schema::slippy::response;
+use crate::schema::telemetry::window::TimeWindow;
 use crate::schema::tile::age::TileAge;
 use crate::schema::tile::source::TileSource;
 use crate::framework::apache2::context::HostContext;
-use crate::service::interface::ServicesContext;
+use crate::io::communication::interface::CommunicationInventory;
+use crate::service::telemetry::interface::TelemetryInventory;
 
 use chrono::Utc;
+use enum_iterator::IntoEnumIterator;
 use http::status::StatusCode;
 use mime;
 
 use std::any::type_name;
+use std::string::String;
 
 
 pub struct StatisticsContext<'c> {
     pub host: HostContext<'c>,
-    pub services: ServicesContext<'c>,
+    pub communication: &'c dyn CommunicationInventory,
+    pub telemetry: &'c dyn TelemetryInventory,
 }
 
 impl<'c> StatisticsContext<'c> {
@@ -31,6 +36,22 @@ impl<'c> StatisticsContext<'c> {
     }
 }
 
+pub struct StatisticsResetContext<'c> {
+    pub host: HostContext<'c>,
+    pub communication: &'c dyn CommunicationInventory,
+    pub telemetry: &'c mut dyn TelemetryInventory,
+}
+
+impl<'c> StatisticsResetContext<'c> {
+    pub fn module_config(&self) -> &'c ModuleConfig {
+        self.host.module_config
+    }
+
+    pub fn host(&self) -> &'c VirtualHost<'c> {
+        self.host.host
+    }
+}
+
 
 pub struct StatisticsHandlerState { }
 
@@ -51,7 +72,42 @@ impl StatisticsHandlerState {
         _header: &request::Header,
     ) -> Result<response::SlippyResponse, HandleError> {
         let before_timestamp = Utc::now();
-        let statistics = self.report(&context.services);
+        let statistics = self.report(context.telemetry, context.communication);
+        let after_timestamp = Utc::now();
+        let response = response::SlippyResponse {
+            header: response::Header {
+                mime_type: mime::TEXT_PLAIN.clone(),
+                before_timestamp,
+                after_timestamp,
+            },
+            body: response::BodyVariant::Statistics(statistics),
+        };
+        return Ok(response);
+    }
+
+    pub fn reset_statistics(
+        &self,
+        context: &mut StatisticsResetContext,
+        _header: &request::Header,
+        body: &request::ResetStatisticsRequest,
+    ) -> Result<response::SlippyResponse, HandleError> {
+        let before_timestamp = Utc::now();
+        let expected_token = match &context.module_config().telemetry.statistics_reset_token {
+            Some(token) => token,
+            None => {
+                return Err(HandleError::Forbidden(String::from("Statistics reset is not enabled")));
+            },
+        };
+        match &body.credential {
+            Some(credential) if is_same_token(credential.as_bytes(), expected_token.as_bytes()) => (),
+            _ => {
+                warn!(context.host().record, "StatisticsHandlerState::reset_statistics - rejected invalid credential");
+                return Err(HandleError::Forbidden(String::from("Invalid statistics reset credential")));
+            },
+        };
+        context.telemetry.reset_metrics();
+        info!(context.host().record, "StatisticsHandlerState::reset_statistics - statistics reset");
+        let statistics = self.report(context.telemetry, context.communication);
         let after_timestamp = Utc::now();
         let response = response::SlippyResponse {
             header: response::Header {
@@ -66,11 +122,13 @@ impl StatisticsHandlerState {
 
     fn report(
         &self,
-        services: &ServicesContext,
+        telemetry: &dyn TelemetryInventory,
+        communication: &dyn CommunicationInventory,
     ) -> response::Statistics {
         let mut result = response::Statistics::new();
-        let response_metrics = services.telemetry.response_metrics();
-        let tile_handling_metrics = services.telemetry.tile_handling_metrics();
+        result.renderd_health = communication.renderd_health();
+        let response_metrics = telemetry.response_metrics();
+        let tile_handling_metrics = telemetry.tile_handling_metrics();
         for status_code in response_metrics.iterate_status_codes_responded() {
             let count = response_metrics.count_response_by_status_code(&status_code);
             match &status_code {
@@ -81,6 +139,18 @@ impl StatisticsHandlerState {
                 &StatusCode::INTERNAL_SERVER_ERROR => { result.number_response_5xx = count; },
                 _ => { result.number_response_other += count; }
             }
+            for window in TimeWindow::into_enum_iter() {
+                let windowed_count = response_metrics.count_response_by_status_code_in_window(&status_code, &window);
+                let windowed = result.windows.get_mut(window.label()).unwrap();
+                match &status_code {
+                    &StatusCode::OK => { windowed.number_response_200 = windowed_count; },
+                    &StatusCode::NOT_MODIFIED => { windowed.number_response_304 = windowed_count; },
+                    &StatusCode::NOT_FOUND => { windowed.number_response_404 = windowed_count; },
+                    &StatusCode::SERVICE_UNAVAILABLE => { windowed.number_response_503 = windowed_count; },
+                    &StatusCode::INTERNAL_SERVER_ERROR => { windowed.number_response_5xx = windowed_count; },
+                    _ => { windowed.number_response_other += windowed_count; }
+                }
+            }
         }
         result.number_fresh_cache = tile_handling_metrics.count_handled_tile_by_source_and_age(
             &TileSource::Cache,
@@ -122,11 +192,61 @@ impl StatisticsHandlerState {
             let count_404 = response_metrics.count_response_by_layer_and_status_code(&layer, &http::StatusCode::NOT_FOUND);
             result.number_response_404_by_layer.insert(String::from(layer.as_str()), count_404);
         }
+        for window in TimeWindow::into_enum_iter() {
+            let windowed = result.windows.get_mut(window.label()).unwrap();
+            windowed.number_fresh_cache = tile_handling_metrics.count_handled_tile_by_source_and_age_in_window(
+                &TileSource::Cache,
+                &TileAge::Fresh,
+                &window,
+            );
+            windowed.number_old_cache = tile_handling_metrics.count_handled_tile_by_source_and_age_in_window(
+                &TileSource::Cache,
+                &TileAge::Old,
+                &window,
+            );
+            windowed.number_very_old_cache = tile_handling_metrics.count_handled_tile_by_source_and_age_in_window(
+                &TileSource::Cache,
+                &TileAge::VeryOld,
+                &window,
+            );
+            windowed.number_fresh_render = tile_handling_metrics.count_handled_tile_by_source_and_age_in_window(
+                &TileSource::Render,
+                &TileAge::Fresh,
+                &window,
+            );
+            windowed.number_old_render = tile_handling_metrics.count_handled_tile_by_source_and_age_in_window(
+                &TileSource::Render,
+                &TileAge::Old,
+                &window,
+            );
+            windowed.number_very_old_render = tile_handling_metrics.count_handled_tile_by_source_and_age_in_window(
+                &TileSource::Render,
+                &TileAge::VeryOld,
+                &window,
+            );
+            windowed.total_number_tile_response = response_metrics.count_total_tile_response_in_window(&window);
+            windowed.total_duration_tile_response = response_metrics.tally_total_tile_response_duration_in_window(&window);
+        }
         return result;
     }
 }
 
 
+// Every byte is compared whatever the position of the first mismatch, so the time
+// taken does not reveal how much of the token a guess got right
+fn is_same_token(
+    credential: &[u8],
+    expected_token: &[u8],
+) -> bool {
+    let mut difference = credential.len() ^ expected_token.len();
+    for (index, expected_byte) in expected_token.iter().enumerate() {
+        let credential_byte = credential.get(index).copied().unwrap_or(!expected_byte);
+        difference |= (credential_byte ^ expected_byte) as usize;
+    }
+    return difference == 0;
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,7 +266,6 @@ mod tests {
         MockResponseMetrics, MockTileHandlingMetrics,
         ResponseMetrics, TileHandlingMetrics, TelemetryInventory
     };
-    use crate::service::rendering::interface::test_utils::NoOpRenderingInventory;
     use crate::service::telemetry::interface::test_utils::NoOpZeroTelemetryInventory;
     use crate::framework::apache2::record::test_utils::with_request_rec;
 
@@ -173,6 +292,7 @@ mod tests {
         write_observer_1: NoOpWriteResponseObserver,
         write_observer_2: NoOpWriteResponseObserver,
         write_observer_3: NoOpWriteResponseObserver,
+        reset_count: u32,
     }
 
     impl TelemetryInventoryWithMockedMetrics {
@@ -194,8 +314,35 @@ mod tests {
                 write_observer_1: NoOpWriteResponseObserver::new(),
                 write_observer_2: NoOpWriteResponseObserver::new(),
                 write_observer_3: NoOpWriteResponseObserver::new(),
+                reset_count: 0,
             }
         }
+
+        fn expect_zero_windowed_tile_handling(&mut self) -> () {
+            self.tile_handling_metrics.expect_count_handled_tile_by_source_and_age_in_window()
+                .times(TileSource::VARIANT_COUNT * TileAge::VARIANT_COUNT * TimeWindow::VARIANT_COUNT)
+                .returning(|_, _, _| { 0 });
+        }
+
+        fn expect_zero_metrics(&mut self) -> () {
+            self.response_metrics.expect_iterate_status_codes_responded()
+                .returning(|| { Vec::new() });
+            self.tile_handling_metrics.expect_count_handled_tile_by_source_and_age()
+                .returning(|_, _| { 0 });
+            self.response_metrics.expect_iterate_valid_zoom_levels()
+                .returning(|| { 0..0 });
+            self.response_metrics.expect_count_total_tile_response()
+                .returning(|| { 0 });
+            self.response_metrics.expect_tally_total_tile_response_duration()
+                .returning(|| { 0 });
+            self.response_metrics.expect_iterate_layers_responded()
+                .returning(|| { Vec::new() });
+            self.expect_zero_windowed_tile_handling();
+            self.response_metrics.expect_count_total_tile_response_in_window()
+                .returning(|_| { 0 });
+            self.response_metrics.expect_tally_total_tile_response_duration_in_window()
+                .returning(|_| { 0 });
+        }
     }
 
     impl TelemetryInventory for TelemetryInventoryWithMockedMetrics {
@@ -203,30 +350,34 @@ mod tests {
 
         fn tile_handling_metrics(&self) -> &dyn TileHandlingMetrics { &self.tile_handling_metrics }
 
-        fn read_request_observers(&mut self) -> [&mut dyn ReadRequestObserver; 2] {
-            [&mut self.read_observer_0, &mut self.read_observer_1]
+        fn read_request_observers(&mut self) -> Vec<&mut dyn ReadRequestObserver> {
+            vec![&mut self.read_observer_0, &mut self.read_observer_1]
         }
 
-        fn description_use_case_observers(&mut self) -> [&mut dyn DescriptionUseCaseObserver; 2] {
-            [&mut self.description_use_case_observer_0, &mut self.description_use_case_observer_1]
+        fn description_use_case_observers(&mut self) -> Vec<&mut dyn DescriptionUseCaseObserver> {
+            vec![&mut self.description_use_case_observer_0, &mut self.description_use_case_observer_1]
         }
 
-        fn statistics_use_case_observers(&mut self) -> [&mut dyn StatisticsUseCaseObserver; 2] {
-            [&mut self.statistics_use_case_observer_0, &mut self.statistics_use_case_observer_1]
+        fn statistics_use_case_observers(&mut self) -> Vec<&mut dyn StatisticsUseCaseObserver> {
+            vec![&mut self.statistics_use_case_observer_0, &mut self.statistics_use_case_observer_1]
         }
 
-        fn tile_use_case_observers(&mut self) -> [&mut dyn TileUseCaseObserver; 2] {
-            [&mut self.tile_use_case_observer_0, &mut self.tile_use_case_observer_1]
+        fn tile_use_case_observers(&mut self) -> Vec<&mut dyn TileUseCaseObserver> {
+            vec![&mut self.tile_use_case_observer_0, &mut self.tile_use_case_observer_1]
         }
 
-        fn write_response_observers(&mut self) -> [&mut dyn WriteResponseObserver; 4] {
-            [
+        fn write_response_observers(&mut self) -> Vec<&mut dyn WriteResponseObserver> {
+            vec![
                 &mut self.write_observer_0,
                 &mut self.write_observer_1,
                 &mut self.write_observer_2,
                 &mut self.write_observer_3,
             ]
         }
+
+        fn reset_metrics(&mut self) -> () {
+            self.reset_count += 1;
+        }
     }
 
     #[test]
@@ -236,7 +387,6 @@ mod tests {
         let layer_name = LayerName::from("default");
         let layer_config = module_config.layers.get(&layer_name).unwrap();
         let mut telemetry = TelemetryInventoryWithMockedMetrics::new();
-        let mut rendering = NoOpRenderingInventory::new();
         let mut communication = EmptyResultCommunicationInventory::new();
         let mut storage = BlankStorageInventory::new();
 
@@ -250,6 +400,21 @@ mod tests {
             .times(1)
             .returning(|_| { 5 });
 
+        telemetry.response_metrics.expect_count_response_by_status_code_in_window()
+            .with(eq(&StatusCode::OK), eq(&TimeWindow::OneMinute))
+            .times(1)
+            .returning(|_, _| { 1 });
+
+        telemetry.response_metrics.expect_count_response_by_status_code_in_window()
+            .with(eq(&StatusCode::OK), eq(&TimeWindow::FiveMinutes))
+            .times(1)
+            .returning(|_, _| { 3 });
+
+        telemetry.response_metrics.expect_count_response_by_status_code_in_window()
+            .with(eq(&StatusCode::OK), eq(&TimeWindow::OneHour))
+            .times(1)
+            .returning(|_, _| { 5 });
+
         telemetry.tile_handling_metrics.expect_count_handled_tile_by_source_and_age()
             .with(eq(&TileSource::Cache), eq(&TileAge::Fresh))
             .times(1)
@@ -331,6 +496,16 @@ mod tests {
             .times(1)
             .returning(move || { vec![layer_name_copy1] });
 
+        telemetry.expect_zero_windowed_tile_handling();
+
+        telemetry.response_metrics.expect_count_total_tile_response_in_window()
+            .times(TimeWindow::VARIANT_COUNT)
+            .returning(|window| { if *window == TimeWindow::OneMinute { 1 } else { 5 } });
+
+        telemetry.response_metrics.expect_tally_total_tile_response_duration_in_window()
+            .times(TimeWindow::VARIANT_COUNT)
+            .returning(|_| { 0 });
+
         telemetry.response_metrics.expect_count_response_by_layer_and_status_code()
             .with(eq(layer_name.clone()), eq(&StatusCode::OK))
             .times(1)
@@ -346,10 +521,8 @@ mod tests {
             record.uri = uri.clone().into_raw();
             let context = StatisticsContext {
                 host: HostContext::new(&module_config, record),
-                services: ServicesContext {
-                    telemetry: &telemetry,
-                    rendering: &mut rendering,
-                },
+                communication: &communication,
+                telemetry: &telemetry,
             };
             let header = request::Header {
                 layer: layer_name.clone(),
@@ -377,6 +550,15 @@ mod tests {
             expected_data.duration_tile_response_by_zoom[8] = 2;
             expected_data.number_response_200_by_layer.insert(String::from(layer_name.as_str()), 5);
             expected_data.number_response_404_by_layer.insert(String::from(layer_name.as_str()), 1);
+            let one_minute = expected_data.windows.get_mut(TimeWindow::OneMinute.label()).unwrap();
+            one_minute.number_response_200 = 1;
+            one_minute.total_number_tile_response = 1;
+            let five_minutes = expected_data.windows.get_mut(TimeWindow::FiveMinutes.label()).unwrap();
+            five_minutes.number_response_200 = 3;
+            five_minutes.total_number_tile_response = 5;
+            let one_hour = expected_data.windows.get_mut(TimeWindow::OneHour.label()).unwrap();
+            one_hour.number_response_200 = 5;
+            one_hour.total_number_tile_response = 5;
             let expected_response = response::SlippyResponse {
                 header: response::Header {
                     mime_type: mime::TEXT_PLAIN.clone(),
@@ -389,4 +571,73 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn test_reset_with_valid_credential() -> Result<(), Box<dyn StdError>> {
+        let mut module_config = ModuleConfig::new();
+        module_config.telemetry.statistics_reset_token = Some(String::from("secret"));
+        let handler_state = StatisticsHandlerState::new(&module_config)?;
+        let mut telemetry = TelemetryInventoryWithMockedMetrics::new();
+        let communication = EmptyResultCommunicationInventory::new();
+        telemetry.expect_zero_metrics();
+        with_request_rec(|record| {
+            let uri = CString::new("/mod_tile_rs/reset")?;
+            record.uri = uri.clone().into_raw();
+            let header = request::Header {
+                layer: LayerName::new(),
+                request_id: generate_id(),
+                uri: uri.into_string()?,
+                received_timestamp: Utc::now(),
+            };
+            let body = request::ResetStatisticsRequest {
+                credential: Some(String::from("secret")),
+            };
+            let mut context = StatisticsResetContext {
+                host: HostContext::new(&module_config, record),
+                communication: &communication,
+                telemetry: &mut telemetry,
+            };
+            let actual_response = handler_state.reset_statistics(&mut context, &header, &body)?;
+            assert_eq!(
+                response::BodyVariant::Statistics(response::Statistics::new()),
+                actual_response.body,
+                "Failed to report the reset statistics"
+            );
+            Ok(())
+        })?;
+        assert_eq!(1, telemetry.reset_count, "Failed to reset the metrics");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_with_invalid_credential() -> Result<(), Box<dyn StdError>> {
+        let mut module_config = ModuleConfig::new();
+        module_config.telemetry.statistics_reset_token = Some(String::from("secret"));
+        let handler_state = StatisticsHandlerState::new(&module_config)?;
+        let mut telemetry = TelemetryInventoryWithMockedMetrics::new();
+        let communication = EmptyResultCommunicationInventory::new();
+        with_request_rec(|record| {
+            let uri = CString::new("/mod_tile_rs/reset")?;
+            record.uri = uri.clone().into_raw();
+            let header = request::Header {
+                layer: LayerName::new(),
+                request_id: generate_id(),
+                uri: uri.into_string()?,
+                received_timestamp: Utc::now(),
+            };
+            let mut context = StatisticsResetContext {
+                host: HostContext::new(&module_config, record),
+                communication: &communication,
+                telemetry: &mut telemetry,
+            };
+            for credential in vec![None, Some(String::from("guess")), Some(String::from("secrets")), Some(String::from("secre"))] {
+                let body = request::ResetStatisticsRequest { credential };
+                let result = handler_state.reset_statistics(&mut context, &header, &body);
+                assert!(matches!(result, Err(HandleError::Forbidden(_))), "Failed to reject the credential");
+            }
+            Ok(())
+        })?;
+        assert_eq!(0, telemetry.reset_count, "Metrics were reset without a valid credential");
+        Ok(())
+    }
 }
@@ -1,30 +1,37 @@
 use crate::binding::renderd_protocol::{protoCmd, protoCmd_cmdIgnore, protocol};
-use crate::schema::apache2::config::ModuleConfig;
+use crate::schema::apache2::config::{ModuleConfig, RenderdConfig,};
 use crate::schema::apache2::error::InvalidConfigError;
-use crate::schema::handler::error::HandleError;
+use crate::schema::communication::error::CommunicationError;
+use crate::schema::handler::error::{HandleError, TimeoutError,};
+use crate::schema::renderd::error::RenderError;
 use crate::schema::apache2::virtual_host::VirtualHost;
 use crate::schema::slippy::request::{BodyVariant, Header, ServeTileRequest, SlippyRequest,};
 use crate::schema::slippy::response;
 use crate::schema::tile::age::TileAge;
 use crate::schema::tile::error::TileReadError;
-use crate::schema::tile::identity::TileIdentity;
+use crate::schema::tile::identity::{LayerName, TileIdentity,};
 use crate::schema::tile::source::TileSource;
+use crate::schema::tile::tile_ref::TileRef;
 use crate::io::interface::IOContext;
 use crate::framework::apache2::context::HostContext;
-use crate::service::interface::ServicesContext;
-use crate::service::rendering::interface::create_request;
+use crate::service::rendering::interface::{create_request, RenderingInventory,};
+use crate::service::rendering::status::data_import_completion_time;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc,};
 
 use std::any::type_name;
 use std::collections::HashMap;
 use std::result::Result;
+use std::sync::{Arc, Condvar, Mutex,};
+use std::time::{Duration, Instant,};
 
 
+// Unlike the other use cases there is no telemetry here, since the telemetry is shared by every thread
+// and must not stay locked while a tile renders
 pub struct TileContext<'c> {
     pub host: HostContext<'c>,
     pub io: IOContext<'c>,
-    pub services: ServicesContext<'c>,
+    pub rendering: &'c mut dyn RenderingInventory,
 }
 
 impl<'c> TileContext<'c> {
@@ -38,14 +45,56 @@ impl<'c> TileContext<'c> {
 }
 
 
+const VERY_OLD_THRESHOLD: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+// A render that requests for the other tiles of its meta tile wait on, rather than render the meta tile again
+struct PendingRender {
+    requested_time: Instant,
+    render_timeout: Duration,
+    is_complete: Mutex<bool>,
+    completion: Condvar,
+}
+
+impl PendingRender {
+    fn new(render_timeout: &Duration) -> PendingRender {
+        PendingRender {
+            requested_time: Instant::now(),
+            render_timeout: *render_timeout,
+            is_complete: Mutex::new(false),
+            completion: Condvar::new(),
+        }
+    }
+
+    // The request rendering the meta tile ends the render within its timeout, unless it never returned
+    fn is_expired(&self) -> bool {
+        self.requested_time.elapsed() > self.render_timeout
+    }
+
+    fn complete(&self) -> () {
+        *self.is_complete.lock().unwrap() = true;
+        self.completion.notify_all();
+    }
+
+    fn wait(
+        &self,
+        timeout: &Duration,
+    ) -> () {
+        let is_complete = self.is_complete.lock().unwrap();
+        let _ = self.completion.wait_timeout_while(is_complete, *timeout, |is_complete| !*is_complete).unwrap();
+    }
+}
+
+// Clones share the outstanding renders, since a request on any thread may be for a meta tile already being rendered
+#[derive(Clone)]
 pub struct TileHandlerState {
-    render_requests_by_tile_id: HashMap<TileIdentity, i32>,
+    // renderd renders every tile of a meta tile together, so outstanding renders are tracked per meta tile
+    render_requests_by_meta_tile: Arc<Mutex<HashMap<TileIdentity, Arc<PendingRender>>>>,
 }
 
 impl TileHandlerState {
-    pub fn new(config: &ModuleConfig) -> Result<TileHandlerState, InvalidConfigError> {
+    pub fn new(_config: &ModuleConfig) -> Result<TileHandlerState, InvalidConfigError> {
         let value = TileHandlerState {
-            render_requests_by_tile_id: HashMap::new(),
+            render_requests_by_meta_tile: Arc::new(Mutex::new(HashMap::new())),
         };
         return Ok(value);
     }
@@ -61,73 +110,648 @@ impl TileHandlerState {
         body: &ServeTileRequest,
     ) -> Result<response::SlippyResponse, HandleError> {
         let before_timestamp = Utc::now();
-        let tile_id = match body {
-            ServeTileRequest::V2(body) => TileIdentity {
-                x: body.x,
-                y: body.y,
-                z: body.z,
-                layer: header.layer.clone(),
-            },
-            ServeTileRequest::V3(body) => TileIdentity {
-                x: body.x,
-                y: body.y,
-                z: body.z,
-                layer: header.layer.clone(),
-            },
-        };
+        let tile_id = requested_tile(header, body);
         // First preference is to fetch the tile from storage if it is available
         let read_result = {
             let primary_store = context.io.storage.primary_tile_store();
             primary_store.read_tile(&context.host, &tile_id)
         };
-        let tile_ref = match read_result {
-            Ok(tile) => tile,
-            Err(TileReadError::NotFound(tile_path)) => {
-                // Second preference is to render the tile
-                // TODO: calculate the rendering priority
-                let request = create_request(
-                    &context.module_config().renderd,
-                    header,
-                    body,
-                );
-                let mut response = protocol {
-                    ver: 0 as std::os::raw::c_int,
-                    cmd: protoCmd_cmdIgnore,
-                    x: 0 as std::os::raw::c_int,
-                    y: 0 as std::os::raw::c_int,
-                    z: 0 as std::os::raw::c_int,
-                    xmlname: [0; 41usize],
-                    mimetype: [0; 41usize],
-                    options: [0; 41usize],
-                };
-                match context.services.rendering.tile_renderer().render_tile(
-                    &mut context.io,
-                    tile_id,
-                    &request,
-                    &mut response,
-                    1,  // TODO: calculate the priority
-                ) {
-                    Ok(tile_ref) => tile_ref,
-                    Err(err) => return Err(HandleError::Render(err)),
-                }
+        let cached_tile = match read_result {
+            Ok(tile_ref) => {
+                let age = calc_tile_age(&context.module_config().renderd, &header.layer, &tile_ref);
+                Some((tile_ref, age))
             },
+            Err(TileReadError::NotFound(_)) => None,
             Err(other) => return Err(HandleError::TileRead(other)),
         };
-        let after_timestamp = Utc::now();
-        let response = response::SlippyResponse {
-            header: response::Header {
-                mime_type: tile_ref.media_type.clone(),
-                before_timestamp,
-                after_timestamp,
-            },
-            body: response::BodyVariant::Tile(
-                response::TileResponse {
-                    source: TileSource::Cache,
-                    age: TileAge::Fresh,
-                    tile_ref,
+        let (tile_ref, source, age) = match cached_tile {
+            Some((tile_ref, TileAge::Fresh)) => (tile_ref, TileSource::Cache, TileAge::Fresh),
+            _ => {
+                // Second preference is to render the tile, allowing more time when there is nothing to fall back on
+                let renderd_config = &context.module_config().renderd;
+                let render_timeout = match cached_tile {
+                    Some(_) => renderd_config.render_timeout,
+                    None => renderd_config.missing_render_timeout,
+                };
+                let meta_tile = tile_id.meta_tile();
+                let render_result = match self.begin_render(&meta_tile, &render_timeout) {
+                    Some(pending_render) => {
+                        // The render already queued for the meta tile will store this tile too,
+                        // so the tile is served from storage once it completes rather than rendered again
+                        debug!(
+                            context.host().record,
+                            "TileHandlerState::fetch_tile - waiting on pending render of meta tile {}/{}/{}",
+                            meta_tile.z, meta_tile.x, meta_tile.y,
+                        );
+                        pending_render.wait(&render_timeout);
+                        read_rendered_tile(context, &header.layer, &tile_id, &render_timeout)
+                    },
+                    None => {
+                        let render_result = render_tile(context, body, &tile_id, &render_timeout);
+                        // Even when the render failed or timed out, the waiting requests can serve what is stored now
+                        self.end_render(&meta_tile);
+                        render_result.map_err(|render_err| render_error(&tile_id, &render_timeout, render_err))
+                    },
+                };
+                match render_result {
+                    Ok(tile_ref) => {
+                        let age = calc_tile_age(&context.module_config().renderd, &header.layer, &tile_ref);
+                        (tile_ref, TileSource::Render, age)
+                    },
+                    Err(handle_err) => {
+                        match cached_tile {
+                            Some((tile_ref, age)) => {
+                                // Last preference is to serve the stale tile rather than nothing
+                                warn!(
+                                    context.host().record,
+                                    "TileHandlerState::fetch_tile - serving stale tile because rendering failed: {}",
+                                    handle_err
+                                );
+                                (tile_ref, TileSource::Cache, age)
+                            },
+                            None => return Err(handle_err),
+                        }
+                    },
                 }
+            },
+        };
+        return Ok(tile_response(tile_ref, source, age, before_timestamp));
+    }
+
+    // Records the render of the meta tile as pending, unless a render of it is pending already,
+    // in which case that render is returned to wait on
+    fn begin_render(
+        &self,
+        meta_tile: &TileIdentity,
+        render_timeout: &Duration,
+    ) -> Option<Arc<PendingRender>> {
+        let mut render_requests_by_meta_tile = self.render_requests_by_meta_tile.lock().unwrap();
+        if let Some(pending_render) = render_requests_by_meta_tile.get(meta_tile) {
+            if !pending_render.is_expired() {
+                return Some(pending_render.clone());
+            }
+            // Nothing else will end the expired render, so the requests still waiting on it are woken here
+            pending_render.complete();
+        }
+        render_requests_by_meta_tile.insert(meta_tile.clone(), Arc::new(PendingRender::new(render_timeout)));
+        return None;
+    }
+
+    // Wakes the requests waiting on the render of the meta tile
+    fn end_render(
+        &self,
+        meta_tile: &TileIdentity,
+    ) -> () {
+        if let Some(pending_render) = self.render_requests_by_meta_tile.lock().unwrap().remove(meta_tile) {
+            pending_render.complete();
+        }
+    }
+}
+
+// The pending render may have stored the tile even if it took longer than the wait for it. A tile that is still
+// stale means the render failed, so the caller falls back to the stale tile it has already read.
+fn read_rendered_tile(
+    context: &mut TileContext,
+    layer: &LayerName,
+    tile_id: &TileIdentity,
+    render_timeout: &Duration,
+) -> Result<TileRef, HandleError> {
+    let primary_store = context.io.storage.primary_tile_store();
+    match primary_store.read_tile(&context.host, tile_id) {
+        Ok(tile_ref) => match calc_tile_age(&context.module_config().renderd, layer, &tile_ref) {
+            TileAge::Fresh => Ok(tile_ref),
+            _ => Err(timeout_error(tile_id, render_timeout)),
+        },
+        Err(TileReadError::NotFound(_)) => Err(timeout_error(tile_id, render_timeout)),
+        Err(other) => Err(HandleError::TileRead(other)),
+    }
+}
+
+fn render_error(
+    tile_id: &TileIdentity,
+    render_timeout: &Duration,
+    error: RenderError,
+) -> HandleError {
+    match error {
+        RenderError::Communication(CommunicationError::TimeoutError) => timeout_error(tile_id, render_timeout),
+        other => HandleError::Render(other),
+    }
+}
+
+fn timeout_error(
+    tile_id: &TileIdentity,
+    render_timeout: &Duration,
+) -> HandleError {
+    // renderd doesn't report its queue depth, so the retry delay is a fixed estimate of one more render timeout
+    let retry_after = render_timeout.as_secs().max(1);
+    HandleError::Timeout(
+        TimeoutError {
+            threshold: render_timeout.as_secs(),
+            retry_after,
+            reason: format!(
+                "Rendering tile {}/{}/{} of layer {} timed out",
+                tile_id.z, tile_id.x, tile_id.y, tile_id.layer,
             ),
+        }
+    )
+}
+
+pub fn requested_tile(
+    header: &Header,
+    body: &ServeTileRequest,
+) -> TileIdentity {
+    match body {
+        ServeTileRequest::V2(body) => TileIdentity {
+            x: body.x,
+            y: body.y,
+            z: body.z,
+            layer: header.layer.clone(),
+            parameter: None,
+        },
+        ServeTileRequest::V3(body) => TileIdentity {
+            x: body.x,
+            y: body.y,
+            z: body.z,
+            layer: header.layer.clone(),
+            parameter: Some(body.parameter.clone()),
+        },
+    }
+}
+
+fn tile_response(
+    tile_ref: TileRef,
+    source: TileSource,
+    age: TileAge,
+    before_timestamp: DateTime<Utc>,
+) -> response::SlippyResponse {
+    let after_timestamp = Utc::now();
+    response::SlippyResponse {
+        header: response::Header {
+            mime_type: tile_ref.media_type.clone(),
+            before_timestamp,
+            after_timestamp,
+        },
+        body: response::BodyVariant::Tile(
+            response::TileResponse {
+                source,
+                age,
+                tile_ref,
+            }
+        ),
+    }
+}
+
+fn render_tile(
+    context: &mut TileContext,
+    body: &ServeTileRequest,
+    tile_id: &TileIdentity,
+    render_timeout: &Duration,
+) -> Result<TileRef, RenderError> {
+    // TODO: calculate the rendering priority
+    let request = create_request(
+        &context.module_config().renderd,
+        tile_id,
+        body,
+    )?;
+    let mut response = protocol {
+        ver: 0 as std::os::raw::c_int,
+        cmd: protoCmd_cmdIgnore,
+        x: 0 as std::os::raw::c_int,
+        y: 0 as std::os::raw::c_int,
+        z: 0 as std::os::raw::c_int,
+        xmlname: [0; 41usize],
+        mimetype: [0; 41usize],
+        options: [0; 41usize],
+    };
+    context.rendering.tile_renderer().render_tile(
+        &context.host,
+        &mut context.io,
+        tile_id.clone(),
+        &request,
+        &mut response,
+        1,  // TODO: calculate the priority
+        render_timeout,
+    )
+}
+
+fn calc_tile_age(
+    config: &RenderdConfig,
+    layer: &LayerName,
+    tile_ref: &TileRef,
+) -> TileAge {
+    // Tiles rendered before the last data import are out of date
+    let import_time = data_import_completion_time(config, layer);
+    match (tile_ref.modified_time, import_time) {
+        (Some(modified_time), Some(import_time)) if modified_time < import_time => {
+            match import_time.duration_since(modified_time) {
+                Ok(staleness) if staleness > VERY_OLD_THRESHOLD => TileAge::VeryOld,
+                _ => TileAge::Old,
+            }
+        },
+        _ => TileAge::Fresh,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::identifier::generate_id;
+    use crate::schema::http::encoding::ContentEncoding;
+    use crate::schema::slippy::request::ServeTileRequestV2;
+    use crate::io::communication::interface::test_utils::EmptyResultCommunicationInventory;
+    use crate::io::communication::renderd_socket::test_utils::{MockRenderd, MockReply,};
+    use crate::io::communication::state::CommunicationState;
+    use crate::io::storage::interface::{StorageInventory, TileStorage,};
+    use crate::io::storage::meta_tile::test_utils::generated_tile;
+    use crate::io::storage::state::StorageState;
+    use crate::schema::renderd::response::RenderResponseCommand;
+    use crate::service::rendering::interface::{RenderingInventory, TileRenderer,};
+    use crate::service::rendering::inventory::RenderingState;
+    use crate::framework::apache2::record::test_utils::with_request_rec;
+
+    use std::cell::RefCell;
+    use std::error::Error as StdError;
+    use std::fs::{create_dir_all, File,};
+    use std::path::PathBuf;
+    use std::string::String;
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::{SystemTime, UNIX_EPOCH,};
+    use std::vec::Vec;
+
+    struct StubTileStorage {
+        modified_time: Option<SystemTime>,
+    }
+
+    impl TileStorage for StubTileStorage {
+        fn read_tile(
+            &mut self,
+            _context: &HostContext,
+            _id: &TileIdentity,
+        ) -> Result<TileRef, TileReadError> {
+            match self.modified_time {
+                Some(modified_time) => Ok(
+                    TileRef {
+                        raw_bytes: RefCell::new(Vec::new()),
+                        begin: 0,
+                        end: 0,
+                        media_type: mime::IMAGE_PNG,
+                        encoding: ContentEncoding::NotCompressed,
+                        modified_time: Some(modified_time),
+                    }
+                ),
+                None => Err(TileReadError::NotFound(PathBuf::from("/var/cache/renderd/default/0/0/0/0/0/0.meta"))),
+            }
+        }
+
+        fn clean_up(&mut self) -> () {
+        }
+    }
+
+    impl StorageInventory for StubTileStorage {
+        fn primary_tile_store(&mut self) -> &mut dyn TileStorage {
+            self
+        }
+    }
+
+    struct TimeoutTileRenderer {
+        render_count: u32,
+    }
+
+    impl TileRenderer for TimeoutTileRenderer {
+        fn render_tile(
+            &mut self,
+            _context: &HostContext,
+            _io: &mut IOContext,
+            _tile_id: TileIdentity,
+            _request: &protocol,
+            _response: &mut protocol,
+            _priority: u8,
+            _timeout: &Duration,
+        ) -> Result<TileRef, RenderError> {
+            self.render_count += 1;
+            Err(RenderError::Communication(CommunicationError::TimeoutError))
+        }
+    }
+
+    impl RenderingInventory for TimeoutTileRenderer {
+        fn tile_renderer(&mut self) -> &mut dyn TileRenderer {
+            self
+        }
+    }
+
+    fn make_config(store_dir: &PathBuf) -> Result<ModuleConfig, Box<dyn StdError>> {
+        let mut module_config = ModuleConfig::new();
+        module_config.renderd.store_uri = String::from(store_dir.to_str().unwrap());
+        module_config.renderd.render_timeout = Duration::from_secs(3);
+        module_config.renderd.missing_render_timeout = Duration::from_secs(10);
+        let layer_dir = store_dir.join(LayerName::new().as_str());
+        create_dir_all(&layer_dir)?;
+        File::create(layer_dir.join("planet-import-complete"))?;
+        Ok(module_config)
+    }
+
+    fn make_request() -> (Header, ServeTileRequest) {
+        make_tile_request(2, 3)
+    }
+
+    fn make_tile_request(
+        x: i32,
+        y: i32,
+    ) -> (Header, ServeTileRequest) {
+        let header = Header {
+            layer: LayerName::new(),
+            request_id: generate_id(),
+            uri: format!("/osm/9/{}/{}.png", x, y),
+            received_timestamp: Utc::now(),
         };
-        return Ok(response);
+        let body = ServeTileRequest::V2(
+            ServeTileRequestV2 {
+                x,
+                y,
+                z: 9,
+                extension: String::from("png"),
+                option: None,
+            }
+        );
+        (header, body)
+    }
+
+    #[test]
+    fn test_stale_tile_served_on_render_timeout() -> Result<(), Box<dyn StdError>> {
+        let store_dir = mktemp::Temp::new_dir()?;
+        let module_config = make_config(&store_dir.to_path_buf())?;
+        let mut handler_state = TileHandlerState::new(&module_config)?;
+        let mut comms = EmptyResultCommunicationInventory::new();
+        let mut storage = StubTileStorage { modified_time: Some(UNIX_EPOCH) };
+        let mut rendering = TimeoutTileRenderer { render_count: 0 };
+        with_request_rec(|record| {
+            let mut context = TileContext {
+                host: HostContext::new(&module_config, record),
+                io: IOContext {
+                    communication: &mut comms,
+                    storage: &mut storage,
+                },
+                rendering: &mut rendering,
+            };
+            let (header, body) = make_request();
+            let response = handler_state.fetch_tile(&mut context, &header, &body)?;
+            match response.body {
+                response::BodyVariant::Tile(tile) => {
+                    assert_eq!(TileSource::Cache, tile.source, "Failed to serve the cached tile");
+                    assert_eq!(TileAge::VeryOld, tile.age, "Failed to report the age of the stale tile");
+                },
+                _ => panic!("Expected a tile response"),
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_timeout_without_cached_tile() -> Result<(), Box<dyn StdError>> {
+        let store_dir = mktemp::Temp::new_dir()?;
+        let module_config = make_config(&store_dir.to_path_buf())?;
+        let mut handler_state = TileHandlerState::new(&module_config)?;
+        let mut comms = EmptyResultCommunicationInventory::new();
+        let mut storage = StubTileStorage { modified_time: None };
+        let mut rendering = TimeoutTileRenderer { render_count: 0 };
+        with_request_rec(|record| {
+            let mut context = TileContext {
+                host: HostContext::new(&module_config, record),
+                io: IOContext {
+                    communication: &mut comms,
+                    storage: &mut storage,
+                },
+                rendering: &mut rendering,
+            };
+            let (header, body) = make_request();
+            match handler_state.fetch_tile(&mut context, &header, &body) {
+                Err(HandleError::Timeout(timeout)) => {
+                    assert_eq!(10, timeout.threshold, "Failed to apply the missing tile render timeout");
+                    assert_eq!(10, timeout.retry_after, "Failed to estimate the retry delay");
+                },
+                _ => panic!("Expected a timeout error"),
+            }
+            Ok(())
+        })
+    }
+
+    fn fetch_from_mock_renderd(
+        reply: MockReply,
+        requests: &[(i32, i32)],
+    ) -> Result<(Vec<Result<response::SlippyResponse, HandleError>>, Vec<TileIdentity>), Box<dyn StdError>> {
+        let store_dir = mktemp::Temp::new_dir()?;
+        let mut module_config = make_config(&store_dir.to_path_buf())?;
+        module_config.renderd.missing_render_timeout = Duration::from_millis(500);
+        let renderd = MockRenderd::start(&module_config, reply)?;
+        module_config.renderd.ipc_uri = String::from(renderd.socket_path().to_str().unwrap());
+        let mut handler_state = TileHandlerState::new(&module_config)?;
+        let mut comms = CommunicationState::new(&module_config)?;
+        let mut storage = StorageState::new(&module_config)?;
+        let mut rendering = RenderingState::new(&module_config)?;
+        let mut responses = Vec::new();
+        with_request_rec(|record| {
+            let mut context = TileContext {
+                host: HostContext::new(&module_config, record),
+                io: IOContext {
+                    communication: &mut comms,
+                    storage: &mut storage,
+                },
+                rendering: &mut rendering,
+            };
+            for (x, y) in requests {
+                let (header, body) = make_tile_request(*x, *y);
+                responses.push(handler_state.fetch_tile(&mut context, &header, &body));
+            }
+            Ok(())
+        })?;
+        Ok((responses, renderd.requests()))
+    }
+
+    // Every request is on its own thread, like Apache's, and they all start together
+    fn fetch_concurrently_from_mock_renderd<F>(
+        reply: MockReply,
+        requests: &[(i32, i32)],
+        check_response: F,
+    ) -> Result<Vec<TileIdentity>, Box<dyn StdError>>
+    where F: Fn((i32, i32), Result<response::SlippyResponse, HandleError>) -> () + Sync {
+        let store_dir = mktemp::Temp::new_dir()?;
+        let mut module_config = make_config(&store_dir.to_path_buf())?;
+        module_config.renderd.missing_render_timeout = Duration::from_millis(500);
+        let renderd = MockRenderd::start(&module_config, reply)?;
+        module_config.renderd.ipc_uri = String::from(renderd.socket_path().to_str().unwrap());
+        let handler_state = TileHandlerState::new(&module_config)?;
+        let comms = CommunicationState::new(&module_config)?;
+        let start = Barrier::new(requests.len());
+        thread::scope(|scope| {
+            for (x, y) in requests {
+                let mut handler_state = handler_state.clone();
+                let mut comms = comms.clone();
+                let (module_config, start, check_response) = (&module_config, &start, &check_response);
+                scope.spawn(move || {
+                    let mut storage = StorageState::new(module_config).unwrap();
+                    let mut rendering = RenderingState::new(module_config).unwrap();
+                    with_request_rec(|record| {
+                        let mut context = TileContext {
+                            host: HostContext::new(module_config, record),
+                            io: IOContext {
+                                communication: &mut comms,
+                                storage: &mut storage,
+                            },
+                            rendering: &mut rendering,
+                        };
+                        let (header, body) = make_tile_request(*x, *y);
+                        start.wait();
+                        check_response((*x, *y), handler_state.fetch_tile(&mut context, &header, &body));
+                        Ok(())
+                    }).unwrap();
+                });
+            }
+        });
+        Ok(renderd.requests())
+    }
+
+    fn assert_rendered_tile(
+        (x, y): (i32, i32),
+        response: Result<response::SlippyResponse, HandleError>,
+    ) -> () {
+        match response {
+            Ok(response::SlippyResponse { body: response::BodyVariant::Tile(tile), .. }) => {
+                let expected = generated_tile(&TileIdentity { x, y, z: 9, layer: LayerName::new(), parameter: None, });
+                tile.tile_ref.with_tile(|raw_bytes| {
+                    assert_eq!(expected, raw_bytes.to_vec(), "Failed to serve the rendered tile {}/{}", x, y);
+                });
+            },
+            Ok(_) => panic!("Expected a tile response"),
+            Err(err) => panic!("Failed to serve tile {}/{}: {}", x, y, err),
+        }
+    }
+
+    #[test]
+    fn test_coalesce_misses_in_one_meta_tile() -> Result<(), Box<dyn StdError>> {
+        // Every tile from 0/0 to 7/7 is in the same meta tile
+        let requests = fetch_concurrently_from_mock_renderd(
+            MockReply::Delay(Duration::from_millis(200)),
+            &[(2, 3), (0, 0), (7, 7), (2, 3), (8, 3)],
+            assert_rendered_tile,
+        )?;
+        let mut meta_tiles: Vec<(i32, i32)> = requests.iter().map(|tile| (tile.x / 8, tile.y / 8)).collect();
+        meta_tiles.sort();
+        assert_eq!(vec![(0, 0), (1, 0)], meta_tiles, "Failed to render each meta tile once");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_missing_tile() -> Result<(), Box<dyn StdError>> {
+        let (mut responses, requests) = fetch_from_mock_renderd(MockReply::Done, &[(2, 3), (5, 1)])?;
+        assert_eq!(1, requests.len(), "Failed to serve the rest of the meta tile from storage");
+        assert_eq!((2, 3, 9), (requests[0].x, requests[0].y, requests[0].z), "Failed to request the missing tile");
+        let expected_sources = vec![TileSource::Render, TileSource::Cache];
+        for ((x, y), expected_source) in vec![(2, 3), (5, 1)].into_iter().zip(expected_sources) {
+            match responses.remove(0)?.body {
+                response::BodyVariant::Tile(tile) => {
+                    assert_eq!(expected_source, tile.source, "Incorrect tile source");
+                    let expected = generated_tile(&TileIdentity { x, y, z: 9, layer: LayerName::new(), parameter: None, });
+                    tile.tile_ref.with_tile(|raw_bytes| {
+                        assert_eq!(expected, raw_bytes.to_vec(), "Failed to serve the rendered tile");
+                    });
+                },
+                _ => panic!("Expected a tile response"),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_not_done() -> Result<(), Box<dyn StdError>> {
+        let (responses, _) = fetch_from_mock_renderd(MockReply::NotDone, &[(2, 3)])?;
+        assert!(
+            matches!(
+                responses[0],
+                Err(HandleError::Render(RenderError::NotRendered(RenderResponseCommand::NotDone)))
+            ),
+            "Failed to report the failed render"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_timeout_from_renderd() -> Result<(), Box<dyn StdError>> {
+        // The first request gives up after 500ms, which ends its render, so the second request renders again
+        // rather than waiting on a render that nothing will end
+        let (responses, requests) = fetch_from_mock_renderd(
+            MockReply::Delay(Duration::from_millis(800)),
+            &[(2, 3), (0, 0)],
+        )?;
+        assert!(matches!(responses[0], Err(HandleError::Timeout(_))), "Failed to time out the render");
+        assert!(matches!(responses[1], Err(HandleError::Timeout(_))), "Failed to time out the second render");
+        assert_eq!(2, requests.len(), "Failed to end the render that timed out");
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_tile_served_after_pending_render() -> Result<(), Box<dyn StdError>> {
+        let store_dir = mktemp::Temp::new_dir()?;
+        let module_config = make_config(&store_dir.to_path_buf())?;
+        let mut handler_state = TileHandlerState::new(&module_config)?;
+        let mut comms = EmptyResultCommunicationInventory::new();
+        let mut storage = StubTileStorage { modified_time: Some(UNIX_EPOCH) };
+        let mut rendering = TimeoutTileRenderer { render_count: 0 };
+        let (header, body) = make_request();
+        let meta_tile = requested_tile(&header, &body).meta_tile();
+        // The render that the request waits on has ended without storing a fresh tile
+        handler_state.begin_render(&meta_tile, &module_config.renderd.render_timeout);
+        handler_state.render_requests_by_meta_tile.lock().unwrap().get(&meta_tile).unwrap().complete();
+        with_request_rec(|record| {
+            let mut context = TileContext {
+                host: HostContext::new(&module_config, record),
+                io: IOContext {
+                    communication: &mut comms,
+                    storage: &mut storage,
+                },
+                rendering: &mut rendering,
+            };
+            match handler_state.fetch_tile(&mut context, &header, &body)?.body {
+                response::BodyVariant::Tile(tile) => {
+                    assert_eq!(TileSource::Cache, tile.source, "Incorrect source of the stale tile");
+                    assert_eq!(TileAge::VeryOld, tile.age, "Incorrect age of the stale tile");
+                },
+                _ => panic!("Expected a tile response"),
+            }
+            Ok(())
+        })?;
+        assert_eq!(0, rendering.render_count, "Rendered the meta tile again while it was pending");
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired_pending_render_wakes_waiters() -> Result<(), Box<dyn StdError>> {
+        let module_config = ModuleConfig::new();
+        let handler_state = TileHandlerState::new(&module_config)?;
+        let (header, body) = make_request();
+        let meta_tile = requested_tile(&header, &body).meta_tile();
+        assert!(handler_state.begin_render(&meta_tile, &Duration::from_millis(0)).is_none(), "Failed to begin the render");
+        let expired_render = handler_state.render_requests_by_meta_tile.lock().unwrap().get(&meta_tile).unwrap().clone();
+        thread::sleep(Duration::from_millis(10));
+        assert!(
+            handler_state.begin_render(&meta_tile, &Duration::from_secs(1)).is_none(),
+            "Failed to replace the expired render"
+        );
+        assert!(*expired_render.is_complete.lock().unwrap(), "Failed to wake the requests waiting on the expired render");
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait_on_pending_render_is_bounded() -> Result<(), Box<dyn StdError>> {
+        let requests = fetch_concurrently_from_mock_renderd(
+            MockReply::Delay(Duration::from_secs(2)),
+            &[(2, 3), (0, 0)],
+            |_, response| {
+                assert!(matches!(response, Err(HandleError::Timeout(_))), "Failed to time out the wait");
+            },
+        )?;
+        assert_eq!(1, requests.len(), "Failed to wait on the pending render");
+        Ok(())
     }
 }